@@ -1,36 +1,54 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use cosmic::iced::widget::image::Handle as ImageHandle;
 use cosmic::iced::{Alignment, Length, Subscription};
 use cosmic::prelude::*;
 use cosmic::{executor, widget, Core};
-use matrix_sdk::ruma::events::room::message::RoomMessageEventContent;
-use matrix_sdk::ruma::events::AnySyncTimelineEvent;
-use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::ruma::events::room::message::{MessageType, RoomMessageEventContent};
+use matrix_sdk::ruma::events::Mentions;
+use matrix_sdk::ruma::{OwnedRoomAliasId, OwnedRoomId, OwnedServerName};
 use matrix_sdk::ruma::OwnedUserId;
 use matrix_sdk::Client;
+use matrix_sdk_ui::timeline::Timeline;
 
 use mime_guess;
 
-use crate::config::{self, SortMode};
+use crate::config::{self, AuthKind, SortMode};
 use crate::matrix;
+use crate::matrix::commands::Command;
 use crate::matrix::verification as matrix_verification;
 use crate::message::{
-    CrossSigningStatus, LoginSuccess, MatrixClient, Message, TimelineItem, VerificationInfo,
-    VerificationPhase, VerificationStateUpdate,
+    CrossSigningStatus, LoginSuccess, MatrixClient, MatrixTimeline, Message, RestoredAccount,
+    SettingsChange, TimelineItem, VerificationInfo, VerificationPhase, VerificationStateUpdate,
 };
 use matrix_sdk::media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings};
 use matrix_sdk::ruma::UInt;
-use crate::state::rooms::RoomsState;
-use crate::state::timeline::TimelineState;
-use crate::ui::login::{self, LoginState};
+use crate::state::accounts::Account;
+use crate::state::create::{CreateRoomState, NewDmState};
+use crate::state::join::JoinState;
+use crate::state::room_settings::RoomSettingsState;
+use crate::state::rooms::{LeaveConfirm, RoomsState};
+use crate::state::timeline::{Devtools, EditHistory, ThreadState, TimelineState, PREVIEW_DELAY};
+use crate::ui::login::{self, LoginState, ReauthTarget};
 use crate::ui::timeline::TIMELINE_SCROLLABLE_ID;
-use crate::ui::{composer, room_header, timeline as timeline_ui};
+use crate::ui::{
+    composer, create, invite, join, leave, room_header, room_settings, timeline as timeline_ui,
+};
 use crate::ui::verification as verification_ui;
 use crate::ui::profile as profile_ui;
 use cosmic::iced::widget::scrollable::{snap_to, RelativeOffset};
 
+/// Number of events requested per back-pagination.
+const HISTORY_PAGE_SIZE: u16 = 50;
+/// History pages fetched looking for the fully-read marker before giving up.
+const MAX_JUMP_PAGES: usize = 20;
+/// How long pending writes, such as drafts and the fully-read marker, wait to be batched.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// Pause in typing before the user directory is searched.
+const DM_SEARCH_DELAY: Duration = Duration::from_millis(400);
+
 enum AppView {
     Loading,
     Login,
@@ -43,10 +61,14 @@ pub struct App {
     login_state: LoginState,
     login_password: String,
     own_user_id: Option<OwnedUserId>,
+    /// Room list of the active account.
     rooms_state: RoomsState,
     timeline_state: TimelineState,
+    /// Client of the active account.
     client: Option<Arc<Client>>,
-    homeserver: String,
+    /// All signed-in accounts, including the active one.
+    accounts: Vec<Account>,
+    active_account: Option<String>,
     cross_signing_status: CrossSigningStatus,
     active_verification: Option<VerificationInfo>,
     pending_incoming: Option<(String, String)>, // (flow_id, sender)
@@ -58,8 +80,20 @@ pub struct App {
     own_avatar: Option<ImageHandle>,
     /// Whether the profile panel is visible.
     show_profile_panel: bool,
+    /// "Join room" dialog, when open.
+    join_dialog: Option<JoinState>,
+    create_room_dialog: Option<CreateRoomState>,
+    new_dm_dialog: Option<NewDmState>,
+    /// Settings panel for the open room, when shown.
+    room_settings: Option<RoomSettingsState>,
+    /// Account problem to tell the user about, e.g. a session the server ended.
+    account_notice: Option<String>,
     /// Suppresses notifications until the initial sync is complete.
     initial_sync_done: bool,
+    /// Whether to tell rooms when we are typing.
+    send_typing_notifications: bool,
+    /// Send `m.read.private` receipts so others don't see our read position.
+    private_read_receipts: bool,
 }
 
 impl cosmic::Application for App {
@@ -80,7 +114,7 @@ impl cosmic::Application for App {
     fn init(mut core: Core, _flags: Self::Flags) -> (Self, cosmic::app::Task<Self::Message>) {
         core.window.content_container = false;
 
-        let has_session = config::load_session().is_some();
+        let has_session = config::has_session();
         let settings = config::load_settings();
 
         let app = App {
@@ -101,7 +135,8 @@ impl cosmic::Application for App {
             },
             timeline_state: TimelineState::default(),
             client: None,
-            homeserver: String::new(),
+            accounts: Vec::new(),
+            active_account: None,
             cross_signing_status: CrossSigningStatus::Unknown,
             active_verification: None,
             pending_incoming: None,
@@ -109,7 +144,14 @@ impl cosmic::Application for App {
             avatars: HashMap::new(),
             own_avatar: None,
             show_profile_panel: false,
+            join_dialog: None,
+            create_room_dialog: None,
+            new_dm_dialog: None,
+            room_settings: None,
+            account_notice: None,
             initial_sync_done: false,
+            send_typing_notifications: settings.send_typing_notifications,
+            private_read_receipts: settings.private_read_receipts,
         };
 
         let task = if has_session {
@@ -138,22 +180,90 @@ impl cosmic::Application for App {
             }
 
             // -- Login --
-            Message::HomeserverChanged(val) => self.login_state.homeserver = val,
+            Message::HomeserverChanged(val) => {
+                self.login_state.homeserver = val;
+                self.login_state.flows = None;
+            }
             Message::UsernameChanged(val) => self.login_state.username = val,
             Message::PasswordChanged(val) => self.login_state.password = val,
             Message::TogglePasswordVisibility => {
                 self.login_state.password_visible = !self.login_state.password_visible;
             }
+            Message::DiscoverLoginFlows => {
+                if self.login_state.discovering || self.login_state.homeserver.is_empty() {
+                    return Task::none();
+                }
+                self.login_state.discovering = true;
+                self.login_state.error = None;
+                let homeserver = self.login_state.homeserver.clone();
+                return cosmic::task::future(async move {
+                    Message::LoginFlowsDiscovered(
+                        matrix::client::discover_login_flows(&homeserver).await,
+                    )
+                });
+            }
+            Message::LoginFlowsDiscovered(result) => {
+                self.login_state.discovering = false;
+                match result {
+                    Ok(flows) => self.login_state.flows = Some(flows),
+                    Err(e) => self.login_state.error = Some(e),
+                }
+            }
+            Message::LoginSso(idp_id) => {
+                if self.login_state.loading {
+                    return Task::none();
+                }
+                self.login_state.loading = true;
+                self.login_state.error = None;
+                if let Some(task) = self.reauthenticate(ReauthMethod::Sso(idp_id.clone())) {
+                    return task;
+                }
+                let homeserver = self.login_state.homeserver.clone();
+                return cosmic::task::future(async move {
+                    match do_sso_login(&homeserver, idp_id.as_deref()).await {
+                        Ok((client, success)) => {
+                            Message::LoginResult(Ok((MatrixClient(client), success)))
+                        }
+                        Err(e) => Message::LoginResult(Err(e)),
+                    }
+                });
+            }
+            Message::LoginOidc => {
+                let issuer = match self.login_state.flows.as_ref().and_then(|f| f.oidc_issuer.clone()) {
+                    Some(i) => i,
+                    None => return Task::none(),
+                };
+                if self.login_state.loading {
+                    return Task::none();
+                }
+                self.login_state.loading = true;
+                self.login_state.error = None;
+                if let Some(task) = self.reauthenticate(ReauthMethod::Oidc(issuer.clone())) {
+                    return task;
+                }
+                let homeserver = self.login_state.homeserver.clone();
+                return cosmic::task::future(async move {
+                    match do_oidc_login(&homeserver, &issuer).await {
+                        Ok((client, success)) => {
+                            Message::LoginResult(Ok((MatrixClient(client), success)))
+                        }
+                        Err(e) => Message::LoginResult(Err(e)),
+                    }
+                });
+            }
             Message::LoginSubmit => {
                 if self.login_state.loading {
                     return Task::none();
                 }
                 self.login_state.loading = true;
                 self.login_state.error = None;
+                let password = self.login_state.password.clone();
+                if let Some(task) = self.reauthenticate(ReauthMethod::Password(password.clone())) {
+                    return task;
+                }
 
                 let homeserver = self.login_state.homeserver.clone();
                 let username = self.login_state.username.clone();
-                let password = self.login_state.password.clone();
 
                 return cosmic::task::future(async move {
                     match do_login(&homeserver, &username, &password).await {
@@ -169,18 +279,37 @@ impl cosmic::Application for App {
                 match result {
                     Ok((matrix_client, success)) => {
                         tracing::info!("Logged in as {}", success.user_id);
-                        self.homeserver = self.login_state.homeserver.clone();
-                        self.login_password = self.login_state.password.clone();
-                        self.login_state.password.clear();
-                        self.own_user_id = Some(success.user_id.clone());
-                        self.client = Some(Arc::new(matrix_client.0));
+                        let password = std::mem::take(&mut self.login_state.password);
+
+                        // Signing in again as an existing account replaces it.
+                        if let Some(old) = self
+                            .accounts
+                            .iter()
+                            .find(|a| a.user_id == success.user_id)
+                            .map(|a| a.account_id.clone())
+                        {
+                            self.remove_account(&old);
+                        }
+                        self.accounts.push(Account {
+                            account_id: success.account_id.clone(),
+                            user_id: success.user_id.clone(),
+                            homeserver: success.homeserver.clone(),
+                            client: Arc::new(matrix_client.0),
+                            rooms_state: RoomsState::for_account(&success.account_id),
+                            needs_reauth: false,
+                        });
+                        self.login_state = LoginState::default();
                         self.view = AppView::Main;
+                        let activate = self.activate_account(&success.account_id);
+                        self.login_password = password;
 
                         let client = Arc::clone(self.client.as_ref().unwrap());
-                        let client2 = Arc::clone(&client);
                         let uid = success.user_id.to_string();
-                        let pw = Some(self.login_password.clone());
+                        // SSO / OIDC logins have no password for UIA
+                        let pw = (!self.login_password.is_empty())
+                            .then(|| self.login_password.clone());
                         return Task::batch(vec![
+                            activate,
                             cosmic::task::future(async move {
                                 matrix_verification::bootstrap_cross_signing(
                                     (*client).clone(),
@@ -189,9 +318,6 @@ impl cosmic::Application for App {
                                 )
                                 .await
                             }),
-                            cosmic::task::future(async move {
-                                fetch_own_avatar((*client2).clone()).await
-                            }),
                         ]);
                     }
                     Err(e) => {
@@ -199,51 +325,176 @@ impl cosmic::Application for App {
                     }
                 }
             }
-            Message::SessionRestored(matrix_client) => {
-                tracing::info!("Session restored");
-                self.client = Some(Arc::new(matrix_client.0));
+            Message::SessionRestored(restored) => {
+                tracing::info!("Restored {} account(s)", restored.len());
+                for RestoredAccount {
+                    account_id,
+                    homeserver,
+                    client,
+                } in restored
+                {
+                    let Some(user_id) = client.0.user_id().map(|u| u.to_owned()) else {
+                        continue;
+                    };
+                    self.accounts.push(Account {
+                        rooms_state: RoomsState::for_account(&account_id),
+                        account_id,
+                        user_id,
+                        homeserver,
+                        client: Arc::new(client.0),
+                        needs_reauth: false,
+                    });
+                }
+
+                let preferred = config::load_settings().active_account;
+                let active = self
+                    .accounts
+                    .iter()
+                    .find(|a| Some(&a.account_id) == preferred.as_ref())
+                    .or_else(|| self.accounts.first())
+                    .map(|a| a.account_id.clone());
+                let Some(active) = active else {
+                    self.view = AppView::Login;
+                    return Task::none();
+                };
                 self.view = AppView::Main;
-                self.own_user_id = self
-                    .client
-                    .as_ref()
-                    .and_then(|c| c.user_id().map(|u| u.to_owned()));
+                let activate = self.activate_account(&active);
 
                 let client = Arc::clone(self.client.as_ref().unwrap());
-                let client2 = Arc::clone(&client);
                 let uid = self
                     .own_user_id
                     .as_ref()
                     .map(|u| u.to_string())
                     .unwrap_or_default();
                 return Task::batch(vec![
+                    activate,
                     cosmic::task::future(async move {
                         matrix_verification::bootstrap_cross_signing((*client).clone(), uid, None)
                             .await
                     }),
-                    cosmic::task::future(async move {
-                        fetch_own_avatar((*client2).clone()).await
-                    }),
                 ]);
             }
 
+            Message::SessionExpired { soft_logout } => {
+                tracing::warn!("Session expired (soft_logout: {soft_logout})");
+                let Some(account_id) = self.active_account.clone() else {
+                    return Task::none();
+                };
+                if soft_logout {
+                    // The device still exists; keep its store and sign in to it again
+                    return self.show_reauth(&account_id);
+                }
+                let user_id = self.own_user_id.as_ref().map(|u| u.to_string()).unwrap_or_default();
+                let task = self.update(Message::Logout);
+                let notice = format!("{user_id} was signed out by the server. Please sign in again.");
+                if matches!(self.view, AppView::Login) {
+                    self.login_state.error = Some(notice);
+                } else {
+                    self.account_notice = Some(notice);
+                }
+                return task;
+            }
+            Message::ReauthResult(account_id, result) => {
+                self.login_state.loading = false;
+                match result {
+                    Ok(()) => {
+                        tracing::info!("Signed in again to account {account_id}");
+                        if let Some(account) =
+                            self.accounts.iter_mut().find(|a| a.account_id == account_id)
+                        {
+                            account.needs_reauth = false;
+                        }
+                        self.login_state = LoginState::default();
+                        self.view = AppView::Main;
+                        return self.activate_account(&account_id);
+                    }
+                    Err(e) => {
+                        tracing::error!("{e}");
+                        self.login_state.error = Some(e);
+                    }
+                }
+            }
+            Message::DismissAccountNotice => {
+                self.account_notice = None;
+            }
+
             Message::Logout => {
+                // Signs out the active account only; another one takes its place.
+                if let Some(account_id) = self.active_account.take() {
+                    self.remove_account(&account_id);
+                }
                 self.login_password.clear();
                 self.active_verification = None;
                 self.pending_incoming = None;
                 self.own_user_id = None;
                 self.cross_signing_status = CrossSigningStatus::Unknown;
-                config::clear_session();
                 self.client = None;
-                self.rooms_state = RoomsState::default();
+                self.rooms_state = RoomsState {
+                    sort_mode: self.rooms_state.sort_mode.clone(),
+                    sections_collapsed: self.rooms_state.sections_collapsed.clone(),
+                    ..RoomsState::default()
+                };
                 self.timeline_state = TimelineState::default();
                 self.login_state = LoginState::default();
                 self.images.clear();
-                self.avatars.clear();
                 self.own_avatar = None;
                 self.show_profile_panel = false;
+
+                if let Some(next) = self.accounts.first().map(|a| a.account_id.clone()) {
+                    self.view = AppView::Main;
+                    return self.activate_account(&next);
+                }
+                self.avatars.clear();
                 self.view = AppView::Login;
             }
 
+            // -- Accounts --
+            Message::ForAccount(account_id, message) => {
+                if self.active_account.as_deref() == Some(account_id.as_str()) {
+                    return self.update(*message);
+                }
+                return self.update_background_account(&account_id, *message);
+            }
+            Message::SwitchAccount(account_id) => {
+                let needs_reauth = self
+                    .accounts
+                    .iter()
+                    .any(|a| a.account_id == account_id && a.needs_reauth);
+                if needs_reauth {
+                    return self.show_reauth(&account_id);
+                }
+                if self.active_account.as_deref() != Some(account_id.as_str()) {
+                    return self.activate_account(&account_id);
+                }
+            }
+            Message::AddAccount => {
+                self.login_state = LoginState {
+                    can_cancel: true,
+                    ..LoginState::default()
+                };
+                self.view = AppView::Login;
+            }
+            Message::CancelAddAccount => {
+                self.login_state = LoginState::default();
+                // An expired account can't be shown; fall back to one that still works
+                let active_expired = self
+                    .accounts
+                    .iter()
+                    .any(|a| Some(&a.account_id) == self.active_account.as_ref() && a.needs_reauth);
+                if active_expired {
+                    if let Some(next) = self
+                        .accounts
+                        .iter()
+                        .find(|a| !a.needs_reauth)
+                        .map(|a| a.account_id.clone())
+                    {
+                        self.view = AppView::Main;
+                        return self.activate_account(&next);
+                    }
+                }
+                self.view = AppView::Main;
+            }
+
             // -- Sync --
             Message::SyncStarted => {
                 tracing::info!("Sync started");
@@ -260,191 +511,1065 @@ impl cosmic::Application for App {
                     return Task::batch(tasks);
                 }
             }
+            Message::RoomsChanged { upserted, removed } => {
+                tracing::debug!("Room list: {} changed, {} removed", upserted.len(), removed.len());
+                let mut tasks: Vec<cosmic::app::Task<Message>> = Vec::new();
+                if let Some(ref client) = self.client {
+                    tasks.extend(spawn_avatar_fetches_for_rooms(&upserted, &self.avatars, client));
+                }
+                self.rooms_state.apply_room_changes(upserted, &removed);
+                if !tasks.is_empty() {
+                    return Task::batch(tasks);
+                }
+            }
             Message::SyncError(e) => {
                 tracing::error!("Sync error: {e}");
             }
 
             // -- Room selection --
             Message::SelectRoom(room_id) => {
+                self.join_dialog = None;
+                self.create_room_dialog = None;
+                self.new_dm_dialog = None;
+                self.room_settings = None;
                 if self.rooms_state.selected.as_ref() == Some(&room_id) {
                     return Task::none();
                 }
+                self.save_draft();
+                let stop_typing = self.typing_notice(false);
+                let fully_read = self.update_fully_read();
                 self.rooms_state.selected = Some(room_id.clone());
                 self.timeline_state.clear();
+                // Invites have no timeline yet; the view shows a preview instead
+                if self.rooms_state.selected_invite().is_some() {
+                    return Task::batch(vec![stop_typing, fully_read]);
+                }
                 self.timeline_state.loading = true;
                 self.timeline_state.room_id = Some(room_id.clone());
+                if let Some(draft) = self.rooms_state.drafts.get(&room_id).cloned() {
+                    self.timeline_state.restore_draft(draft);
+                }
 
                 if let Some(ref client) = self.client {
                     let client = client.clone();
-                    return cosmic::task::future(async move {
-                        load_timeline_for_room(&client, &room_id).await
-                    });
+                    return Task::batch(vec![
+                        stop_typing,
+                        fully_read,
+                        cosmic::task::future(async move {
+                            open_timeline_for_room(&client, &room_id).await
+                        }),
+                    ]);
                 }
+                return Task::batch(vec![stop_typing, fully_read]);
             }
             Message::RoomFilterChanged(val) => {
                 self.rooms_state.filter = val;
             }
 
             // -- Timeline --
-            Message::TimelineUpdated(room_id, items, token) => {
+            Message::TimelineOpened(room_id, timeline) => {
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.set_timeline(room_id.clone(), timeline.0);
+                    let mut tasks: Vec<cosmic::app::Task<Message>> = Vec::new();
+                    if let Some(client) = self.client.clone() {
+                        if let Some(room) = client.get_room(&room_id) {
+                            let members_room = room.clone();
+                            tasks.push(cosmic::task::future(matrix::timeline::load_fully_read(
+                                room.clone(),
+                            )));
+                            tasks.push(cosmic::task::future(async move {
+                                matrix::timeline::load_redact_permissions(&room).await
+                            }));
+                            tasks.push(cosmic::task::future(async move {
+                                matrix::timeline::load_members(&members_room).await
+                            }));
+                        }
+                    }
+                    if let Some(timeline) = self.timeline_state.timeline.clone() {
+                        tasks.push(cosmic::task::future(async move {
+                            paginate_timeline(&timeline, &room_id).await
+                        }));
+                    }
+                    // Optimistically clear unread badge
+                    if let Some(rid) = self.timeline_state.room_id.clone() {
+                        if let Some(room) = self.rooms_state.rooms.iter_mut().find(|r| r.room_id == rid) {
+                            room.unread_count = 0;
+                            room.mention_count = 0;
+                        }
+                    }
+                    return Task::batch(tasks);
+                }
+            }
+            Message::TimelineOpenFailed(room_id, e) => {
+                tracing::error!("Failed to open timeline for {room_id}: {e}");
                 if self.timeline_state.room_id.as_ref() == Some(&room_id) {
-                    self.timeline_state.set_timeline(room_id.clone(), items, token);
-                    let mut tasks: Vec<cosmic::app::Task<Message>> = vec![snap_to(
-                        TIMELINE_SCROLLABLE_ID.clone(),
-                        RelativeOffset::END,
-                    )];
+                    self.timeline_state.loading = false;
+                }
+            }
+            Message::TimelineDiffs(room_id, diffs) => {
+                if self.timeline_state.room_id.as_ref() != Some(&room_id) {
+                    return Task::none();
+                }
+                let changed: Vec<TimelineItem> = diffs
+                    .iter()
+                    .flat_map(|d| d.items().iter().cloned())
+                    .collect();
+                self.timeline_state.apply_diffs(diffs);
+                matrix::timeline::apply_continuation_markers(&mut self.timeline_state.items);
+
+                let mut tasks: Vec<cosmic::app::Task<Message>> = Vec::new();
+                if let Some(root) = self.timeline_state.thread.as_ref().map(|t| t.root_event_id.clone()) {
+                    let before = self.timeline_state.thread_latest_event_id();
+                    self.timeline_state.refresh_thread();
+                    let latest = self.timeline_state.thread_latest_event_id();
+                    if latest != before {
+                        tasks.extend(self.thread_read_receipt(root, latest));
+                    }
+                }
+                if let Some(ref client) = self.client {
+                    tasks.extend(spawn_image_fetches(&changed, &self.images, client));
+                    tasks.extend(spawn_avatar_fetches_for_timeline(&changed, &self.avatars, client));
+                    tasks.extend(spawn_avatar_fetches_for_readers(
+                        &changed,
+                        &self.timeline_state.members,
+                        &self.avatars,
+                        client,
+                    ));
+                }
+                if self.timeline_state.jumping_to_unread {
+                    tasks.push(self.continue_jump_to_unread());
+                }
+                if self.timeline_state.at_bottom {
+                    if !self.timeline_state.jumping_to_unread {
+                        tasks.push(snap_to(TIMELINE_SCROLLABLE_ID.clone(), RelativeOffset::END));
+                    }
+                    self.timeline_state.seen_up_to = last_message_event_id(&self.timeline_state.items);
+                    // Send read receipt for latest event when user is at bottom
                     if let Some(ref client) = self.client {
-                        tasks.extend(spawn_image_fetches(
-                            &self.timeline_state.items,
-                            &self.images,
-                            client,
-                        ));
-                        tasks.extend(spawn_avatar_fetches_for_timeline(
-                            &self.timeline_state.items,
-                            &self.avatars,
-                            client,
-                        ));
-                        // Send read receipt for last event to clear unread count
-                        if let Some(last_id) = last_message_event_id(&self.timeline_state.items) {
+                        if let Some(last_id) = self.timeline_state.seen_up_to.clone() {
                             let c = client.clone();
                             let rid = room_id.clone();
+                            let private = self.private_read_receipts;
                             tasks.push(cosmic::task::future(async move {
-                                send_read_receipt(&c, &rid, &last_id).await
+                                send_read_receipt(&c, &rid, &last_id, None, private).await
                             }));
                         }
                     }
-                    // Optimistically clear unread badge
+                    // Optimistically clear unread badge for current room
                     if let Some(room) = self.rooms_state.rooms.iter_mut().find(|r| r.room_id == room_id) {
                         room.unread_count = 0;
                         room.mention_count = 0;
                     }
+                }
+                if !tasks.is_empty() {
                     return Task::batch(tasks);
                 }
             }
             Message::IncomingEvents(room_id, new_items) => {
-                // Desktop notifications for messages in non-active rooms
+                // Desktop notifications for messages in non-active rooms.
+                // The open room's view is driven by TimelineDiffs instead.
                 let is_active_room = self.timeline_state.room_id.as_ref() == Some(&room_id);
                 if !is_active_room && self.initial_sync_done {
                     let own_id = self.own_user_id.as_ref().map(|u| u.to_string()).unwrap_or_default();
-                    let room_name = self.rooms_state.rooms.iter()
-                        .find(|r| r.room_id == room_id)
-                        .map(|r| r.name.clone())
-                        .unwrap_or_else(|| room_id.to_string());
-                    let notifiable: Vec<(String, String)> = new_items.iter().filter_map(|item| {
-                        if let crate::message::TimelineItem::Message(msg) = item {
-                            if msg.sender != own_id && !msg.event_id.is_empty() {
-                                let body = if msg.image.is_some() {
-                                    "📷 Image".to_string()
-                                } else {
-                                    msg.body.chars().take(100).collect()
-                                };
-                                return Some((msg.sender_display.clone(), body));
+                    let account_label = self.notification_account_label(self.active_account.as_deref());
+                    notify_incoming(
+                        &self.rooms_state,
+                        &own_id,
+                        account_label.as_deref(),
+                        &room_id,
+                        &new_items,
+                    );
+                }
+            }
+            Message::TimelineScrolled(offset) => {
+                self.timeline_state.at_bottom = offset.y >= 0.99;
+                if self.timeline_state.at_bottom {
+                    if let Some(last_id) = last_message_event_id(&self.timeline_state.items) {
+                        self.timeline_state.seen_up_to = Some(last_id);
+                    }
+                }
+            }
+            Message::ScrollToBottom => {
+                return snap_to(
+                    TIMELINE_SCROLLABLE_ID.clone(),
+                    RelativeOffset::END,
+                );
+            }
+            Message::ComposerAction(action) => {
+                let is_edit = action.is_edit();
+                self.timeline_state.composer_action(action);
+                self.timeline_state.refresh_suggestions(&self.rooms_state.rooms);
+                if is_edit {
+                    self.timeline_state.command_feedback = None;
+                    let typing = !self.timeline_state.composer_text().is_empty();
+                    return self.typing_notice(typing);
+                }
+            }
+            Message::ReplyTo(ctx) => {
+                if self.timeline_state.editing.is_some() {
+                    self.timeline_state.finish_edit();
+                }
+                self.timeline_state.reply_to = Some(ctx);
+                self.timeline_state.draft_dirty = true;
+            }
+            Message::CancelReply => {
+                self.timeline_state.reply_to = None;
+                self.timeline_state.draft_dirty = true;
+            }
+
+            // -- Edits --
+            Message::StartEdit { event_id, body } => {
+                self.timeline_state.start_edit(event_id, body);
+            }
+            Message::EditLastMessage => {
+                if let Some(msg) = self.timeline_state.last_editable_message() {
+                    let (event_id, body) = (msg.event_id.clone(), msg.edit_text());
+                    return self.update(Message::StartEdit { event_id, body });
+                }
+            }
+            Message::CancelEdit => {
+                self.timeline_state.finish_edit();
+            }
+            Message::ShowEditHistory(event_id) => {
+                let (Some(client), Some(room_id), Some(timeline), Some(message)) = (
+                    self.client.clone(),
+                    self.timeline_state.room_id.clone(),
+                    self.timeline_state.timeline.clone(),
+                    self.timeline_state.message(&event_id),
+                ) else {
+                    return Task::none();
+                };
+                let sender = message.sender.clone();
+                self.timeline_state.edit_history = Some(EditHistory {
+                    event_id: event_id.clone(),
+                    entries: Vec::new(),
+                    loading: true,
+                    error: None,
+                });
+                return cosmic::task::future(async move {
+                    load_edit_history(&client, &room_id, &timeline, event_id, sender).await
+                });
+            }
+            Message::EditHistoryLoaded(event_id, result) => {
+                if let Some(ref mut history) = self.timeline_state.edit_history {
+                    if history.event_id == event_id {
+                        history.loading = false;
+                        match result {
+                            Ok(entries) => history.entries = entries,
+                            Err(e) => history.error = Some(e),
+                        }
+                    }
+                }
+            }
+            Message::CloseEditHistory => {
+                self.timeline_state.edit_history = None;
+            }
+
+            // -- Reactions --
+            Message::ToggleReaction { event_id, key } => {
+                self.timeline_state.reaction_picker = None;
+                let Some(timeline) = self.timeline_state.timeline.clone() else {
+                    return Task::none();
+                };
+                return cosmic::task::future(async move {
+                    toggle_reaction(&timeline, &event_id, &key).await
+                });
+            }
+            Message::ReactionFailed(room_id, e) => {
+                tracing::error!("Reaction failed: {e}");
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.command_feedback = Some(Err(e));
+                }
+            }
+            Message::ToggleReactionPicker(event_id) => {
+                if self.timeline_state.reaction_picker.as_ref() == Some(&event_id) {
+                    self.timeline_state.reaction_picker = None;
+                } else {
+                    self.timeline_state.reaction_picker = Some(event_id);
+                }
+            }
+
+            // -- Threads --
+            Message::OpenThread(root_event_id) => {
+                let (Some(client), Some(room_id)) =
+                    (self.client.clone(), self.timeline_state.room_id.clone())
+                else {
+                    return Task::none();
+                };
+                self.timeline_state.reaction_picker = None;
+                self.timeline_state.thread = Some(ThreadState {
+                    root_event_id: root_event_id.clone(),
+                    fetched: Vec::new(),
+                    items: Vec::new(),
+                    loading: true,
+                });
+                self.timeline_state.refresh_thread();
+                return cosmic::task::future(async move {
+                    load_thread(&client, &room_id, root_event_id).await
+                });
+            }
+            Message::ThreadLoaded(root_event_id, result) => {
+                let Some(ref mut thread) = self.timeline_state.thread else {
+                    return Task::none();
+                };
+                if thread.root_event_id != root_event_id {
+                    return Task::none();
+                }
+                thread.loading = false;
+                match result {
+                    Ok(replies) => thread.fetched = replies,
+                    Err(e) => tracing::error!("Failed to load thread: {e}"),
+                }
+                self.timeline_state.refresh_thread();
+
+                let mut tasks: Vec<cosmic::app::Task<Message>> = Vec::new();
+                if let Some(ref client) = self.client {
+                    let items = self.timeline_state.thread.as_ref().map(|t| t.items.as_slice()).unwrap_or(&[]);
+                    tasks.extend(spawn_image_fetches(items, &self.images, client));
+                    tasks.extend(spawn_avatar_fetches_for_timeline(items, &self.avatars, client));
+                }
+                let latest = self.timeline_state.thread_latest_event_id();
+                tasks.extend(self.thread_read_receipt(root_event_id, latest));
+                return Task::batch(tasks);
+            }
+            Message::ReloadThread => {
+                return self.reload_thread();
+            }
+            Message::CloseThread => {
+                self.timeline_state.thread = None;
+            }
+
+            Message::OpenLink(url) => {
+                if let Err(e) = matrix::loopback::open_in_browser(&url) {
+                    tracing::error!("Failed to open link: {e}");
+                }
+            }
+            Message::CopyToClipboard(text) => {
+                return cosmic::iced::clipboard::write(text);
+            }
+
+            // -- Redactions --
+            Message::RedactMessage(event_id) => {
+                self.timeline_state.redacting = Some((event_id, String::new()));
+            }
+            Message::RedactionReasonChanged(reason) => {
+                if let Some((_, ref mut r)) = self.timeline_state.redacting {
+                    *r = reason;
+                }
+            }
+            Message::CancelRedaction => {
+                self.timeline_state.redacting = None;
+            }
+            Message::ConfirmRedaction => {
+                let (Some((event_id, reason)), Some(client), Some(room_id)) = (
+                    self.timeline_state.redacting.take(),
+                    self.client.clone(),
+                    self.timeline_state.room_id.clone(),
+                ) else {
+                    return Task::none();
+                };
+                return cosmic::task::future(async move {
+                    redact_event(&client, &room_id, &event_id, reason.trim()).await
+                });
+            }
+            Message::MessageRedacted => {
+                tracing::info!("Message deleted");
+            }
+            Message::RedactionFailed(room_id, e) => {
+                tracing::error!("Delete failed: {e}");
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.command_feedback = Some(Err(e));
+                }
+            }
+            Message::RedactPermissionsLoaded { room_id, own, other } => {
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.can_redact_own = own;
+                    self.timeline_state.can_redact_other = other;
+                }
+            }
+            Message::SendMessage => {
+                let text = self.timeline_state.composer_text();
+                if text.is_empty() {
+                    return Task::none();
+                }
+                let room_id = match self.timeline_state.room_id.clone() {
+                    Some(id) => id,
+                    None => return Task::none(),
+                };
+                let timeline = match self.timeline_state.timeline.clone() {
+                    Some(t) => t,
+                    None => return Task::none(),
+                };
+
+                let prepared =
+                    matrix::mentions::prepare(&text, &self.timeline_state.composer_pills());
+                let (msgtype, mut mentions) = match prepared {
+                    Ok((Command::Send(msgtype), mentions)) => (msgtype, mentions),
+                    Ok(_) if self.timeline_state.editing.is_some() => {
+                        self.timeline_state.command_feedback =
+                            Some(Err("Only message commands can be used in an edit".to_string()));
+                        return Task::none();
+                    }
+                    Ok((Command::Devtools, _)) => {
+                        self.timeline_state.set_composer(String::new());
+                        self.timeline_state.draft_dirty = true;
+                        self.timeline_state.devtools = Some(Devtools {
+                            loading: true,
+                            text: String::new(),
+                            error: None,
+                        });
+                        let Some(client) = self.client.clone() else {
+                            return Task::none();
+                        };
+                        return cosmic::task::future(async move {
+                            matrix::commands::load_room_state(&client, &room_id).await
+                        });
+                    }
+                    Ok((command, _)) => {
+                        self.timeline_state.set_composer(String::new());
+                        self.timeline_state.draft_dirty = true;
+                        let Some(client) = self.client.clone() else {
+                            return Task::none();
+                        };
+                        return cosmic::task::future(async move {
+                            matrix::commands::execute(&client, &room_id, command).await
+                        });
+                    }
+                    Err(e) => {
+                        self.timeline_state.command_feedback = Some(Err(e));
+                        return Task::none();
+                    }
+                };
+
+                if let Some(event_id) = self.timeline_state.editing.clone() {
+                    self.timeline_state.finish_edit();
+                    self.timeline_state.sending = true;
+                    return cosmic::task::future(async move {
+                        edit_message(&timeline, &room_id, &event_id, msgtype, mentions).await
+                    });
+                }
+
+                let reply_event_id = self.timeline_state.reply_to.as_ref()
+                    .map(|ctx| ctx.event_id.clone());
+                // Replying mentions the author of the original message.
+                if let Some(ctx) = self.timeline_state.reply_to.as_ref() {
+                    if let Ok(sender) = OwnedUserId::try_from(ctx.sender_id.as_str()) {
+                        if self.own_user_id.as_ref() != Some(&sender) {
+                            mentions.user_ids.insert(sender);
+                        }
+                    }
+                }
+                // With the thread panel open, messages go into the thread.
+                let thread = self.timeline_state.thread.as_ref().and_then(|t| {
+                    let latest = self.timeline_state.thread_latest_event_id()?;
+                    Some((t.root_event_id.clone(), latest))
+                });
+                self.timeline_state.reply_to = None;
+                self.timeline_state.set_composer(String::new());
+                self.timeline_state.draft_dirty = true;
+                self.timeline_state.sending = true;
+
+                return cosmic::task::future(async move {
+                    send_message(&timeline, &room_id, msgtype, mentions, reply_event_id, thread)
+                        .await
+                });
+            }
+            Message::ApplyCompletion(completion) => {
+                self.timeline_state.apply_completion(completion);
+            }
+            Message::MembersLoaded(room_id, members) => {
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.members = members;
+                    if let Some(ref client) = self.client {
+                        return Task::batch(spawn_avatar_fetches_for_readers(
+                            &self.timeline_state.items,
+                            &self.timeline_state.members,
+                            &self.avatars,
+                            client,
+                        ));
+                    }
+                }
+            }
+            Message::TypingUsers(room_id, user_ids) => {
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.typing_users = user_ids;
+                }
+            }
+            Message::SetPrivateReadReceipts(enabled) => {
+                self.private_read_receipts = enabled;
+                let mut settings = config::load_settings();
+                settings.private_read_receipts = enabled;
+                let _ = config::save_settings(&settings);
+            }
+            Message::SetSendTypingNotifications(enabled) => {
+                // Withdraw a notice that is still showing before going quiet.
+                let stop = if enabled {
+                    Task::none()
+                } else {
+                    self.typing_notice(false)
+                };
+                self.send_typing_notifications = enabled;
+                let mut settings = config::load_settings();
+                settings.send_typing_notifications = enabled;
+                let _ = config::save_settings(&settings);
+                return stop;
+            }
+            Message::CompleteCommand => {
+                if let Some(text) = matrix::commands::complete(&self.timeline_state.composer_text()) {
+                    self.timeline_state.set_composer(text);
+                }
+            }
+            Message::CommandFinished(result) => {
+                if let Err(ref e) = result {
+                    tracing::error!("Command failed: {e}");
+                }
+                self.timeline_state.command_feedback = Some(result);
+            }
+            Message::DismissCommandFeedback => {
+                self.timeline_state.command_feedback = None;
+            }
+            Message::RoomJoined(room_id) => {
+                self.join_dialog = None;
+                self.create_room_dialog = None;
+                self.new_dm_dialog = None;
+                if let Some(room) = self.rooms_state.rooms.iter_mut().find(|r| r.room_id == room_id) {
+                    if room.is_invite {
+                        room.is_invite = false;
+                        room.inviter = None;
+                        // Re-select so the timeline opens in place of the preview
+                        if self.rooms_state.selected.as_ref() == Some(&room_id) {
+                            self.rooms_state.selected = None;
+                        }
+                    }
+                }
+                if self.rooms_state.invite_pending.as_ref() == Some(&room_id) {
+                    self.rooms_state.invite_pending = None;
+                }
+                return self.update(Message::SelectRoom(room_id));
+            }
+            Message::RoomLeft(room_id) => {
+                // Drop the room right away instead of waiting for the next sync
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.clear();
+                }
+                if self.room_settings.as_ref().is_some_and(|s| s.room_id == room_id) {
+                    self.room_settings = None;
+                }
+                if self.rooms_state.remove_room(&room_id) {
+                    if let Some(ref account_id) = self.active_account {
+                        if let Err(e) = config::save_drafts(account_id, &self.rooms_state.drafts) {
+                            tracing::warn!("Failed to save drafts: {e}");
+                        }
+                    }
+                }
+            }
+            Message::ToggleRoomMenu(room_id) => {
+                if self.rooms_state.room_menu.as_ref() == Some(&room_id) {
+                    self.rooms_state.room_menu = None;
+                } else {
+                    self.rooms_state.room_menu = Some(room_id);
+                }
+            }
+            Message::ConfirmLeave(room_id, forget) => {
+                self.rooms_state.room_menu = None;
+                if let Some(ref client) = self.client {
+                    let client = client.clone();
+                    self.rooms_state.leaving = Some(LeaveConfirm {
+                        room_id: room_id.clone(),
+                        forget,
+                        warning: None,
+                        checking: true,
+                        leaving: false,
+                        error: None,
+                    });
+                    return cosmic::task::future(async move {
+                        matrix::rooms::leave_warning(&client, room_id).await
+                    });
+                }
+            }
+            Message::LeaveWarning(room_id, warning) => {
+                if let Some(ref mut confirm) = self.rooms_state.leaving {
+                    if confirm.room_id == room_id {
+                        confirm.checking = false;
+                        confirm.warning = warning;
+                    }
+                }
+            }
+            Message::CancelLeave => {
+                self.rooms_state.leaving = None;
+            }
+            Message::LeaveRoom => {
+                if let (Some(confirm), Some(client)) =
+                    (self.rooms_state.leaving.as_mut(), self.client.clone())
+                {
+                    confirm.leaving = true;
+                    confirm.error = None;
+                    let room_id = confirm.room_id.clone();
+                    let forget = confirm.forget;
+                    return cosmic::task::future(async move {
+                        matrix::rooms::leave_room(&client, room_id, forget).await
+                    });
+                }
+            }
+            Message::LeaveFailed(room_id, e) => {
+                tracing::error!("Leaving {room_id} failed: {e}");
+                if let Some(ref mut confirm) = self.rooms_state.leaving {
+                    if confirm.room_id == room_id {
+                        confirm.leaving = false;
+                        confirm.error = Some(e);
+                    }
+                }
+            }
+            // -- Room settings --
+            Message::ShowRoomSettings => {
+                if let (Some(room_id), Some(client)) =
+                    (self.timeline_state.room_id.clone(), self.client.clone())
+                {
+                    self.room_settings = Some(RoomSettingsState::new(room_id.clone()));
+                    return cosmic::task::future(async move {
+                        matrix::room_settings::load_local_aliases(&client, room_id).await
+                    });
+                }
+            }
+            Message::CloseRoomSettings => {
+                self.room_settings = None;
+            }
+            Message::RoomSettingsLoaded(room_id, settings) => {
+                let Some(ref mut state) = self.room_settings else {
+                    return Task::none();
+                };
+                if state.room_id != room_id {
+                    return Task::none();
+                }
+                let fetch = settings
+                    .avatar_url
+                    .clone()
+                    .filter(|url| !self.avatars.contains_key(url));
+                state.update(settings);
+                if let (Some(url), Some(client)) = (fetch, self.client.clone()) {
+                    return spawn_avatar_fetch(client, url);
+                }
+            }
+            Message::LocalAliasesLoaded(room_id, result) => {
+                if let Some(ref mut state) = self.room_settings {
+                    if state.room_id == room_id {
+                        match result {
+                            Ok(aliases) => {
+                                state.local_aliases = aliases;
+                                state.local_aliases_error = None;
+                            }
+                            Err(e) => {
+                                tracing::warn!("{e}");
+                                state.local_aliases_error = Some(e);
+                            }
+                        }
+                    }
+                }
+            }
+            Message::RoomSettingsNameChanged(name) => {
+                if let Some(ref mut state) = self.room_settings {
+                    state.name = name;
+                }
+            }
+            Message::RoomSettingsTopicChanged(topic) => {
+                if let Some(ref mut state) = self.room_settings {
+                    state.topic = topic;
+                }
+            }
+            Message::RoomSettingsNewAliasChanged(alias) => {
+                if let Some(ref mut state) = self.room_settings {
+                    state.new_alias = alias;
+                }
+            }
+            Message::AddRoomAlias => {
+                let (Some(state), Some(own_user_id)) =
+                    (self.room_settings.as_mut(), self.own_user_id.as_ref())
+                else {
+                    return Task::none();
+                };
+                // "name" and "#name" are completed with our own server
+                let input = state.new_alias.trim().to_string();
+                let mut alias = input.clone();
+                if !alias.starts_with('#') {
+                    alias.insert(0, '#');
+                }
+                if !alias.contains(':') {
+                    alias = format!("{alias}:{}", own_user_id.server_name());
+                }
+                match OwnedRoomAliasId::try_from(alias) {
+                    Ok(alias) => {
+                        state.new_alias.clear();
+                        return self.update(Message::UpdateRoomSetting(
+                            SettingsChange::AddLocalAlias(alias),
+                        ));
+                    }
+                    Err(e) => {
+                        state.feedback = Some(Err(format!("Invalid address {input}: {e}")));
+                    }
+                }
+            }
+            Message::UpdateRoomSetting(change) => {
+                if let (Some(state), Some(client)) =
+                    (self.room_settings.as_mut(), self.client.clone())
+                {
+                    state.saving += 1;
+                    state.feedback = None;
+                    let room_id = state.room_id.clone();
+                    return cosmic::task::future(async move {
+                        matrix::room_settings::apply_change(&client, room_id, change).await
+                    });
+                }
+            }
+            Message::PickRoomAvatar => {
+                if let (Some(state), Some(client)) =
+                    (self.room_settings.as_mut(), self.client.clone())
+                {
+                    state.feedback = None;
+                    let room_id = state.room_id.clone();
+                    return cosmic::task::future(async move {
+                        matrix::room_settings::pick_room_avatar(&client, room_id).await
+                    });
+                }
+            }
+            Message::RoomSettingSaved(room_id, result) => {
+                let Some(ref mut state) = self.room_settings else {
+                    return Task::none();
+                };
+                if state.room_id != room_id {
+                    return Task::none();
+                }
+                state.saving = state.saving.saturating_sub(1);
+                if let Err(ref e) = result {
+                    tracing::error!("{e}");
+                }
+                state.feedback = Some(result);
+                // Local aliases aren't room state, so sync won't tell us about them
+                if let Some(client) = self.client.clone() {
+                    return cosmic::task::future(async move {
+                        matrix::room_settings::load_local_aliases(&client, room_id).await
+                    });
+                }
+            }
+            Message::AcceptInvite(room_id) => {
+                if let Some(ref client) = self.client {
+                    let client = client.clone();
+                    self.rooms_state.invite_pending = Some(room_id.clone());
+                    self.rooms_state.invite_error = None;
+                    return cosmic::task::future(async move {
+                        matrix::rooms::accept_invite(&client, room_id).await
+                    });
+                }
+            }
+            Message::DeclineInvite(room_id, ignore) => {
+                if let Some(ref client) = self.client {
+                    let client = client.clone();
+                    let inviter = ignore
+                        .then(|| {
+                            self.rooms_state.rooms.iter()
+                                .find(|r| r.room_id == room_id)
+                                .and_then(|r| r.inviter.as_ref())
+                                .map(|i| i.user_id.clone())
+                        })
+                        .flatten();
+                    self.rooms_state.invite_pending = Some(room_id.clone());
+                    self.rooms_state.invite_error = None;
+                    return cosmic::task::future(async move {
+                        matrix::rooms::decline_invite(&client, room_id, inviter).await
+                    });
+                }
+            }
+            Message::InviteFailed(room_id, e) => {
+                tracing::error!("Invite action for {room_id} failed: {e}");
+                self.rooms_state.invite_pending = None;
+                self.rooms_state.invite_error = Some((room_id, e));
+            }
+            // -- Join dialog --
+            Message::ShowJoinDialog => {
+                self.join_dialog = Some(JoinState::default());
+                return self.update(Message::DirectorySearch);
+            }
+            Message::CloseJoinDialog => {
+                self.join_dialog = None;
+            }
+            Message::JoinAddressChanged(address) => {
+                if let Some(ref mut dialog) = self.join_dialog {
+                    dialog.address = address;
+                    dialog.error = None;
+                }
+            }
+            Message::JoinSubmit => {
+                if let (Some(dialog), Some(client)) = (self.join_dialog.as_mut(), self.client.clone()) {
+                    match matrix::rooms::parse_join_target(&dialog.address) {
+                        Ok((target, via)) => {
+                            dialog.joining = true;
+                            dialog.error = None;
+                            return cosmic::task::future(async move {
+                                matrix::rooms::join_room(&client, target, via).await
+                            });
+                        }
+                        Err(e) => dialog.error = Some(e),
+                    }
+                }
+            }
+            Message::JoinPublicRoom(room_id) => {
+                if let (Some(dialog), Some(client)) = (self.join_dialog.as_mut(), self.client.clone()) {
+                    // Rooms from another server's directory are joined through that server
+                    let via: Vec<OwnedServerName> =
+                        OwnedServerName::try_from(dialog.listed_server.as_str())
+                            .into_iter()
+                            .collect();
+                    dialog.joining = true;
+                    dialog.error = None;
+                    return cosmic::task::future(async move {
+                        matrix::rooms::join_room(&client, room_id.into(), via).await
+                    });
+                }
+            }
+            Message::JoinFailed(e) => {
+                tracing::error!("{e}");
+                if let Some(ref mut dialog) = self.join_dialog {
+                    dialog.joining = false;
+                    dialog.error = Some(e);
+                }
+            }
+            Message::DirectoryServerChanged(server) => {
+                if let Some(ref mut dialog) = self.join_dialog {
+                    dialog.server = server;
+                }
+            }
+            Message::DirectorySearchChanged(search) => {
+                if let Some(ref mut dialog) = self.join_dialog {
+                    dialog.search = search;
+                }
+            }
+            Message::DirectorySearch => {
+                if let (Some(dialog), Some(client)) = (self.join_dialog.as_mut(), self.client.clone()) {
+                    dialog.start_search();
+                    let server = dialog.listed_server.clone();
+                    let search = dialog.listed_search.clone();
+                    return cosmic::task::future(async move {
+                        matrix::rooms::public_rooms(&client, server, search, None).await
+                    });
+                }
+            }
+            Message::DirectoryLoadMore => {
+                if let (Some(dialog), Some(client)) = (self.join_dialog.as_mut(), self.client.clone()) {
+                    let Some(since) = dialog.next_batch.clone() else {
+                        return Task::none();
+                    };
+                    if dialog.loading {
+                        return Task::none();
+                    }
+                    dialog.loading = true;
+                    let server = dialog.listed_server.clone();
+                    let search = dialog.listed_search.clone();
+                    return cosmic::task::future(async move {
+                        matrix::rooms::public_rooms(&client, server, search, Some(since)).await
+                    });
+                }
+            }
+            Message::DirectoryLoaded(result) => {
+                let Some(ref mut dialog) = self.join_dialog else {
+                    return Task::none();
+                };
+                match result {
+                    Ok(page) => {
+                        let urls: Vec<String> = page
+                            .rooms
+                            .iter()
+                            .filter_map(|r| r.avatar_url.clone())
+                            .filter(|url| !self.avatars.contains_key(url))
+                            .collect();
+                        if dialog.apply_page(page) {
+                            if let Some(ref client) = self.client {
+                                let tasks: Vec<_> = urls
+                                    .into_iter()
+                                    .map(|url| spawn_avatar_fetch(client.clone(), url))
+                                    .collect();
+                                return Task::batch(tasks);
                             }
                         }
-                        None
-                    }).collect();
-                    if notifiable.len() == 1 {
-                        let (ref sender, ref body) = notifiable[0];
-                        send_notification(&room_name, sender, body);
-                    } else if notifiable.len() > 1 {
-                        send_batch_notification(&room_name, notifiable.len());
+                    }
+                    Err(e) => {
+                        tracing::warn!("{e}");
+                        dialog.loading = false;
+                        dialog.directory_error = Some(e);
                     }
                 }
+            }
 
-                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
-                    if !self.timeline_state.at_bottom
-                        && !self.timeline_state.unread_marker_inserted
-                        && !new_items.is_empty()
-                    {
-                        self.timeline_state.items.push(TimelineItem::UnreadMarker);
-                        self.timeline_state.unread_marker_inserted = true;
-                    }
-                    let mut extra_tasks: Vec<cosmic::app::Task<Message>> = Vec::new();
-                    if let Some(ref client) = self.client {
-                        extra_tasks.extend(spawn_image_fetches(&new_items, &self.images, client));
-                        extra_tasks.extend(spawn_avatar_fetches_for_timeline(&new_items, &self.avatars, client));
-                    }
-                    self.timeline_state.items.extend(new_items);
-                    matrix::timeline::apply_continuation_markers(
-                        &mut self.timeline_state.items,
-                    );
-                    if self.timeline_state.at_bottom {
-                        let mut tasks: Vec<cosmic::app::Task<Message>> = vec![snap_to(
-                            TIMELINE_SCROLLABLE_ID.clone(),
-                            RelativeOffset::END,
-                        )];
-                        tasks.extend(extra_tasks);
-                        // Send read receipt for latest event when user is at bottom
-                        if let (Some(ref client), Some(ref rid)) = (&self.client, &self.timeline_state.room_id) {
-                            if let Some(last_id) = last_message_event_id(&self.timeline_state.items) {
-                                let c = client.clone();
-                                let rid2 = rid.clone();
-                                tasks.push(cosmic::task::future(async move {
-                                    send_read_receipt(&c, &rid2, &last_id).await
-                                }));
-                            }
-                        }
-                        // Optimistically clear unread badge for current room
-                        if let Some(rid) = self.timeline_state.room_id.clone() {
-                            if let Some(room) = self.rooms_state.rooms.iter_mut().find(|r| r.room_id == rid) {
-                                room.unread_count = 0;
-                                room.mention_count = 0;
-                            }
-                        }
-                        return Task::batch(tasks);
-                    } else if !extra_tasks.is_empty() {
-                        return Task::batch(extra_tasks);
-                    }
+            // -- Room and DM creation --
+            Message::ShowCreateRoomDialog => {
+                self.create_room_dialog = Some(CreateRoomState::default());
+            }
+            Message::CloseCreateRoomDialog => {
+                self.create_room_dialog = None;
+            }
+            Message::CreateRoomNameChanged(name) => {
+                if let Some(ref mut dialog) = self.create_room_dialog {
+                    dialog.name = name;
                 }
             }
-            Message::TimelineScrolled(offset) => {
-                self.timeline_state.at_bottom = offset.y >= 0.99;
+            Message::CreateRoomTopicChanged(topic) => {
+                if let Some(ref mut dialog) = self.create_room_dialog {
+                    dialog.topic = topic;
+                }
             }
-            Message::ScrollToBottom => {
-                return snap_to(
-                    TIMELINE_SCROLLABLE_ID.clone(),
-                    RelativeOffset::END,
-                );
+            Message::CreateRoomAliasChanged(alias) => {
+                if let Some(ref mut dialog) = self.create_room_dialog {
+                    dialog.alias = alias;
+                }
             }
-            Message::ComposerChanged(val) => {
-                self.timeline_state.composer = val;
+            Message::CreateRoomInviteesChanged(invitees) => {
+                if let Some(ref mut dialog) = self.create_room_dialog {
+                    dialog.invitees = invitees;
+                    dialog.error = None;
+                }
             }
-            Message::ReplyTo(ctx) => {
-                self.timeline_state.reply_to = Some(ctx);
+            Message::SetCreateRoomPublic(public) => {
+                if let Some(ref mut dialog) = self.create_room_dialog {
+                    dialog.public = public;
+                    // Default to encryption for private rooms only; it can still be toggled
+                    dialog.encrypted = !public;
+                }
             }
-            Message::CancelReply => {
-                self.timeline_state.reply_to = None;
+            Message::SetCreateRoomEncrypted(encrypted) => {
+                if let Some(ref mut dialog) = self.create_room_dialog {
+                    dialog.encrypted = encrypted;
+                }
             }
-            Message::SendMessage => {
-                let text = self.timeline_state.composer.trim().to_string();
-                if text.is_empty() {
-                    return Task::none();
+            Message::CreateRoomSubmit => {
+                if let (Some(dialog), Some(client)) =
+                    (self.create_room_dialog.as_mut(), self.client.clone())
+                {
+                    let invite = match dialog.invitee_ids() {
+                        Ok(ids) => ids,
+                        Err(e) => {
+                            dialog.error = Some(e);
+                            return Task::none();
+                        }
+                    };
+                    let room = matrix::rooms::NewRoom {
+                        name: dialog.name.clone(),
+                        topic: dialog.topic.clone(),
+                        alias: dialog.alias_localpart(),
+                        public: dialog.public,
+                        encrypted: dialog.encrypted,
+                        invite,
+                    };
+                    dialog.creating = true;
+                    dialog.error = None;
+                    return cosmic::task::future(async move {
+                        matrix::rooms::create_room(&client, room).await
+                    });
                 }
-                let room_id = match self.timeline_state.room_id.clone() {
-                    Some(id) => id,
-                    None => return Task::none(),
-                };
-                let client = match self.client.clone() {
-                    Some(c) => c,
-                    None => return Task::none(),
+            }
+            Message::CreateRoomFailed(e) => {
+                tracing::error!("{e}");
+                if let Some(ref mut dialog) = self.create_room_dialog {
+                    dialog.creating = false;
+                    dialog.error = Some(e);
+                }
+            }
+            Message::ShowNewDmDialog => {
+                self.new_dm_dialog = Some(NewDmState::default());
+            }
+            Message::CloseNewDmDialog => {
+                self.new_dm_dialog = None;
+            }
+            Message::DmSearchChanged(query) => {
+                if let Some(ref mut dialog) = self.new_dm_dialog {
+                    dialog.query = query;
+                    dialog.error = None;
+                    let query = dialog.query.trim().to_string();
+                    if query.is_empty() {
+                        dialog.results.clear();
+                        dialog.searching = false;
+                        return Task::none();
+                    }
+                    // Wait for typing to pause rather than searching on every keystroke
+                    dialog.searching = true;
+                    return cosmic::task::future(async move {
+                        tokio::time::sleep(DM_SEARCH_DELAY).await;
+                        Message::DmSearchDue(query)
+                    });
+                }
+            }
+            Message::DmSearchDue(query) => {
+                if let (Some(dialog), Some(client)) = (self.new_dm_dialog.as_ref(), self.client.clone()) {
+                    if dialog.query.trim() == query {
+                        return cosmic::task::future(async move {
+                            matrix::rooms::search_users(&client, query).await
+                        });
+                    }
+                }
+            }
+            Message::DmSearchResults(query, result) => {
+                let Some(ref mut dialog) = self.new_dm_dialog else {
+                    return Task::none();
                 };
+                // Only the results for what is in the search field now
+                if dialog.query.trim() != query {
+                    return Task::none();
+                }
+                dialog.searching = false;
+                match result {
+                    Ok(users) => {
+                        let tasks: Vec<_> = match self.client {
+                            Some(ref client) => users
+                                .iter()
+                                .filter_map(|u| u.avatar_url.clone())
+                                .filter(|url| !self.avatars.contains_key(url))
+                                .map(|url| spawn_avatar_fetch(client.clone(), url))
+                                .collect(),
+                            None => Vec::new(),
+                        };
+                        dialog.results = users;
+                        return Task::batch(tasks);
+                    }
+                    Err(e) => {
+                        tracing::warn!("{e}");
+                        dialog.error = Some(e);
+                    }
+                }
+            }
+            Message::StartDm(user_id) => {
+                if let (Some(dialog), Some(client)) = (self.new_dm_dialog.as_mut(), self.client.clone()) {
+                    dialog.starting = true;
+                    dialog.error = None;
+                    return cosmic::task::future(async move {
+                        matrix::rooms::start_dm(&client, user_id).await
+                    });
+                }
+            }
+            Message::DmFailed(e) => {
+                tracing::error!("{e}");
+                if let Some(ref mut dialog) = self.new_dm_dialog {
+                    dialog.starting = false;
+                    dialog.error = Some(e);
+                }
+            }
 
-                let reply_event_id = self.timeline_state.reply_to.as_ref()
-                    .map(|ctx| ctx.event_id.clone());
-                self.timeline_state.reply_to = None;
-                self.timeline_state.composer.clear();
-                self.timeline_state.sending = true;
-
-                return cosmic::task::future(async move {
-                    send_message(&client, &room_id, &text, reply_event_id).await
-                });
+            Message::DevtoolsLoaded(result) => {
+                if let Some(ref mut devtools) = self.timeline_state.devtools {
+                    devtools.loading = false;
+                    match result {
+                        Ok(text) => devtools.text = text,
+                        Err(e) => devtools.error = Some(e),
+                    }
+                }
+            }
+            Message::CloseDevtools => {
+                self.timeline_state.devtools = None;
             }
             Message::MessageSent(_room_id) => {
                 self.timeline_state.sending = false;
-                return snap_to(
-                    TIMELINE_SCROLLABLE_ID.clone(),
-                    RelativeOffset::END,
-                );
+                return Task::batch(vec![
+                    self.typing_notice(false),
+                    snap_to(TIMELINE_SCROLLABLE_ID.clone(), RelativeOffset::END),
+                    // Edits of replies outside the live timeline only show up on refetch
+                    self.reload_thread(),
+                ]);
             }
             Message::SendError(e) => {
                 self.timeline_state.sending = false;
@@ -569,46 +1694,64 @@ impl cosmic::Application for App {
             }
 
             Message::LoadMoreHistory => {
-                let token = match self.timeline_state.pagination_token.clone() {
-                    Some(t) => t,
-                    None => return Task::none(),
-                };
+                if self.timeline_state.loading || self.timeline_state.at_timeline_start {
+                    return Task::none();
+                }
                 let room_id = match self.timeline_state.room_id.clone() {
                     Some(id) => id,
                     None => return Task::none(),
                 };
-                let client = match self.client.clone() {
-                    Some(c) => c,
+                let timeline = match self.timeline_state.timeline.clone() {
+                    Some(t) => t,
                     None => return Task::none(),
                 };
 
                 self.timeline_state.loading = true;
 
                 return cosmic::task::future(async move {
-                    load_more_history(&client, &room_id, &token).await
+                    paginate_timeline(&timeline, &room_id).await
                 });
             }
-            Message::HistoryLoaded(room_id, items, token) => {
+            Message::HistoryLoaded(room_id, reached_start) => {
                 if self.timeline_state.room_id.as_ref() == Some(&room_id) {
-                    let extra_tasks = if let Some(ref client) = self.client {
-                        let mut t = spawn_image_fetches(&items, &self.images, client);
-                        t.extend(spawn_avatar_fetches_for_timeline(&items, &self.avatars, client));
-                        t
-                    } else {
-                        Vec::new()
-                    };
-                    self.timeline_state.prepend_items(items, token);
-                    matrix::timeline::dedup_adjacent_date_separators(
-                        &mut self.timeline_state.items,
-                    );
-                    matrix::timeline::apply_continuation_markers(
-                        &mut self.timeline_state.items,
-                    );
-                    if !extra_tasks.is_empty() {
-                        return Task::batch(extra_tasks);
+                    self.timeline_state.loading = false;
+                    self.timeline_state.at_timeline_start = reached_start;
+                    if self.timeline_state.jumping_to_unread {
+                        return self.continue_jump_to_unread();
                     }
                 }
             }
+            Message::HistoryFailed(room_id, e) => {
+                tracing::error!("{e}");
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.loading = false;
+                    // Don't retry from the jump; the user can scroll up to try again
+                    self.timeline_state.jumping_to_unread = false;
+                }
+            }
+            Message::FullyReadLoaded(room_id, event_id) => {
+                if self.timeline_state.room_id.as_ref() == Some(&room_id) {
+                    self.timeline_state.fully_read = event_id;
+                }
+            }
+            Message::RefreshPreview => {
+                self.timeline_state.refresh_preview_if_idle();
+            }
+            Message::FlushPending => {
+                if self.timeline_state.draft_dirty {
+                    self.save_draft();
+                }
+                return self.update_fully_read();
+            }
+            Message::JumpToUnread => {
+                if self.timeline_state.fully_read.is_none() {
+                    return Task::none();
+                }
+                self.timeline_state.jumping_to_unread = true;
+                self.timeline_state.jump_pages = 0;
+                self.timeline_state.at_bottom = false;
+                return self.continue_jump_to_unread();
+            }
 
             // -- Cross-signing --
             Message::BootstrapCrossSigning => {
@@ -765,9 +1908,37 @@ impl cosmic::Application for App {
         Task::none()
     }
 
+    fn on_app_exit(&mut self) -> Option<Self::Message> {
+        self.save_draft();
+        if let Some((room, seen)) = self.take_fully_read_update() {
+            block_on_exit(matrix::timeline::send_fully_read(room, seen));
+        }
+        None
+    }
+
     fn subscription(&self) -> Subscription<Self::Message> {
-        let sync_sub = if let Some(ref client) = self.client {
-            matrix::sync::sync_subscription(client.clone())
+        // Every account keeps syncing; its messages are tagged so they can be routed.
+        // Accounts waiting for the user to sign in again are paused until then.
+        let account_subs = self.accounts.iter().filter(|a| !a.needs_reauth).map(|account| {
+            Subscription::batch([
+                matrix::sync::sync_subscription(account.client.clone()),
+                matrix::client::session_changes_subscription(account.client.clone()),
+            ])
+            .with(account.account_id.clone())
+            .map(|(account_id, message)| Message::ForAccount(account_id, Box::new(message)))
+        });
+
+        let timeline_sub = if let (Some(ref room_id), Some(ref timeline)) =
+            (&self.timeline_state.room_id, &self.timeline_state.timeline)
+        {
+            match self.own_user_id {
+                Some(ref uid) => matrix::timeline::timeline_subscription(
+                    room_id.clone(),
+                    timeline.clone(),
+                    uid.clone(),
+                ),
+                None => Subscription::none(),
+            }
         } else {
             Subscription::none()
         };
@@ -784,67 +1955,432 @@ impl cosmic::Application for App {
             Subscription::none()
         };
 
-        Subscription::batch([sync_sub, verify_sub])
+        let room = match (&self.client, &self.timeline_state.room_id) {
+            (Some(client), Some(room_id)) => client.get_room(room_id),
+            _ => None,
+        };
+        let typing_sub = room
+            .clone()
+            .map(matrix::timeline::typing_subscription)
+            .unwrap_or_else(Subscription::none);
+        let fully_read_sub = room
+            .map(matrix::timeline::fully_read_subscription)
+            .unwrap_or_else(Subscription::none);
+
+        // Writes that are batched up rather than made on every change
+        let flush_sub = if self.has_pending_writes() {
+            cosmic::iced::time::every(FLUSH_INTERVAL).map(|_| Message::FlushPending)
+        } else {
+            Subscription::none()
+        };
+
+        let preview_sub = if self.timeline_state.preview_stale_since.is_some() {
+            cosmic::iced::time::every(PREVIEW_DELAY).map(|_| Message::RefreshPreview)
+        } else {
+            Subscription::none()
+        };
+
+        let settings_sub = match (&self.client, &self.room_settings) {
+            (Some(client), Some(state)) => client
+                .get_room(&state.room_id)
+                .map(matrix::room_settings::settings_subscription)
+                .unwrap_or_else(Subscription::none),
+            _ => Subscription::none(),
+        };
+
+        Subscription::batch(account_subs.chain([
+            timeline_sub,
+            typing_sub,
+            fully_read_sub,
+            flush_sub,
+            preview_sub,
+            verify_sub,
+            settings_sub,
+        ]))
+    }
+
+    fn view(&self) -> Element<'_, Self::Message> {
+        let content: Element<_> = match self.view {
+            AppView::Loading => widget::container(widget::text::body("Loading..."))
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .align_x(Alignment::Center)
+                .align_y(Alignment::Center)
+                .into(),
+            AppView::Login => login::login_view(&self.login_state),
+            AppView::Main => self.main_view(),
+        };
+        widget::container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .class(cosmic::theme::Container::Background)
+            .into()
+    }
+
+    fn header_center(&self) -> Vec<Element<'_, Self::Message>> {
+        match self.view {
+            AppView::Main => {
+                let title = self
+                    .rooms_state
+                    .selected_room_name()
+                    .unwrap_or("Cosmic Matrix");
+                vec![widget::text::heading(title).into()]
+            }
+            _ => vec![widget::text::heading("Cosmic Matrix").into()],
+        }
+    }
+
+    fn header_end(&self) -> Vec<Element<'_, Self::Message>> {
+        match self.view {
+            AppView::Main => {
+                let icon_label = match self.cross_signing_status {
+                    CrossSigningStatus::Verified => "🔒",
+                    CrossSigningStatus::Unverified => "🔓",
+                    CrossSigningStatus::Unknown => "?",
+                };
+                let mut items: Vec<Element<'_, Self::Message>> = Vec::new();
+                for account in &self.accounts {
+                    let is_active =
+                        self.active_account.as_deref() == Some(account.account_id.as_str());
+                    let unread = account.rooms_state.unread_total();
+                    let label = if account.needs_reauth {
+                        format!("{} (signed out)", account.label())
+                    } else if !is_active && unread > 0 {
+                        format!("{} ({unread})", account.label())
+                    } else {
+                        account.label()
+                    };
+                    let mut btn = widget::button::text(label)
+                        .on_press(Message::SwitchAccount(account.account_id.clone()));
+                    if is_active {
+                        btn = btn.class(cosmic::theme::Button::Suggested);
+                    }
+                    items.push(btn.into());
+                }
+                items.push(
+                    widget::button::text("+")
+                        .on_press(Message::AddAccount)
+                        .into(),
+                );
+                items.extend([
+                    widget::text::body(icon_label).into(),
+                    widget::button::text("Profile")
+                        .on_press(Message::ShowProfilePanel)
+                        .into(),
+                    widget::button::text("Verify")
+                        .on_press(Message::StartVerification)
+                        .into(),
+                    widget::button::text("Logout")
+                        .on_press(Message::Logout)
+                        .into(),
+                ]);
+                items
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl App {
+    /// Make `account_id` the active account: park the current account's room list,
+    /// bring in the new one's and reset everything tied to the previous account.
+    fn activate_account(&mut self, account_id: &str) -> cosmic::app::Task<Message> {
+        let Some(index) = self.accounts.iter().position(|a| a.account_id == account_id) else {
+            return Task::none();
+        };
+        let sort_mode = self.rooms_state.sort_mode.clone();
+        let sections_collapsed = self.rooms_state.sections_collapsed.clone();
+
+        self.save_draft();
+        let fully_read = self.update_fully_read();
+        if let Some(current) = self.active_account.take() {
+            if let Some(account) = self.accounts.iter_mut().find(|a| a.account_id == current) {
+                std::mem::swap(&mut account.rooms_state, &mut self.rooms_state);
+            }
+        }
+        let account = &mut self.accounts[index];
+        tracing::info!("Active account: {} on {}", account.user_id, account.homeserver);
+        self.rooms_state = std::mem::take(&mut account.rooms_state);
+        self.rooms_state.sort_mode = sort_mode;
+        self.rooms_state.sections_collapsed = sections_collapsed;
+        self.rooms_state.selected = None;
+        self.rooms_state.room_menu = None;
+        self.rooms_state.leaving = None;
+
+        self.client = Some(account.client.clone());
+        self.own_user_id = Some(account.user_id.clone());
+        self.active_account = Some(account_id.to_string());
+        self.login_password.clear();
+        self.timeline_state = TimelineState::default();
+        self.active_verification = None;
+        self.pending_incoming = None;
+        self.cross_signing_status = CrossSigningStatus::Unknown;
+        self.own_avatar = None;
+        self.show_profile_panel = false;
+        self.join_dialog = None;
+        self.create_room_dialog = None;
+        self.new_dm_dialog = None;
+        self.room_settings = None;
+
+        let mut settings = config::load_settings();
+        settings.active_account = Some(account_id.to_string());
+        let _ = config::save_settings(&settings);
+
+        let client = account.client.clone();
+        let client2 = client.clone();
+        let mut tasks = spawn_avatar_fetches_for_rooms(&self.rooms_state.rooms, &self.avatars, &client);
+        tasks.push(cosmic::task::future(async move {
+            fetch_own_avatar((*client).clone()).await
+        }));
+        tasks.push(cosmic::task::future(async move {
+            matrix_verification::fetch_cross_signing_status((*client2).clone()).await
+        }));
+        tasks.push(fully_read);
+        Task::batch(tasks)
+    }
+
+    /// Move the open room's fully-read marker up to the last event we saw, so the
+    /// "New messages" line is in the same place on all our clients. Runs while reading
+    /// (batched by `FLUSH_INTERVAL`) and when leaving the room.
+    fn update_fully_read(&mut self) -> cosmic::app::Task<Message> {
+        match self.take_fully_read_update() {
+            Some((room, seen)) => cosmic::task::future(matrix::timeline::send_fully_read(room, seen)),
+            None => Task::none(),
+        }
+    }
+
+    /// The room and event to move the fully-read marker to, if it is behind. A marker
+    /// synced from another client may already be further along; it never moves back.
+    fn take_fully_read_update(&mut self) -> Option<(matrix_sdk::Room, String)> {
+        let seen = self.timeline_state.seen_up_to.take()?;
+        if !self.timeline_state.is_after_fully_read(&seen) {
+            return None;
+        }
+        let room = match (&self.client, &self.timeline_state.room_id) {
+            (Some(client), Some(room_id)) => client.get_room(room_id),
+            _ => None,
+        }?;
+        self.timeline_state.fully_read = Some(seen.clone());
+        Some((room, seen))
+    }
+
+    /// Whether something is waiting for the next `FlushPending`.
+    fn has_pending_writes(&self) -> bool {
+        self.timeline_state.draft_dirty
+            || self
+                .timeline_state
+                .seen_up_to
+                .as_deref()
+                .is_some_and(|seen| self.timeline_state.is_after_fully_read(seen))
+    }
+
+    /// Scroll to the fully-read marker if it is loaded, otherwise fetch another page of
+    /// history. Gives up at the start of the room or after `MAX_JUMP_PAGES` pages.
+    fn continue_jump_to_unread(&mut self) -> cosmic::app::Task<Message> {
+        if let Some((row, rows)) = self.timeline_state.fully_read_row() {
+            self.timeline_state.jumping_to_unread = false;
+            let last = rows.saturating_sub(1).max(1);
+            return snap_to(
+                TIMELINE_SCROLLABLE_ID.clone(),
+                RelativeOffset {
+                    x: 0.0,
+                    y: (row as f32 / last as f32).min(1.0),
+                },
+            );
+        }
+        if self.timeline_state.at_timeline_start || self.timeline_state.jump_pages >= MAX_JUMP_PAGES
+        {
+            self.timeline_state.jumping_to_unread = false;
+            return Task::none();
+        }
+        self.timeline_state.jump_pages += 1;
+        self.update(Message::LoadMoreHistory)
+    }
+
+    /// Start or stop our typing notice in the open room. `Room::typing_notice`
+    /// throttles re-sends while typing continues, so every keystroke goes through.
+    fn typing_notice(&mut self, typing: bool) -> cosmic::app::Task<Message> {
+        if !self.send_typing_notifications || (!typing && !self.timeline_state.typing_sent) {
+            return Task::none();
+        }
+        let room = match (&self.client, &self.timeline_state.room_id) {
+            (Some(client), Some(room_id)) => client.get_room(room_id),
+            _ => None,
+        };
+        let Some(room) = room else {
+            return Task::none();
+        };
+        self.timeline_state.typing_sent = typing;
+        cosmic::task::future(matrix::timeline::send_typing_notice(room, typing))
+    }
+
+    /// Store the open room's composer contents as its draft and write the account's
+    /// drafts to disk. Runs shortly after typing stops, on room and account switches
+    /// and on exit.
+    fn save_draft(&mut self) {
+        let (Some(account_id), Some(room_id)) =
+            (self.active_account.as_ref(), self.timeline_state.room_id.clone())
+        else {
+            return;
+        };
+        self.timeline_state.draft_dirty = false;
+        match self.timeline_state.draft() {
+            Some(draft) => {
+                self.rooms_state.drafts.insert(room_id, draft);
+            }
+            None => {
+                if self.rooms_state.drafts.remove(&room_id).is_none() {
+                    return;
+                }
+            }
+        }
+        if let Err(e) = config::save_drafts(account_id, &self.rooms_state.drafts) {
+            tracing::warn!("Failed to save drafts: {e}");
+        }
+    }
+
+    /// Ask the user to sign in to a soft-logged-out account again. The account, its
+    /// store and store passphrase stay as they are until then.
+    fn show_reauth(&mut self, account_id: &str) -> cosmic::app::Task<Message> {
+        let can_cancel = self
+            .accounts
+            .iter()
+            .any(|a| a.account_id != account_id && !a.needs_reauth);
+        let Some(account) = self.accounts.iter_mut().find(|a| a.account_id == account_id) else {
+            return Task::none();
+        };
+        account.needs_reauth = true;
+        self.login_state = LoginState {
+            homeserver: account.homeserver.clone(),
+            username: account.user_id.to_string(),
+            can_cancel,
+            reauth: Some(ReauthTarget {
+                account_id: account.account_id.clone(),
+                user_id: account.user_id.clone(),
+            }),
+            ..LoginState::default()
+        };
+        let activate = if self.active_account.as_deref() != Some(account_id) {
+            self.activate_account(account_id)
+        } else {
+            Task::none()
+        };
+        self.view = AppView::Login;
+        Task::batch(vec![activate, self.update(Message::DiscoverLoginFlows)])
+    }
+
+    /// Sign in to the expired account in `login_state.reauth` again, on its existing
+    /// client and device. `None` when this is an ordinary login.
+    fn reauthenticate(&mut self, method: ReauthMethod) -> Option<cosmic::app::Task<Message>> {
+        let target = self.login_state.reauth.clone()?;
+        let client = self
+            .accounts
+            .iter()
+            .find(|a| a.account_id == target.account_id)
+            .map(|a| a.client.clone())?;
+        Some(cosmic::task::future(async move {
+            let result = do_reauth(&client, &target, method).await;
+            Message::ReauthResult(target.account_id, result)
+        }))
+    }
+
+    /// Drop an account and its stored session. Does not touch the active-account state.
+    fn remove_account(&mut self, account_id: &str) {
+        config::remove_session(account_id);
+        self.accounts.retain(|a| a.account_id != account_id);
+    }
+
+    /// Handle a subscription message from an account that is not currently shown.
+    fn update_background_account(
+        &mut self,
+        account_id: &str,
+        message: Message,
+    ) -> cosmic::app::Task<Message> {
+        let Some(account) = self.accounts.iter_mut().find(|a| a.account_id == account_id) else {
+            return Task::none();
+        };
+        match message {
+            Message::RoomsUpdated(rooms) => account.rooms_state.update_rooms(rooms),
+            Message::RoomsChanged { upserted, removed } => {
+                account.rooms_state.apply_room_changes(upserted, &removed);
+            }
+            Message::IncomingEvents(room_id, new_items) => {
+                if self.initial_sync_done {
+                    let label = self.notification_account_label(Some(account_id));
+                    if let Some(account) = self.accounts.iter().find(|a| a.account_id == account_id) {
+                        notify_incoming(
+                            &account.rooms_state,
+                            account.user_id.as_str(),
+                            label.as_deref(),
+                            &room_id,
+                            &new_items,
+                        );
+                    }
+                }
+            }
+            Message::SessionExpired { soft_logout } => {
+                tracing::warn!("Session expired for {} (soft_logout: {soft_logout})", account.user_id);
+                if soft_logout {
+                    account.needs_reauth = true;
+                    self.account_notice = Some(format!(
+                        "The session for {} has expired. Select it to sign in again.",
+                        account.user_id
+                    ));
+                } else {
+                    self.account_notice = Some(format!(
+                        "{} was signed out by the server. Please sign in again.",
+                        account.user_id
+                    ));
+                    self.remove_account(account_id);
+                }
+            }
+            Message::SyncError(e) => {
+                tracing::error!("Sync error for {}: {e}", account.user_id);
+            }
+            _ => {}
+        }
+        Task::none()
     }
 
-    fn view(&self) -> Element<'_, Self::Message> {
-        let content: Element<_> = match self.view {
-            AppView::Loading => widget::container(widget::text::body("Loading..."))
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .align_x(Alignment::Center)
-                .align_y(Alignment::Center)
-                .into(),
-            AppView::Login => login::login_view(&self.login_state),
-            AppView::Main => self.main_view(),
+    /// Refetch the open thread's replies, keeping the current ones on screen meanwhile.
+    fn reload_thread(&self) -> cosmic::app::Task<Message> {
+        let (Some(client), Some(room_id), Some(thread)) = (
+            self.client.clone(),
+            self.timeline_state.room_id.clone(),
+            self.timeline_state.thread.as_ref(),
+        ) else {
+            return Task::none();
         };
-        widget::container(content)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .class(cosmic::theme::Container::Background)
-            .into()
+        let root_event_id = thread.root_event_id.clone();
+        cosmic::task::future(async move { load_thread(&client, &room_id, root_event_id).await })
     }
 
-    fn header_center(&self) -> Vec<Element<'_, Self::Message>> {
-        match self.view {
-            AppView::Main => {
-                let title = self
-                    .rooms_state
-                    .selected_room_name()
-                    .unwrap_or("Cosmic Matrix");
-                vec![widget::text::heading(title).into()]
-            }
-            _ => vec![widget::text::heading("Cosmic Matrix").into()],
-        }
+    /// Threaded read receipt for the latest event in the open thread.
+    fn thread_read_receipt(
+        &self,
+        root: String,
+        latest: Option<String>,
+    ) -> Option<cosmic::app::Task<Message>> {
+        let client = self.client.clone()?;
+        let room_id = self.timeline_state.room_id.clone()?;
+        let latest = latest.filter(|id| id != &root)?;
+        let private = self.private_read_receipts;
+        Some(cosmic::task::future(async move {
+            send_read_receipt(&client, &room_id, &latest, Some(root), private).await
+        }))
     }
 
-    fn header_end(&self) -> Vec<Element<'_, Self::Message>> {
-        match self.view {
-            AppView::Main => {
-                let icon_label = match self.cross_signing_status {
-                    CrossSigningStatus::Verified => "🔒",
-                    CrossSigningStatus::Unverified => "🔓",
-                    CrossSigningStatus::Unknown => "?",
-                };
-                vec![
-                    widget::text::body(icon_label).into(),
-                    widget::button::text("Profile")
-                        .on_press(Message::ShowProfilePanel)
-                        .into(),
-                    widget::button::text("Verify")
-                        .on_press(Message::StartVerification)
-                        .into(),
-                    widget::button::text("Logout")
-                        .on_press(Message::Logout)
-                        .into(),
-                ]
-            }
-            _ => vec![],
+    /// Account name to show in notifications; only needed with several accounts.
+    fn notification_account_label(&self, account_id: Option<&str>) -> Option<String> {
+        if self.accounts.len() < 2 {
+            return None;
         }
+        self.accounts
+            .iter()
+            .find(|a| Some(a.account_id.as_str()) == account_id)
+            .map(|a| a.label())
     }
-}
 
-impl App {
     fn main_view(&self) -> Element<'_, Message> {
         let spacing = cosmic::theme::spacing();
 
@@ -858,14 +2394,61 @@ impl App {
             return profile_ui::profile_panel_view(
                 own_display,
                 self.own_avatar.as_ref(),
+                self.send_typing_notifications,
+                self.private_read_receipts,
             );
         }
 
+        if let Some(ref confirm) = self.rooms_state.leaving {
+            let room_name = self
+                .rooms_state
+                .rooms
+                .iter()
+                .find(|r| r.room_id == confirm.room_id)
+                .map_or(confirm.room_id.as_str(), |r| r.name.as_str());
+            return leave::leave_confirm_view(confirm, room_name);
+        }
+        if let Some(ref state) = self.room_settings {
+            let avatar = state
+                .current
+                .as_ref()
+                .and_then(|s| s.avatar_url.as_ref())
+                .and_then(|url| self.avatars.get(url));
+            return room_settings::room_settings_view(state, avatar);
+        }
+        if let Some(ref dialog) = self.join_dialog {
+            return join::join_dialog_view(dialog, &self.rooms_state, &self.avatars);
+        }
+        if let Some(ref dialog) = self.create_room_dialog {
+            let server_name = self
+                .own_user_id
+                .as_ref()
+                .map(|u| u.server_name().as_str())
+                .unwrap_or("server");
+            return create::create_room_view(dialog, server_name);
+        }
+        if let Some(ref dialog) = self.new_dm_dialog {
+            return create::new_dm_view(dialog, &self.avatars);
+        }
+
         // Sidebar: room list
         let mut sidebar_col = widget::column()
             .spacing(spacing.space_xxs)
             .width(Length::Fixed(280.0));
 
+        if let Some(ref notice) = self.account_notice {
+            sidebar_col = sidebar_col.push(
+                widget::row()
+                    .push(widget::text::caption(notice.as_str()).width(Length::Fill))
+                    .push(
+                        widget::button::text("Dismiss")
+                            .on_press(Message::DismissAccountNotice)
+                            .padding([2, spacing.space_xs]),
+                    )
+                    .align_y(Alignment::Center),
+            );
+        }
+
         // Room search
         sidebar_col = sidebar_col.push(
             widget::text_input::search_input("Search rooms...", &self.rooms_state.filter)
@@ -873,6 +2456,26 @@ impl App {
                 .on_clear(Message::RoomFilterChanged(String::new())),
         );
 
+        sidebar_col = sidebar_col.push(
+            widget::row()
+                .push(
+                    widget::button::text("Join room")
+                        .on_press(Message::ShowJoinDialog)
+                        .padding([2, spacing.space_xs]),
+                )
+                .push(
+                    widget::button::text("New room")
+                        .on_press(Message::ShowCreateRoomDialog)
+                        .padding([2, spacing.space_xs]),
+                )
+                .push(
+                    widget::button::text("New DM")
+                        .on_press(Message::ShowNewDmDialog)
+                        .padding([2, spacing.space_xs]),
+                )
+                .align_y(Alignment::Center),
+        );
+
         // Sort mode selector
         let sort_label = match self.rooms_state.sort_mode {
             SortMode::RecentActivity => "Recent",
@@ -975,7 +2578,25 @@ impl App {
                     } else {
                         info_col = info_col.push(widget::text::body(room.name.clone()));
                     }
-                    if let Some(ref preview) = room.last_message {
+                    let draft = self
+                        .rooms_state
+                        .drafts
+                        .get(&room.room_id)
+                        .filter(|_| !is_selected);
+                    if room.is_invite {
+                        let label = match room.inviter {
+                            Some(ref inviter) => format!("Invited by {}", inviter.name()),
+                            None => "Invited".to_string(),
+                        };
+                        info_col = info_col.push(widget::text::caption(label));
+                    } else if let Some(draft) = draft {
+                        let mut preview: String = draft.text.chars().take(50).collect();
+                        if draft.text.chars().count() > 50 {
+                            preview.push('\u{2026}');
+                        }
+                        info_col = info_col
+                            .push(widget::text::caption(format!("\u{270E} Draft: {preview}")));
+                    } else if let Some(ref preview) = room.last_message {
                         let truncated = if preview.len() > 50 {
                             format!("{}\u{2026}", &preview[..50])
                         } else {
@@ -988,13 +2609,40 @@ impl App {
                     row = row.push(info_col);
                     row = row.push(widget::horizontal_space());
 
-                    let fav_label = if room.is_favourite { "\u{2605}" } else { "\u{2606}" };
-                    let fav_room_id = room.room_id.clone();
-                    row = row.push(
-                        widget::button::text(fav_label)
-                            .on_press(Message::ToggleFavourite(fav_room_id))
-                            .padding([0, 2]),
-                    );
+                    if room.is_invite {
+                        let pending = self.rooms_state.invite_pending.as_ref() == Some(&room.room_id);
+                        let mut accept = widget::button::text("\u{2713}").padding([0, 2]);
+                        let mut decline = widget::button::text("\u{2715}").padding([0, 2]);
+                        if !pending {
+                            accept = accept.on_press(Message::AcceptInvite(room.room_id.clone()));
+                            decline = decline
+                                .on_press(Message::DeclineInvite(room.room_id.clone(), false));
+                        }
+                        row = row
+                            .push(widget::tooltip(
+                                accept,
+                                widget::text::caption("Accept"),
+                                widget::tooltip::Position::Top,
+                            ))
+                            .push(widget::tooltip(
+                                decline,
+                                widget::text::caption("Decline"),
+                                widget::tooltip::Position::Top,
+                            ));
+                    } else {
+                        let fav_label = if room.is_favourite { "\u{2605}" } else { "\u{2606}" };
+                        let fav_room_id = room.room_id.clone();
+                        row = row.push(
+                            widget::button::text(fav_label)
+                                .on_press(Message::ToggleFavourite(fav_room_id))
+                                .padding([0, 2]),
+                        );
+                        row = row.push(
+                            widget::button::text("\u{22EF}")
+                                .on_press(Message::ToggleRoomMenu(room.room_id.clone()))
+                                .padding([0, 2]),
+                        );
+                    }
 
                     if room.mention_count > 0 {
                         row = row.push(
@@ -1022,7 +2670,29 @@ impl App {
                             .width(Length::Fill)
                             .class(cosmic::theme::Button::Text)
                     };
-                    room_list = room_list.push(btn);
+                    // Right-click opens the same menu as the "⋯" button
+                    let mut area = widget::mouse_area(btn);
+                    if !room.is_invite {
+                        area = area.on_right_press(Message::ToggleRoomMenu(room.room_id.clone()));
+                    }
+                    room_list = room_list.push(area);
+
+                    if self.rooms_state.room_menu.as_ref() == Some(&room.room_id) {
+                        room_list = room_list.push(
+                            widget::row()
+                                .push(widget::horizontal_space())
+                                .push(
+                                    widget::button::text("Leave")
+                                        .on_press(Message::ConfirmLeave(room.room_id.clone(), false)),
+                                )
+                                .push(
+                                    widget::button::text("Forget")
+                                        .on_press(Message::ConfirmLeave(room.room_id.clone(), true)),
+                                )
+                                .spacing(spacing.space_xxs)
+                                .padding([0, spacing.space_xs]),
+                        );
+                    }
                 }
             }
 
@@ -1047,6 +2717,27 @@ impl App {
         // Main content: verification panel or room timeline
         if let Some(ref info) = self.active_verification {
             content_col = content_col.push(verification_ui::verification_panel(info));
+        } else if let Some(room) = self.rooms_state.selected_invite() {
+            let avatar = room.avatar_url.as_ref().and_then(|url| self.avatars.get(url));
+            let inviter_avatar = room
+                .inviter
+                .as_ref()
+                .and_then(|i| i.avatar_url.as_ref())
+                .and_then(|url| self.avatars.get(url));
+            let pending = self.rooms_state.invite_pending.as_ref() == Some(&room.room_id);
+            let error = self
+                .rooms_state
+                .invite_error
+                .as_ref()
+                .filter(|(id, _)| id == &room.room_id)
+                .map(|(_, e)| e.as_str());
+            content_col = content_col.push(invite::invite_preview_view(
+                room,
+                avatar,
+                inviter_avatar,
+                pending,
+                error,
+            ));
         } else if self.timeline_state.room_id.is_some() {
             content_col = content_col.push(self.content_view());
         } else {
@@ -1068,6 +2759,8 @@ impl App {
     }
 
     fn content_view(&self) -> Element<'_, Message> {
+        let spacing = cosmic::theme::spacing();
+
         // Room header
         let room_name = self
             .rooms_state
@@ -1086,18 +2779,54 @@ impl App {
             .and_then(|r| r.avatar_url.as_ref())
             .and_then(|url| self.avatars.get(url));
 
-        let header = room_header::room_header_view(room_name, is_encrypted, topic, room_avatar);
+        // Offer the jump while something arrived after our fully-read marker
+        let has_unread = self.timeline_state.fully_read.is_some()
+            && self.timeline_state.fully_read != last_message_event_id(&self.timeline_state.items);
+
+        let header = room_header::room_header_view(
+            self.timeline_state.room_id.as_ref(),
+            room_name,
+            is_encrypted,
+            topic,
+            room_avatar,
+            has_unread,
+        );
 
-        // Timeline
-        let timeline = timeline_ui::timeline_view(&self.timeline_state, &self.images, &self.avatars);
+        // Timeline, or the edit history of one of its messages, or the room devtools
+        let timeline = match (&self.timeline_state.edit_history, &self.timeline_state.devtools) {
+            (Some(history), _) => timeline_ui::edit_history_view(history),
+            (None, Some(devtools)) => timeline_ui::devtools_view(devtools),
+            (None, None) => {
+                timeline_ui::timeline_view(&self.timeline_state, &self.images, &self.avatars)
+            }
+        };
 
         // Composer
         let composer = composer::composer_view(&self.timeline_state);
+        let typing: Element<_> = match self.timeline_state.typing_label() {
+            Some(label) => widget::container(widget::text::caption(label))
+                .padding([0, spacing.space_s])
+                .into(),
+            None => widget::Space::with_height(Length::Fixed(0.0)).into(),
+        };
+
+        // Thread panel beside the timeline
+        let timeline: Element<_> = if self.timeline_state.thread.is_some() {
+            widget::row()
+                .push(timeline)
+                .push(widget::divider::vertical::default())
+                .push(timeline_ui::thread_panel_view(&self.timeline_state, &self.images, &self.avatars))
+                .height(Length::Fill)
+                .into()
+        } else {
+            timeline
+        };
 
         widget::column()
             .push(header)
             .push(widget::divider::horizontal::default())
             .push(timeline)
+            .push(typing)
             .push(widget::divider::horizontal::default())
             .push(composer)
             .width(Length::Fill)
@@ -1108,75 +2837,316 @@ impl App {
 
 // -- Async helpers --
 
+/// Run `future` to completion while the app is shutting down, when tasks handed to
+/// the runtime may never be polled. Gives up after a few seconds.
+fn block_on_exit<F>(future: F)
+where
+    F: std::future::Future + Send + 'static,
+{
+    let worker = std::thread::spawn(move || {
+        match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => {
+                let _ = runtime.block_on(tokio::time::timeout(Duration::from_secs(3), future));
+            }
+            Err(e) => tracing::warn!("Failed to flush on exit: {e}"),
+        }
+    });
+    let _ = worker.join();
+}
+
+/// How to sign in again after a soft logout.
+enum ReauthMethod {
+    Password(String),
+    Sso(Option<String>),
+    Oidc(String),
+}
+
+/// Sign `client` in again to its own device and save the new tokens with the
+/// account's existing store passphrase.
+async fn do_reauth(
+    client: &Client,
+    target: &ReauthTarget,
+    method: ReauthMethod,
+) -> Result<(), String> {
+    let device_id = client
+        .device_id()
+        .map(|d| d.to_owned())
+        .ok_or("The expired session has no device ID")?;
+    let auth = match method {
+        ReauthMethod::Password(password) => {
+            matrix::client::login(client, target.user_id.as_str(), &password, Some(device_id.as_str()))
+                .await?;
+            None
+        }
+        ReauthMethod::Sso(idp_id) => {
+            matrix::client::login_sso(client, idp_id.as_deref(), Some(device_id.as_str())).await?;
+            None
+        }
+        ReauthMethod::Oidc(issuer) => {
+            Some(matrix::client::login_oidc(client, &issuer, Some(device_id)).await?)
+        }
+    };
+    matrix::client::save_reauthenticated_session(client, &target.account_id, auth)
+}
+
 async fn do_login(
     homeserver: &str,
     username: &str,
     password: &str,
 ) -> Result<(Client, LoginSuccess), String> {
-    let client = matrix::client::create_client(homeserver).await?;
-    let response = matrix::client::login(&client, username, password).await?;
+    let (client, account_id, passphrase) = matrix::client::create_login_client(homeserver).await?;
+    let response = matrix::client::login(&client, username, password, None).await?;
 
-    matrix::client::save_session_from_client(&client, homeserver)?;
+    matrix::client::save_session_from_client(
+        &client,
+        &account_id,
+        homeserver,
+        Some(passphrase),
+        AuthKind::Matrix,
+    )?;
 
     Ok((
         client,
         LoginSuccess {
+            account_id,
+            homeserver: homeserver.to_string(),
             user_id: response.user_id,
             device_id: response.device_id.to_string(),
         },
     ))
 }
 
+async fn do_sso_login(
+    homeserver: &str,
+    idp_id: Option<&str>,
+) -> Result<(Client, LoginSuccess), String> {
+    let (client, account_id, passphrase) = matrix::client::create_login_client(homeserver).await?;
+    matrix::client::login_sso(&client, idp_id, None).await?;
+
+    matrix::client::save_session_from_client(
+        &client,
+        &account_id,
+        homeserver,
+        Some(passphrase),
+        AuthKind::Matrix,
+    )?;
+    let success = login_success_from_client(&client, account_id, homeserver)?;
+    Ok((client, success))
+}
+
+async fn do_oidc_login(homeserver: &str, issuer: &str) -> Result<(Client, LoginSuccess), String> {
+    let (client, account_id, passphrase) = matrix::client::create_login_client(homeserver).await?;
+    let auth = matrix::client::login_oidc(&client, issuer, None).await?;
+
+    matrix::client::save_session_from_client(&client, &account_id, homeserver, Some(passphrase), auth)?;
+    let success = login_success_from_client(&client, account_id, homeserver)?;
+    Ok((client, success))
+}
+
+fn login_success_from_client(
+    client: &Client,
+    account_id: String,
+    homeserver: &str,
+) -> Result<LoginSuccess, String> {
+    let user_id = client.user_id().ok_or("No user ID after login")?.to_owned();
+    let device_id = client.device_id().ok_or("No device ID after login")?.to_string();
+    Ok(LoginSuccess {
+        account_id,
+        homeserver: homeserver.to_string(),
+        user_id,
+        device_id,
+    })
+}
+
+/// Restore every stored account; accounts that fail to restore are skipped.
 async fn try_restore_session() -> Result<Message, String> {
-    let stored = config::load_session().ok_or("No session")?;
-    let client = matrix::client::restore_session(&stored).await?;
-    tracing::info!("Session restored for {}", stored.user_id);
-    Ok(Message::SessionRestored(MatrixClient(client)))
+    let mut restored = Vec::new();
+    for stored in config::load_sessions()? {
+        match matrix::client::restore_session(&stored).await {
+            Ok(client) => {
+                tracing::info!("Session restored for {}", stored.user_id);
+                restored.push(RestoredAccount {
+                    account_id: stored.account_id,
+                    homeserver: stored.homeserver,
+                    client: MatrixClient(client),
+                });
+            }
+            Err(e) => tracing::warn!("Session restore failed for {}: {e}", stored.user_id),
+        }
+    }
+    if restored.is_empty() {
+        return Err("No session".to_string());
+    }
+    Ok(Message::SessionRestored(restored))
 }
 
-async fn load_timeline_for_room(client: &Client, room_id: &OwnedRoomId) -> Message {
+async fn open_timeline_for_room(client: &Client, room_id: &OwnedRoomId) -> Message {
     let room = match client.get_room(room_id) {
         Some(r) => r,
-        None => return Message::TimelineUpdated(room_id.clone(), Vec::new(), None),
+        None => return Message::TimelineOpenFailed(room_id.clone(), "Room not found".to_string()),
     };
 
-    match matrix::timeline::load_room_timeline(&room).await {
-        Ok((items, token)) => Message::TimelineUpdated(room_id.clone(), items, token),
-        Err(e) => {
-            tracing::error!("Failed to load timeline: {e}");
-            Message::TimelineUpdated(room_id.clone(), Vec::new(), None)
-        }
+    match matrix::timeline::open_room_timeline(&room).await {
+        Ok(timeline) => Message::TimelineOpened(room_id.clone(), MatrixTimeline(Arc::new(timeline))),
+        Err(e) => Message::TimelineOpenFailed(room_id.clone(), e),
+    }
+}
+
+/// Back-paginate the SDK timeline; new items arrive through the timeline subscription.
+async fn paginate_timeline(timeline: &Timeline, room_id: &OwnedRoomId) -> Message {
+    match timeline.paginate_backwards(HISTORY_PAGE_SIZE).await {
+        Ok(reached_start) => Message::HistoryLoaded(room_id.clone(), reached_start),
+        Err(e) => Message::HistoryFailed(room_id.clone(), format!("Failed to load history: {e}")),
     }
 }
 
+/// `thread` is `(root, latest event)` when sending into a thread; the latest event is
+/// the fallback reply target for clients without thread support.
 async fn send_message(
-    client: &Client,
+    timeline: &Timeline,
     room_id: &OwnedRoomId,
-    text: &str,
+    msgtype: MessageType,
+    mentions: Mentions,
     reply_to: Option<String>,
+    thread: Option<(String, String)>,
 ) -> Message {
-    let room = match client.get_room(room_id) {
-        Some(r) => r,
-        None => return Message::SendError("Room not found".to_string()),
-    };
+    use matrix_sdk::ruma::events::relation::{InReplyTo, Thread};
+    use matrix_sdk::ruma::events::room::message::Relation;
+    use matrix_sdk::ruma::OwnedEventId;
 
-    let mut content = RoomMessageEventContent::text_plain(text);
-    if let Some(event_id_str) = reply_to {
-        use matrix_sdk::ruma::events::relation::InReplyTo;
-        use matrix_sdk::ruma::events::room::message::Relation;
-        use matrix_sdk::ruma::OwnedEventId;
-        if let Ok(eid) = OwnedEventId::try_from(event_id_str.as_str()) {
-            content.relates_to = Some(Relation::Reply {
-                in_reply_to: InReplyTo::new(eid),
-            });
-        }
-    }
-    match room.send(content).await {
+    let mut content = RoomMessageEventContent::new(msgtype).add_mentions(mentions);
+    let reply_to = reply_to.and_then(|id| OwnedEventId::try_from(id.as_str()).ok());
+    let thread = thread.and_then(|(root, latest)| {
+        Some((
+            OwnedEventId::try_from(root.as_str()).ok()?,
+            OwnedEventId::try_from(latest.as_str()).ok()?,
+        ))
+    });
+    content.relates_to = match (thread, reply_to) {
+        (Some((root, _)), Some(eid)) => Some(Relation::Thread(Thread::reply(root, eid))),
+        (Some((root, latest)), None) => Some(Relation::Thread(Thread::plain(root, latest))),
+        (None, Some(eid)) => Some(Relation::Reply {
+            in_reply_to: InReplyTo::new(eid),
+        }),
+        (None, None) => None,
+    };
+    // Goes through the send queue, so a local echo shows up in the timeline immediately.
+    match timeline.send(content.into()).await {
         Ok(_) => Message::MessageSent(room_id.clone()),
         Err(e) => Message::SendError(format!("Failed to send: {e}")),
     }
 }
 
+/// Replace one of our messages with new content (sent as `m.replace`).
+async fn edit_message(
+    timeline: &Timeline,
+    room_id: &OwnedRoomId,
+    event_id: &str,
+    msgtype: MessageType,
+    mentions: Mentions,
+) -> Message {
+    use matrix_sdk::ruma::events::room::message::RoomMessageEventContentWithoutRelation;
+    use matrix_sdk::ruma::OwnedEventId;
+    use matrix_sdk_ui::timeline::EditedContent;
+
+    let Ok(eid) = OwnedEventId::try_from(event_id) else {
+        return Message::SendError(format!("Invalid event ID: {event_id}"));
+    };
+    let Some(item) = timeline.item_by_event_id(&eid).await else {
+        // Older thread replies only exist in the thread panel
+        return match matrix::timeline::edit_event(timeline.room(), &eid, msgtype, mentions).await {
+            Ok(()) => Message::MessageSent(room_id.clone()),
+            Err(e) => Message::SendError(e),
+        };
+    };
+    let content = RoomMessageEventContentWithoutRelation::new(msgtype).add_mentions(mentions);
+    match timeline.edit(&item, EditedContent::RoomMessage(content)).await {
+        Ok(_) => Message::MessageSent(room_id.clone()),
+        Err(e) => Message::SendError(format!("Failed to edit: {e}")),
+    }
+}
+
+/// Add or remove our reaction; the timeline shows the change as a local echo.
+async fn toggle_reaction(timeline: &Timeline, event_id: &str, key: &str) -> Message {
+    use matrix_sdk::ruma::OwnedEventId;
+    use matrix_sdk_ui::timeline::TimelineEventItemId;
+
+    let room_id = timeline.room().room_id().to_owned();
+    let Ok(eid) = OwnedEventId::try_from(event_id) else {
+        return Message::ReactionFailed(room_id, format!("Invalid event ID: {event_id}"));
+    };
+    if timeline.item_by_event_id(&eid).await.is_none() {
+        // Older thread replies only exist in the thread panel, which is refetched to
+        // show the change
+        return match matrix::timeline::toggle_reaction_on_event(timeline.room(), &eid, key).await {
+            Ok(()) => Message::ReloadThread,
+            Err(e) => Message::ReactionFailed(room_id, e),
+        };
+    }
+    match timeline.toggle_reaction(&TimelineEventItemId::EventId(eid), key).await {
+        Ok(_) => Message::None,
+        Err(e) => Message::ReactionFailed(room_id, format!("Failed to react: {e}")),
+    }
+}
+
+async fn load_thread(client: &Client, room_id: &OwnedRoomId, root_event_id: String) -> Message {
+    use matrix_sdk::ruma::OwnedEventId;
+
+    let Some(own_user_id) = client.user_id() else {
+        return Message::ThreadLoaded(root_event_id, Err("Not signed in".to_string()));
+    };
+    let result = match (client.get_room(room_id), OwnedEventId::try_from(root_event_id.as_str())) {
+        (Some(room), Ok(root)) => {
+            matrix::timeline::fetch_thread_replies(&room, &root, own_user_id).await
+        }
+        (None, _) => Err("Room not found".to_string()),
+        (_, Err(e)) => Err(format!("Invalid event ID: {e}")),
+    };
+    Message::ThreadLoaded(root_event_id, result)
+}
+
+async fn redact_event(client: &Client, room_id: &OwnedRoomId, event_id: &str, reason: &str) -> Message {
+    use matrix_sdk::ruma::OwnedEventId;
+
+    let Some(room) = client.get_room(room_id) else {
+        return Message::RedactionFailed(room_id.clone(), "Room not found".to_string());
+    };
+    let Ok(eid) = OwnedEventId::try_from(event_id) else {
+        return Message::RedactionFailed(room_id.clone(), format!("Invalid event ID: {event_id}"));
+    };
+    let reason = (!reason.is_empty()).then_some(reason);
+    match room.redact(&eid, reason, None).await {
+        Ok(_) => Message::MessageRedacted,
+        Err(e) => {
+            Message::RedactionFailed(room_id.clone(), format!("Failed to delete message: {e}"))
+        }
+    }
+}
+
+async fn load_edit_history(
+    client: &Client,
+    room_id: &OwnedRoomId,
+    timeline: &Timeline,
+    event_id: String,
+    sender: String,
+) -> Message {
+    use matrix_sdk::ruma::OwnedEventId;
+
+    let result = match (
+        client.get_room(room_id),
+        OwnedEventId::try_from(event_id.as_str()),
+        OwnedUserId::try_from(sender.as_str()),
+    ) {
+        (Some(room), Ok(eid), Ok(sender)) => {
+            matrix::timeline::fetch_edit_history(&room, timeline, &eid, &sender).await
+        }
+        (None, _, _) => Err("Room not found".to_string()),
+        (_, Err(e), _) => Err(format!("Invalid event ID: {e}")),
+        (_, _, Err(e)) => Err(format!("Invalid sender: {e}")),
+    };
+    Message::EditHistoryLoaded(event_id, result)
+}
+
 async fn pick_and_send_attachment(client: &Client, room_id: &OwnedRoomId) -> Message {
     use cosmic::dialog::file_chooser;
 
@@ -1330,6 +3300,34 @@ fn spawn_avatar_fetches_for_timeline(
     tasks
 }
 
+/// Avatars of members shown in read receipts, when not cached yet.
+fn spawn_avatar_fetches_for_readers(
+    items: &[TimelineItem],
+    members: &[crate::message::RoomMember],
+    avatars: &HashMap<String, ImageHandle>,
+    client: &Arc<Client>,
+) -> Vec<cosmic::app::Task<Message>> {
+    let mut tasks = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for item in items {
+        let TimelineItem::Message(msg) = item else {
+            continue;
+        };
+        for user_id in &msg.read_by {
+            let url = members
+                .iter()
+                .find(|m| m.user_id.as_str() == user_id)
+                .and_then(|m| m.avatar_url.as_ref());
+            if let Some(url) = url {
+                if !avatars.contains_key(url) && seen.insert(url.clone()) {
+                    tasks.push(spawn_avatar_fetch(client.clone(), url.clone()));
+                }
+            }
+        }
+    }
+    tasks
+}
+
 /// Collect avatar fetch tasks for room avatars not yet cached.
 fn spawn_avatar_fetches_for_rooms(
     rooms: &[crate::message::RoomEntry],
@@ -1339,7 +3337,8 @@ fn spawn_avatar_fetches_for_rooms(
     let mut tasks = Vec::new();
     let mut seen = std::collections::HashSet::new();
     for room in rooms {
-        if let Some(ref url) = room.avatar_url {
+        let inviter_avatar = room.inviter.as_ref().and_then(|i| i.avatar_url.as_ref());
+        for url in room.avatar_url.iter().chain(inviter_avatar) {
             if !avatars.contains_key(url) && seen.insert(url.clone()) {
                 tasks.push(spawn_avatar_fetch(client.clone(), url.clone()));
             }
@@ -1398,58 +3397,6 @@ async fn fetch_image_data(
     }
 }
 
-async fn load_more_history(
-    client: &Client,
-    room_id: &OwnedRoomId,
-    token: &str,
-) -> Message {
-    let room = match client.get_room(room_id) {
-        Some(r) => r,
-        None => return Message::HistoryLoaded(room_id.clone(), Vec::new(), None),
-    };
-
-    let (display_names, avatar_urls) = matrix::timeline::build_member_info(&room).await;
-    let options = matrix_sdk::room::MessagesOptions::backward().from(Some(token));
-    match room.messages(options).await {
-        Ok(messages) => {
-            let mut items = Vec::new();
-            let mut last_date: Option<chrono::NaiveDate> = None;
-            for event in messages.chunk.iter().rev() {
-                if let Ok(ev) = event.raw().deserialize() {
-                    let ts_millis: i64 = ev.origin_server_ts().0.into();
-                    let item_date = matrix::timeline::ts_to_naive_date(ts_millis);
-
-                    if let Some(date) = item_date {
-                        if last_date.as_ref() != Some(&date) {
-                            items.push(crate::message::TimelineItem::DateSeparator(
-                                matrix::timeline::format_date_label(date),
-                            ));
-                            last_date = Some(date);
-                        }
-                    }
-
-                    match ev {
-                        AnySyncTimelineEvent::MessageLike(msg_ev) => {
-                            if let Some(item) =
-                                matrix::timeline::convert_message_event(&msg_ev, &display_names, &avatar_urls)
-                            {
-                                items.push(item);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            matrix::timeline::apply_continuation_markers(&mut items);
-            Message::HistoryLoaded(room_id.clone(), items, messages.end)
-        }
-        Err(e) => {
-            tracing::error!("Failed to load history: {e}");
-            Message::HistoryLoaded(room_id.clone(), Vec::new(), None)
-        }
-    }
-}
-
 async fn toggle_favourite_tag(
     client: matrix_sdk::Client,
     room_id: matrix_sdk::ruma::OwnedRoomId,
@@ -1479,11 +3426,11 @@ async fn toggle_favourite_tag(
     }
 }
 
-/// Returns the event_id of the last real message in a timeline item list.
+/// Returns the event_id of the last real main-timeline message (not a thread reply).
 fn last_message_event_id(items: &[crate::message::TimelineItem]) -> Option<String> {
     items.iter().rev().find_map(|item| {
         if let crate::message::TimelineItem::Message(msg) = item {
-            if !msg.event_id.is_empty() {
+            if !msg.event_id.is_empty() && msg.thread_root.is_none() {
                 return Some(msg.event_id.clone());
             }
         }
@@ -1491,10 +3438,13 @@ fn last_message_event_id(items: &[crate::message::TimelineItem]) -> Option<Strin
     })
 }
 
+/// Mark `event_id` as read, in the main timeline or (with `thread_root`) in a thread.
 async fn send_read_receipt(
     client: &matrix_sdk::Client,
     room_id: &matrix_sdk::ruma::OwnedRoomId,
     event_id: &str,
+    thread_root: Option<String>,
+    private: bool,
 ) -> Message {
     use matrix_sdk::ruma::api::client::receipt::create_receipt::v3::ReceiptType;
     use matrix_sdk::ruma::events::receipt::ReceiptThread;
@@ -1504,23 +3454,75 @@ async fn send_read_receipt(
         Some(r) => r,
         None => return Message::None,
     };
-    if let Ok(eid) = OwnedEventId::try_from(event_id) {
-        // Send m.read receipt to clear server-side unread count
-        let _ = room
-            .send_single_receipt(ReceiptType::Read, ReceiptThread::Unthreaded, eid.clone())
-            .await;
-        // Send m.fully_read marker so other clients know the read position
-        let _ = room
-            .send_single_receipt(ReceiptType::FullyRead, ReceiptThread::Unthreaded, eid)
-            .await;
+    let Ok(eid) = OwnedEventId::try_from(event_id) else {
+        return Message::None;
+    };
+    // Private receipts clear our unread counts without showing others where we are.
+    let receipt_type = if private {
+        ReceiptType::ReadPrivate
+    } else {
+        ReceiptType::Read
+    };
+    match thread_root.map(|root| OwnedEventId::try_from(root.as_str())) {
+        Some(Ok(root)) => {
+            let _ = room
+                .send_single_receipt(receipt_type, ReceiptThread::Thread(root), eid)
+                .await;
+        }
+        Some(Err(_)) => {}
+        None => {
+            // Send read receipt to clear server-side unread count. The fully-read
+            // marker only moves when we leave the room (see `update_fully_read`).
+            let _ = room
+                .send_single_receipt(receipt_type, ReceiptThread::Main, eid)
+                .await;
+        }
     }
     Message::None
 }
 
+/// Notify about new messages from others in `room_id`. With several accounts the
+/// summary also names the account, e.g. "Room · alice".
+fn notify_incoming(
+    rooms_state: &RoomsState,
+    own_id: &str,
+    account_label: Option<&str>,
+    room_id: &OwnedRoomId,
+    new_items: &[TimelineItem],
+) {
+    let room_name = rooms_state.rooms.iter()
+        .find(|r| &r.room_id == room_id)
+        .map(|r| r.name.clone())
+        .unwrap_or_else(|| room_id.to_string());
+    let summary = match account_label {
+        Some(label) => format!("{room_name} · {label}"),
+        None => room_name,
+    };
+    let notifiable: Vec<(String, String)> = new_items.iter().filter_map(|item| {
+        if let TimelineItem::Message(msg) = item {
+            if msg.sender != own_id && !msg.event_id.is_empty() {
+                let body = if msg.image.is_some() {
+                    "📷 Image".to_string()
+                } else {
+                    msg.body.chars().take(100).collect()
+                };
+                return Some((msg.sender_display.clone(), body));
+            }
+        }
+        None
+    }).collect();
+    if notifiable.len() == 1 {
+        let (ref sender, ref body) = notifiable[0];
+        send_notification(&summary, sender, body);
+    } else if notifiable.len() > 1 {
+        send_batch_notification(&summary, notifiable.len());
+    }
+}
+
 /// Send a desktop notification in a background thread to avoid blocking the executor.
 /// Batches multiple messages: if count > 1, shows a summary instead of individual messages.
-fn send_notification(room_name: &str, sender: &str, body: &str) {
-    let summary = room_name.to_string();
+fn send_notification(summary: &str, sender: &str, body: &str) {
+    let summary = summary.to_string();
     let full_body = format!("{sender}: {body}");
     std::thread::spawn(move || {
         let _ = notify_rust::Notification::new()
@@ -1532,8 +3534,8 @@ fn send_notification(room_name: &str, sender: &str, body: &str) {
     });
 }
 
-fn send_batch_notification(room_name: &str, count: usize) {
-    let summary = room_name.to_string();
+fn send_batch_notification(summary: &str, count: usize) {
+    let summary = summary.to_string();
     let body = format!("{count} new messages");
     std::thread::spawn(move || {
         let _ = notify_rust::Notification::new()
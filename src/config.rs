@@ -1,15 +1,47 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
+use matrix_sdk::ruma::OwnedRoomId;
+
+use crate::message::{Pill, ReplyContext};
+use crate::secrets::{self, SessionSecrets};
+
 pub const APP_ID: &str = "com.cosmic.CosmicMatrix";
 
+/// How the session was obtained; decides how it is restored.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AuthKind {
+    /// Classic Matrix auth (password or SSO login token).
+    #[default]
+    Matrix,
+    /// Next-gen auth (MSC3861) via a dynamically registered OIDC client.
+    Oidc {
+        issuer: String,
+        client_id: String,
+        redirect_uri: String,
+    },
+}
+
+/// Session metadata for one account in `accounts.json`. Secrets are never serialized;
+/// they live in the keyring and are filled in by `load_sessions`.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct StoredSession {
+    /// Local identifier naming the account's store directory.
+    #[serde(default)]
+    pub account_id: String,
     pub homeserver: String,
     pub user_id: String,
+    /// Read from legacy plaintext session files only, for migration.
+    #[serde(default, skip_serializing)]
     pub access_token: String,
     pub device_id: String,
+    #[serde(default)]
+    pub auth: AuthKind,
+    #[serde(skip)]
+    pub refresh_token: Option<String>,
+    #[serde(skip)]
+    pub store_passphrase: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
@@ -19,52 +51,255 @@ pub enum SortMode {
     Alphabetical,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppSettings {
     #[serde(default)]
     pub sort_mode: SortMode,
     /// Maps section key → collapsed state. Missing key = not collapsed.
     #[serde(default)]
     pub sections_collapsed: HashMap<String, bool>,
+    /// Account shown on startup.
+    #[serde(default)]
+    pub active_account: Option<String>,
+    #[serde(default = "default_true")]
+    pub send_typing_notifications: bool,
+    /// Send `m.read.private` instead of public read receipts.
+    #[serde(default)]
+    pub private_read_receipts: bool,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            sort_mode: SortMode::default(),
+            sections_collapsed: HashMap::new(),
+            active_account: None,
+            send_typing_notifications: true,
+            private_read_receipts: false,
+        }
+    }
 }
 
+fn default_true() -> bool {
+    true
+}
+
+/// Unsent composer contents for one room.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Draft {
+    pub text: String,
+    #[serde(default)]
+    pub reply_to: Option<ReplyContext>,
+    /// Completed mentions in `text`, so they still link once the draft is restored.
+    #[serde(default)]
+    pub pills: Vec<Pill>,
+}
+
+#[cfg(not(test))]
 pub fn config_dir() -> PathBuf {
     let base = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
     base.join("cosmic-matrix")
 }
 
+#[cfg(not(test))]
 pub fn data_dir() -> PathBuf {
     let base = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
     base.join("cosmic-matrix")
 }
 
+#[cfg(test)]
+thread_local! {
+    /// Tests never touch the user's files; each test thread gets its own directory.
+    static TEST_DIR: PathBuf =
+        std::env::temp_dir().join(format!("cosmic-matrix-test-{}", new_account_id()));
+}
+
+#[cfg(test)]
+pub fn config_dir() -> PathBuf {
+    TEST_DIR.with(|dir| dir.join("config"))
+}
+
+#[cfg(test)]
+pub fn data_dir() -> PathBuf {
+    TEST_DIR.with(|dir| dir.join("data"))
+}
+
+/// Legacy single-account session file, migrated into `accounts.json` on first load.
 pub fn session_path() -> PathBuf {
     config_dir().join("session.json")
 }
 
+pub fn accounts_path() -> PathBuf {
+    config_dir().join("accounts.json")
+}
+
 pub fn settings_path() -> PathBuf {
     config_dir().join("settings.json")
 }
 
-pub fn save_session(session: &StoredSession) -> Result<(), String> {
+/// Each account gets its own SQLite store.
+pub fn account_store_path(account_id: &str) -> PathBuf {
+    data_dir().join("accounts").join(account_id).join("matrix-store")
+}
+
+pub fn drafts_path(account_id: &str) -> PathBuf {
+    data_dir().join("accounts").join(account_id).join("drafts.json")
+}
+
+pub fn new_account_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+pub fn has_session() -> bool {
+    session_path().exists() || !read_accounts().unwrap_or_default().is_empty()
+}
+
+fn read_accounts() -> Result<Vec<StoredSession>, String> {
+    let path = accounts_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&data).map_err(|e| e.to_string())
+}
+
+fn write_accounts(sessions: &[StoredSession]) -> Result<(), String> {
     let dir = config_dir();
     std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
-    let json = serde_json::to_string_pretty(session).map_err(|e| e.to_string())?;
-    std::fs::write(session_path(), json).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(sessions).map_err(|e| e.to_string())?;
+    std::fs::write(accounts_path(), json).map_err(|e| e.to_string())?;
     Ok(())
 }
 
-pub fn load_session() -> Option<StoredSession> {
+fn save_session_secrets(session: &StoredSession) -> Result<(), String> {
+    secrets::save_secrets(
+        &session.account_id,
+        &SessionSecrets {
+            access_token: session.access_token.clone(),
+            refresh_token: session.refresh_token.clone(),
+            store_passphrase: session.store_passphrase.clone(),
+        },
+    )
+}
+
+/// Add or replace (by `account_id`) an account and its keyring secrets.
+pub fn save_session(session: &StoredSession) -> Result<(), String> {
+    save_session_secrets(session)?;
+
+    let mut sessions = read_accounts()?;
+    match sessions.iter_mut().find(|s| s.account_id == session.account_id) {
+        Some(existing) => *existing = session.clone(),
+        None => sessions.push(session.clone()),
+    }
+    write_accounts(&sessions)
+}
+
+/// Move a pre-multi-account `session.json` (and its store) into `accounts.json`.
+fn migrate_legacy_session() -> Result<(), String> {
     let path = session_path();
     if !path.exists() {
-        return None;
+        return Ok(());
     }
-    let data = std::fs::read_to_string(path).ok()?;
-    serde_json::from_str(&data).ok()
+    let data = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let mut session: StoredSession = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+    session.account_id = new_account_id();
+
+    if !session.access_token.is_empty() {
+        // Plaintext token: move it into the keyring. The existing store was created
+        // without a passphrase, so it stays unencrypted until the next fresh login.
+        tracing::info!("Migrating plaintext session for {} to the keyring", session.user_id);
+        session.store_passphrase = None;
+        save_session_secrets(&session)?;
+    }
+
+    let legacy_store = data_dir().join("matrix-store");
+    if legacy_store.exists() {
+        let new_store = account_store_path(&session.account_id);
+        if let Some(parent) = new_store.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::rename(&legacy_store, &new_store)
+            .map_err(|e| format!("Failed to move store: {e}"))?;
+    }
+
+    let mut sessions = read_accounts()?;
+    sessions.push(session);
+    write_accounts(&sessions)?;
+    std::fs::remove_file(path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Load every stored account with its keyring secrets. Accounts whose secrets are
+/// missing are skipped with a warning.
+pub fn load_sessions() -> Result<Vec<StoredSession>, String> {
+    migrate_legacy_session()?;
+
+    let mut sessions = Vec::new();
+    let mut migrated = HashSet::new();
+    for mut session in read_accounts()? {
+        let loaded = match secrets::load_secrets(&session.account_id) {
+            Ok(None) => migrate_user_keyed_secrets(&session, &mut migrated),
+            loaded => loaded,
+        };
+        match loaded {
+            Ok(Some(secrets)) => {
+                session.access_token = secrets.access_token;
+                session.refresh_token = secrets.refresh_token;
+                session.store_passphrase = secrets.store_passphrase;
+                sessions.push(session);
+            }
+            Ok(None) => tracing::warn!("No keyring secrets for {}", session.user_id),
+            Err(e) => tracing::warn!("Skipping {}: {e}", session.user_id),
+        }
+    }
+    // Only once every account sharing the MXID has its own copy.
+    for user_id in migrated {
+        secrets::delete_secrets(&user_id);
+    }
+    Ok(sessions)
 }
 
-pub fn clear_session() {
-    let _ = std::fs::remove_file(session_path());
+/// Copy secrets from an item keyed by MXID, as written before items were keyed by
+/// account, to the account's own item. `migrated` collects the old keys to delete.
+fn migrate_user_keyed_secrets(
+    session: &StoredSession,
+    migrated: &mut HashSet<String>,
+) -> Result<Option<SessionSecrets>, String> {
+    let Some(secrets) = secrets::load_secrets(&session.user_id)? else {
+        return Ok(None);
+    };
+    secrets::save_secrets(&session.account_id, &secrets)?;
+    migrated.insert(session.user_id.clone());
+    Ok(Some(secrets))
+}
+
+/// Forget an account: its entry, keyring secrets and store.
+pub fn remove_session(account_id: &str) {
+    let mut sessions = read_accounts().unwrap_or_default();
+    sessions.retain(|s| s.account_id != account_id);
+    let _ = write_accounts(&sessions);
+    secrets::delete_secrets(account_id);
+
+    if let Some(account_dir) = account_store_path(account_id).parent() {
+        let _ = std::fs::remove_dir_all(account_dir);
+    }
+}
+
+pub fn save_drafts(account_id: &str, drafts: &HashMap<OwnedRoomId, Draft>) -> Result<(), String> {
+    let path = drafts_path(account_id);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(drafts).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn load_drafts(account_id: &str) -> HashMap<OwnedRoomId, Draft> {
+    let Ok(data) = std::fs::read_to_string(drafts_path(account_id)) else {
+        return HashMap::new();
+    };
+    serde_json::from_str(&data).unwrap_or_default()
 }
 
 pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
@@ -86,3 +321,148 @@ pub fn load_settings() -> AppSettings {
     };
     serde_json::from_str(&data).unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Removes the thread's test directory when the test ends.
+    struct Cleanup;
+
+    impl Drop for Cleanup {
+        fn drop(&mut self) {
+            TEST_DIR.with(|dir| {
+                let _ = std::fs::remove_dir_all(dir);
+            });
+        }
+    }
+
+    fn session(user_id: &str) -> StoredSession {
+        StoredSession {
+            account_id: new_account_id(),
+            homeserver: "https://example.org".to_string(),
+            user_id: user_id.to_string(),
+            access_token: "syt_token".to_string(),
+            device_id: "DEVICE".to_string(),
+            auth: AuthKind::Matrix,
+            refresh_token: None,
+            store_passphrase: Some("passphrase".to_string()),
+        }
+    }
+
+    #[test]
+    fn saved_session_keeps_secrets_out_of_accounts_file() {
+        let _cleanup = Cleanup;
+        save_session(&session("@alice:example.org")).unwrap();
+
+        let json = std::fs::read_to_string(accounts_path()).unwrap();
+        assert!(!json.contains("syt_token"));
+        assert!(!json.contains("passphrase"));
+
+        let loaded = load_sessions().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].access_token, "syt_token");
+        assert_eq!(loaded[0].store_passphrase.as_deref(), Some("passphrase"));
+    }
+
+    #[test]
+    fn account_without_secrets_is_skipped() {
+        let _cleanup = Cleanup;
+        let alice = session("@alice:example.org");
+        save_session(&alice).unwrap();
+        secrets::delete_secrets(&alice.account_id);
+
+        assert!(load_sessions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn removing_session_deletes_secrets_and_store() {
+        let _cleanup = Cleanup;
+        let alice = session("@alice:example.org");
+        save_session(&alice).unwrap();
+        let store = account_store_path(&alice.account_id);
+        std::fs::create_dir_all(&store).unwrap();
+
+        remove_session(&alice.account_id);
+
+        assert!(read_accounts().unwrap().is_empty());
+        assert!(secrets::load_secrets(&alice.account_id).unwrap().is_none());
+        assert!(!store.exists());
+    }
+
+    #[test]
+    fn accounts_of_the_same_user_keep_their_own_secrets() {
+        let _cleanup = Cleanup;
+        let old = session("@alice:example.org");
+        let mut new = session("@alice:example.org");
+        new.access_token = "syt_new".to_string();
+        new.store_passphrase = Some("new passphrase".to_string());
+        save_session(&old).unwrap();
+        save_session(&new).unwrap();
+
+        let loaded = load_sessions().unwrap();
+        let find = |id: &str| loaded.iter().find(|s| s.account_id == id).unwrap();
+        assert_eq!(find(&old.account_id).access_token, "syt_token");
+        assert_eq!(find(&new.account_id).access_token, "syt_new");
+        assert_eq!(
+            find(&new.account_id).store_passphrase.as_deref(),
+            Some("new passphrase")
+        );
+    }
+
+    #[test]
+    fn user_keyed_secrets_move_to_the_account() {
+        let _cleanup = Cleanup;
+        let alice = session("@alice:example.org");
+        save_session(&alice).unwrap();
+        let stored = secrets::load_secrets(&alice.account_id).unwrap().unwrap();
+        secrets::delete_secrets(&alice.account_id);
+        secrets::save_secrets(&alice.user_id, &stored).unwrap();
+
+        let loaded = load_sessions().unwrap();
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].access_token, "syt_token");
+        assert!(secrets::load_secrets(&alice.account_id).unwrap().is_some());
+        assert!(secrets::load_secrets(&alice.user_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn legacy_plaintext_session_is_migrated() {
+        let _cleanup = Cleanup;
+        std::fs::create_dir_all(config_dir()).unwrap();
+        std::fs::write(
+            session_path(),
+            r#"{"homeserver":"https://example.org","user_id":"@alice:example.org","access_token":"syt_legacy","device_id":"DEVICE"}"#,
+        )
+        .unwrap();
+        let legacy_store = data_dir().join("matrix-store");
+        std::fs::create_dir_all(&legacy_store).unwrap();
+        std::fs::write(legacy_store.join("matrix-sdk-state.sqlite3"), "").unwrap();
+
+        let sessions = load_sessions().unwrap();
+
+        assert_eq!(sessions.len(), 1);
+        let alice = &sessions[0];
+        assert_eq!(alice.user_id, "@alice:example.org");
+        assert_eq!(alice.access_token, "syt_legacy");
+        // The old store was never encrypted.
+        assert_eq!(alice.store_passphrase, None);
+
+        assert!(!session_path().exists());
+        assert!(!std::fs::read_to_string(accounts_path())
+            .unwrap()
+            .contains("syt_legacy"));
+        assert!(!legacy_store.exists());
+        assert!(account_store_path(&alice.account_id)
+            .join("matrix-sdk-state.sqlite3")
+            .exists());
+    }
+
+    #[test]
+    fn no_legacy_session_is_a_no_op() {
+        let _cleanup = Cleanup;
+        assert!(load_sessions().unwrap().is_empty());
+        assert!(!accounts_path().exists());
+    }
+}
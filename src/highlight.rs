@@ -0,0 +1,111 @@
+//! Syntax highlighting for code blocks. Tokens are classified rather than coloured
+//! here, so the timeline can pick colours for the current theme.
+
+use std::sync::LazyLock;
+
+use syntect::parsing::{ParseState, ScopeRangeIterator, ScopeStack, SyntaxReference, SyntaxSet};
+
+use crate::message::{CodeToken, TokenKind};
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// Split `code` into lines of tokens. Uses the fence language (`rust`, `sh`, `py`…)
+/// if known, else a first-line guess such as a shebang; unknown code stays plain.
+pub fn highlight(language: Option<&str>, code: &str) -> Vec<Vec<CodeToken>> {
+    match find_syntax(language, code) {
+        Some(syntax) => highlight_with(syntax, code).unwrap_or_else(|e| {
+            tracing::debug!("Highlighting failed: {e}");
+            plain(code)
+        }),
+        None => plain(code),
+    }
+}
+
+fn find_syntax(language: Option<&str>, code: &str) -> Option<&'static SyntaxReference> {
+    let syntaxes = &*SYNTAXES;
+    match language {
+        Some(lang) => {
+            let lang = lang.to_lowercase();
+            let token = match lang.as_str() {
+                "shell" | "console" | "zsh" => "sh",
+                "rs" => "rust",
+                other => other,
+            };
+            syntaxes.find_syntax_by_token(token)
+        }
+        None => syntaxes.find_syntax_by_first_line(code.lines().next()?),
+    }
+}
+
+fn highlight_with(syntax: &SyntaxReference, code: &str) -> Result<Vec<Vec<CodeToken>>, String> {
+    let syntaxes = &*SYNTAXES;
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = Vec::new();
+
+    for line in code.split('\n') {
+        // The `newlines` syntax set expects each line to end in '\n'.
+        let line = format!("{line}\n");
+        let ops = state.parse_line(&line, syntaxes).map_err(|e| e.to_string())?;
+        let mut tokens: Vec<CodeToken> = Vec::new();
+        for (range, op) in ScopeRangeIterator::new(&ops, &line) {
+            stack.apply(op).map_err(|e| e.to_string())?;
+            let text = line[range].trim_end_matches('\n');
+            if text.is_empty() {
+                continue;
+            }
+            let kind = classify(&stack);
+            match tokens.last_mut() {
+                Some(last) if last.kind == kind => last.text.push_str(text),
+                _ => tokens.push(CodeToken {
+                    text: text.to_string(),
+                    kind,
+                }),
+            }
+        }
+        lines.push(tokens);
+    }
+    Ok(lines)
+}
+
+/// Innermost recognised scope decides the class.
+fn classify(stack: &ScopeStack) -> TokenKind {
+    for scope in stack.as_slice().iter().rev() {
+        let name = scope.build_string();
+        let kind = if name.starts_with("comment") {
+            TokenKind::Comment
+        } else if name.starts_with("string") {
+            TokenKind::String
+        } else if name.starts_with("constant") {
+            TokenKind::Constant
+        } else if name.starts_with("entity.name.function") || name.starts_with("support.function") {
+            TokenKind::Function
+        } else if name.starts_with("entity.name.type")
+            || name.starts_with("support.type")
+            || name.starts_with("storage.type")
+        {
+            TokenKind::Type
+        } else if name.starts_with("keyword") || name.starts_with("storage") {
+            TokenKind::Keyword
+        } else {
+            continue;
+        };
+        return kind;
+    }
+    TokenKind::Plain
+}
+
+fn plain(code: &str) -> Vec<Vec<CodeToken>> {
+    code.split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                Vec::new()
+            } else {
+                vec![CodeToken {
+                    text: line.to_string(),
+                    kind: TokenKind::Plain,
+                }]
+            }
+        })
+        .collect()
+}
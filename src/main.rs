@@ -1,7 +1,9 @@
 mod app;
 mod config;
+mod highlight;
 mod matrix;
 mod message;
+mod secrets;
 mod state;
 mod ui;
 
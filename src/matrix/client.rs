@@ -1,73 +1,368 @@
+use std::sync::Arc;
+
+use cosmic::iced::futures::SinkExt;
+use cosmic::iced::stream;
+use cosmic::iced::Subscription;
 use matrix_sdk::matrix_auth::{MatrixSession, MatrixSessionTokens};
+use matrix_sdk::oidc::types::iana::oauth::OAuthClientAuthenticationMethod;
+use matrix_sdk::oidc::types::oidc::ApplicationType;
+use matrix_sdk::oidc::types::registration::{ClientMetadata, Localized, VerifiedClientMetadata};
+use matrix_sdk::oidc::types::requests::GrantType;
+use matrix_sdk::oidc::registrations::ClientId;
+use matrix_sdk::oidc::{OidcSession, OidcSessionTokens, UserSession};
+use matrix_sdk::ruma::api::client::session::get_login_types::v3::LoginType;
 use matrix_sdk::ruma::{OwnedDeviceId, OwnedUserId};
-use matrix_sdk::Client;
+use matrix_sdk::{AuthSession, Client, SessionChange};
+use tokio::sync::broadcast::error::RecvError;
+use url::Url;
 
-use crate::config::{self, StoredSession};
+use crate::config::{self, AuthKind, StoredSession};
+use crate::matrix::loopback::{self, LoopbackListener};
+use crate::message::{IdentityProviderInfo, LoginFlows, Message};
+use crate::secrets;
 
-pub async fn create_client(homeserver: &str) -> Result<Client, String> {
-    let db_path = config::data_dir().join("matrix-store");
+const CLIENT_NAME: &str = "Cosmic Matrix";
+const CLIENT_URI: &str = "https://github.com/beezly/cosmic-matrix";
 
+pub async fn create_client(
+    homeserver: &str,
+    account_id: &str,
+    passphrase: Option<&str>,
+) -> Result<Client, String> {
     Client::builder()
         .server_name_or_homeserver_url(homeserver)
-        .sqlite_store(&db_path, None)
+        .sqlite_store(config::account_store_path(account_id), passphrase)
+        .handle_refresh_tokens()
         .build()
         .await
         .map_err(|e| format!("Failed to create client: {e}"))
 }
 
+/// Create a client for a fresh login. A new login is a new device, so it gets a new
+/// account ID (and store directory) and a random store passphrase.
+/// Returns `(client, account_id, passphrase)`.
+pub async fn create_login_client(homeserver: &str) -> Result<(Client, String, String), String> {
+    let account_id = config::new_account_id();
+    let passphrase = secrets::generate_passphrase();
+    let client = create_client(homeserver, &account_id, Some(&passphrase)).await?;
+    Ok((client, account_id, passphrase))
+}
+
+/// Ask the homeserver which login methods it offers. Uses a throwaway in-memory client.
+pub async fn discover_login_flows(homeserver: &str) -> Result<LoginFlows, String> {
+    let client = Client::builder()
+        .server_name_or_homeserver_url(homeserver)
+        .build()
+        .await
+        .map_err(|e| format!("Failed to reach homeserver: {e}"))?;
+
+    let response = client
+        .matrix_auth()
+        .get_login_types()
+        .await
+        .map_err(|e| format!("Failed to fetch login methods: {e}"))?;
+
+    let mut flows = LoginFlows::default();
+    for flow in response.flows {
+        match flow {
+            LoginType::Password(_) => flows.password = true,
+            LoginType::Sso(sso) => {
+                flows.sso = true;
+                flows
+                    .identity_providers
+                    .extend(sso.identity_providers.into_iter().map(|idp| IdentityProviderInfo {
+                        id: idp.id,
+                        name: idp.name,
+                    }));
+            }
+            _ => {}
+        }
+    }
+    flows.oidc_issuer = client.oidc().fetch_authentication_issuer().await.ok();
+
+    Ok(flows)
+}
+
+/// Password login. Passing the device ID of a soft-logged-out session signs that
+/// device in again instead of creating a new one.
 pub async fn login(
     client: &Client,
     username: &str,
     password: &str,
+    device_id: Option<&str>,
 ) -> Result<matrix_sdk::ruma::api::client::session::login::v3::Response, String> {
-    client
+    let mut builder = client
         .matrix_auth()
         .login_username(username, password)
-        .initial_device_display_name("Cosmic Matrix")
+        .initial_device_display_name(CLIENT_NAME)
+        .request_refresh_token();
+    if let Some(device_id) = device_id {
+        builder = builder.device_id(device_id);
+    }
+    builder
         .await
         .map_err(|e| format!("Login failed: {e}"))
 }
 
-pub fn save_session_from_client(client: &Client, homeserver: &str) -> Result<(), String> {
-    let session = client
+/// Legacy SSO: send the user to the identity provider, receive the `loginToken` on a
+/// loopback redirect, then exchange it for a session.
+pub async fn login_sso(
+    client: &Client,
+    idp_id: Option<&str>,
+    device_id: Option<&str>,
+) -> Result<(), String> {
+    let listener = LoopbackListener::bind().await?;
+    let sso_url = client
+        .matrix_auth()
+        .get_sso_login_url(listener.redirect_uri().as_str(), idp_id)
+        .await
+        .map_err(|e| format!("Failed to start SSO login: {e}"))?;
+    loopback::open_in_browser(&sso_url)?;
+
+    let callback = listener.wait_for_callback().await?;
+    let login_token = callback
+        .query_pairs()
+        .find(|(key, _)| key == "loginToken")
+        .map(|(_, value)| value.into_owned())
+        .ok_or_else(|| "SSO redirect did not include a login token".to_string())?;
+
+    let mut builder = client
         .matrix_auth()
-        .session()
-        .ok_or_else(|| "No session available".to_string())?;
+        .login_token(&login_token)
+        .initial_device_display_name(CLIENT_NAME)
+        .request_refresh_token();
+    if let Some(device_id) = device_id {
+        builder = builder.device_id(device_id);
+    }
+    builder
+        .await
+        .map_err(|e| format!("SSO login failed: {e}"))?;
+    Ok(())
+}
+
+/// Next-gen auth (MSC3861): register as a public native client with the issuer and
+/// run the authorization-code flow through a loopback redirect.
+pub async fn login_oidc(
+    client: &Client,
+    issuer: &str,
+    device_id: Option<OwnedDeviceId>,
+) -> Result<AuthKind, String> {
+    let listener = LoopbackListener::bind().await?;
+    let redirect_uri = listener.redirect_uri().clone();
+    let metadata = oidc_client_metadata(&redirect_uri)?;
+    let oidc = client.oidc();
+
+    let registration = oidc
+        .register_client(issuer, metadata.clone(), None)
+        .await
+        .map_err(|e| format!("Failed to register with the identity provider: {e}"))?;
+    let client_id = registration.client_id;
+    oidc.restore_registered_client(
+        issuer.to_string(),
+        metadata,
+        ClientId(client_id.clone()),
+    );
+
+    let authorization = oidc
+        .login(redirect_uri.clone(), device_id)
+        .map_err(|e| format!("Failed to start OIDC login: {e}"))?
+        .build()
+        .await
+        .map_err(|e| format!("Failed to start OIDC login: {e}"))?;
+    loopback::open_in_browser(authorization.url.as_str())?;
+
+    let callback = listener.wait_for_callback().await?;
+    oidc.login_with_oidc_callback(&authorization, callback)
+        .await
+        .map_err(|e| format!("OIDC login failed: {e}"))?;
+
+    Ok(AuthKind::Oidc {
+        issuer: issuer.to_string(),
+        client_id,
+        redirect_uri: redirect_uri.to_string(),
+    })
+}
+
+fn oidc_client_metadata(redirect_uri: &Url) -> Result<VerifiedClientMetadata, String> {
+    let client_uri = Url::parse(CLIENT_URI).map_err(|e| e.to_string())?;
+    ClientMetadata {
+        application_type: Some(ApplicationType::Native),
+        redirect_uris: Some(vec![redirect_uri.clone()]),
+        grant_types: Some(vec![GrantType::AuthorizationCode, GrantType::RefreshToken]),
+        token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
+        client_name: Some(Localized::new(CLIENT_NAME.to_string(), [])),
+        client_uri: Some(Localized::new(client_uri, [])),
+        ..Default::default()
+    }
+    .validate()
+    .map_err(|e| format!("Invalid OIDC client metadata: {e}"))
+}
+
+pub fn save_session_from_client(
+    client: &Client,
+    account_id: &str,
+    homeserver: &str,
+    store_passphrase: Option<String>,
+    auth: AuthKind,
+) -> Result<(), String> {
+    let (meta, access_token, refresh_token) = match client.session() {
+        Some(AuthSession::Matrix(session)) => (
+            session.meta,
+            session.tokens.access_token,
+            session.tokens.refresh_token,
+        ),
+        Some(AuthSession::Oidc(session)) => (
+            session.user.meta,
+            session.user.tokens.access_token,
+            session.user.tokens.refresh_token,
+        ),
+        _ => return Err("No session available".to_string()),
+    };
 
     let stored = StoredSession {
+        account_id: account_id.to_string(),
         homeserver: homeserver.to_string(),
-        user_id: session.meta.user_id.to_string(),
-        access_token: session.tokens.access_token.clone(),
-        device_id: session.meta.device_id.to_string(),
+        user_id: meta.user_id.to_string(),
+        access_token,
+        device_id: meta.device_id.to_string(),
+        auth,
+        refresh_token,
+        store_passphrase,
     };
 
     config::save_session(&stored)
 }
 
+/// Save the session of an account that signed in again after a soft logout, keeping
+/// its account ID, homeserver and store passphrase. `auth` replaces the stored auth
+/// kind when the new login used a different one.
+pub fn save_reauthenticated_session(
+    client: &Client,
+    account_id: &str,
+    auth: Option<AuthKind>,
+) -> Result<(), String> {
+    let stored = config::load_sessions()?
+        .into_iter()
+        .find(|s| s.account_id == account_id)
+        .ok_or_else(|| format!("No stored session for account {account_id}"))?;
+    save_session_from_client(
+        client,
+        account_id,
+        &stored.homeserver,
+        stored.store_passphrase,
+        auth.unwrap_or(stored.auth),
+    )
+}
+
+/// Re-save the session after the SDK rotated its tokens, keeping the stored
+/// homeserver, auth kind and store passphrase.
+fn persist_refreshed_tokens(client: &Client) -> Result<(), String> {
+    let user_id = client.user_id().ok_or_else(|| "Client has no user".to_string())?;
+    let device_id = client.device_id().ok_or_else(|| "Client has no device".to_string())?;
+    // The same user may be signed in more than once; the device tells them apart.
+    let stored = config::load_sessions()?
+        .into_iter()
+        .find(|s| s.user_id == user_id.as_str() && s.device_id == device_id.as_str())
+        .ok_or_else(|| format!("No stored session for {user_id}"))?;
+    save_session_from_client(
+        client,
+        &stored.account_id,
+        &stored.homeserver,
+        stored.store_passphrase,
+        stored.auth,
+    )
+}
+
+/// Persist rotated tokens and report when the server no longer accepts our token.
+pub fn session_changes_subscription(client: Arc<Client>) -> Subscription<Message> {
+    let user_id = client.user_id().map(|u| u.to_owned());
+    Subscription::run_with_id(
+        (std::any::TypeId::of::<SessionChangesMarker>(), user_id),
+        stream::channel(10, move |mut output| async move {
+            let mut changes = client.subscribe_to_session_changes();
+            loop {
+                match changes.recv().await {
+                    // After a lag a rotated refresh token may have been missed; the
+                    // client's current tokens are the ones to keep either way.
+                    Ok(SessionChange::TokensRefreshed) | Err(RecvError::Lagged(_)) => {
+                        tracing::debug!("Access token refreshed");
+                        if let Err(e) = persist_refreshed_tokens(&client) {
+                            tracing::error!("Failed to persist refreshed tokens: {e}");
+                        }
+                    }
+                    Ok(SessionChange::UnknownToken { soft_logout }) => {
+                        let _ = output.send(Message::SessionExpired { soft_logout }).await;
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+
+            futures::future::pending::<()>().await;
+        }),
+    )
+}
+
 pub async fn restore_session(stored: &StoredSession) -> Result<Client, String> {
-    let client = create_client(&stored.homeserver).await?;
+    let client = create_client(
+        &stored.homeserver,
+        &stored.account_id,
+        stored.store_passphrase.as_deref(),
+    )
+    .await?;
 
     let user_id: OwnedUserId = stored
         .user_id
         .parse()
         .map_err(|e| format!("Invalid user_id: {e}"))?;
     let device_id: OwnedDeviceId = stored.device_id.as_str().into();
-
-    let session = MatrixSession {
-        meta: matrix_sdk::SessionMeta {
-            user_id,
-            device_id,
-        },
-        tokens: MatrixSessionTokens {
-            access_token: stored.access_token.clone(),
-            refresh_token: None,
-        },
+    let meta = matrix_sdk::SessionMeta {
+        user_id,
+        device_id,
     };
 
-    client
-        .restore_session(session)
-        .await
-        .map_err(|e| format!("Session restore failed: {e}"))?;
+    match &stored.auth {
+        AuthKind::Matrix => {
+            let session = MatrixSession {
+                meta,
+                tokens: MatrixSessionTokens {
+                    access_token: stored.access_token.clone(),
+                    refresh_token: stored.refresh_token.clone(),
+                },
+            };
+            client
+                .restore_session(session)
+                .await
+                .map_err(|e| format!("Session restore failed: {e}"))?;
+        }
+        AuthKind::Oidc {
+            issuer,
+            client_id,
+            redirect_uri,
+        } => {
+            let redirect_uri =
+                Url::parse(redirect_uri).map_err(|e| format!("Invalid redirect URI: {e}"))?;
+            let session = OidcSession {
+                client_id: ClientId(client_id.clone()),
+                metadata: oidc_client_metadata(&redirect_uri)?,
+                user: UserSession {
+                    meta,
+                    tokens: OidcSessionTokens {
+                        access_token: stored.access_token.clone(),
+                        refresh_token: stored.refresh_token.clone(),
+                        latest_id_token: None,
+                    },
+                    issuer: issuer.clone(),
+                },
+            };
+            client
+                .oidc()
+                .restore_session(session)
+                .await
+                .map_err(|e| format!("Session restore failed: {e}"))?;
+        }
+    }
 
     Ok(client)
 }
+
+struct SessionChangesMarker;
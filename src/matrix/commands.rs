@@ -0,0 +1,542 @@
+//! Slash commands typed into the composer. Each command is a row in [`COMMANDS`]: its
+//! name, usage and a parser from the argument text to a [`Command`].
+
+use matrix_sdk::deserialized_responses::RawAnySyncOrStrippedState;
+use matrix_sdk::ruma::events::room::member::{MembershipState, RoomMemberEventContent};
+use matrix_sdk::ruma::events::room::message::{
+    EmoteMessageEventContent, MessageType, NoticeMessageEventContent,
+};
+use matrix_sdk::ruma::events::StateEventType;
+use matrix_sdk::ruma::{Int, OwnedRoomId, OwnedRoomOrAliasId, OwnedUserId};
+use matrix_sdk::Client;
+
+use crate::message::Message;
+
+/// What a command asks for.
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Send a message; goes through the normal send path (replies, threads, edits).
+    Send(MessageType),
+    Join(OwnedRoomOrAliasId),
+    Leave,
+    Invite(OwnedUserId),
+    Nick(String),
+    Topic(String),
+    Ignore(OwnedUserId),
+    Kick(OwnedUserId, Option<String>),
+    Ban(OwnedUserId, Option<String>),
+    Op(OwnedUserId, Int),
+    Devtools,
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Argument synopsis shown in the usage help, e.g. `<user> [reason]`.
+    pub args: &'static str,
+    pub description: &'static str,
+    /// `None` means the arguments don't fit `args`.
+    parse: fn(&str) -> Option<Command>,
+}
+
+impl CommandSpec {
+    pub fn usage(&self) -> String {
+        if self.args.is_empty() {
+            format!("/{}", self.name)
+        } else {
+            format!("/{} {}", self.name, self.args)
+        }
+    }
+}
+
+pub static COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "me",
+        args: "<action>",
+        description: "Send an emote",
+        parse: |args| {
+            non_empty(args)
+                .map(|a| Command::Send(MessageType::Emote(EmoteMessageEventContent::markdown(a))))
+        },
+    },
+    CommandSpec {
+        name: "notice",
+        args: "<message>",
+        description: "Send a notice, as bots do",
+        parse: |args| {
+            non_empty(args).map(|a| {
+                Command::Send(MessageType::Notice(NoticeMessageEventContent::markdown(a)))
+            })
+        },
+    },
+    CommandSpec {
+        name: "shrug",
+        args: "[message]",
+        description: "Prepend ¯\\_(ツ)_/¯",
+        parse: |args| {
+            let text = format!("¯\\_(ツ)_/¯ {args}");
+            Some(Command::Send(MessageType::text_plain(text.trim_end())))
+        },
+    },
+    CommandSpec {
+        name: "plain",
+        args: "<message>",
+        description: "Send without Markdown formatting",
+        parse: |args| non_empty(args).map(|a| Command::Send(MessageType::text_plain(a))),
+    },
+    CommandSpec {
+        name: "html",
+        args: "<html>",
+        description: "Send raw HTML",
+        parse: |args| {
+            non_empty(args).map(|a| {
+                Command::Send(MessageType::text_html(crate::matrix::html::plain_text(a), a))
+            })
+        },
+    },
+    CommandSpec {
+        name: "rainbow",
+        args: "<message>",
+        description: "Send the message in rainbow colours",
+        parse: |args| non_empty(args).map(|a| Command::Send(rainbow(a))),
+    },
+    CommandSpec {
+        name: "join",
+        args: "<#alias:server | !room:server>",
+        description: "Join a room",
+        parse: |args| {
+            OwnedRoomOrAliasId::try_from(args.trim())
+                .ok()
+                .map(Command::Join)
+        },
+    },
+    CommandSpec {
+        name: "leave",
+        args: "",
+        description: "Leave this room",
+        parse: |args| args.trim().is_empty().then_some(Command::Leave),
+    },
+    CommandSpec {
+        name: "invite",
+        args: "<@user:server>",
+        description: "Invite a user to this room",
+        parse: |args| user_id(args).map(Command::Invite),
+    },
+    CommandSpec {
+        name: "nick",
+        args: "<display name>",
+        description: "Change your display name in this room",
+        parse: |args| non_empty(args).map(|a| Command::Nick(a.to_string())),
+    },
+    CommandSpec {
+        name: "topic",
+        args: "<topic>",
+        description: "Set the room topic",
+        parse: |args| non_empty(args).map(|a| Command::Topic(a.to_string())),
+    },
+    CommandSpec {
+        name: "ignore",
+        args: "<@user:server>",
+        description: "Hide all messages from a user",
+        parse: |args| user_id(args).map(Command::Ignore),
+    },
+    CommandSpec {
+        name: "kick",
+        args: "<@user:server> [reason]",
+        description: "Remove a user from this room",
+        parse: |args| {
+            let (user, reason) = user_and_reason(args)?;
+            Some(Command::Kick(user, reason))
+        },
+    },
+    CommandSpec {
+        name: "ban",
+        args: "<@user:server> [reason]",
+        description: "Ban a user from this room",
+        parse: |args| {
+            let (user, reason) = user_and_reason(args)?;
+            Some(Command::Ban(user, reason))
+        },
+    },
+    CommandSpec {
+        name: "op",
+        args: "<@user:server> [power level]",
+        description: "Set a user's power level (default 50)",
+        parse: |args| {
+            let (user, level) = user_and_reason(args)?;
+            // Out-of-range levels fail to parse and show the usage line.
+            let level = match level {
+                Some(level) => Int::try_from(level.parse::<i64>().ok()?).ok()?,
+                None => Int::from(50),
+            };
+            Some(Command::Op(user, level))
+        },
+    },
+    CommandSpec {
+        name: "devtools",
+        args: "",
+        description: "Show the room's state events",
+        parse: |args| args.trim().is_empty().then_some(Command::Devtools),
+    },
+];
+
+/// Parse composer text. Text not starting with `/` (or escaped as `//`) is sent as
+/// Markdown. Errors carry the usage line to show under the composer.
+pub fn parse(text: &str) -> Result<Command, String> {
+    let Some(rest) = text.strip_prefix('/') else {
+        return Ok(Command::Send(MessageType::text_markdown(text)));
+    };
+    if rest.starts_with('/') {
+        return Ok(Command::Send(MessageType::text_markdown(rest)));
+    }
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let spec = find(name).ok_or_else(|| {
+        format!("Unknown command /{name}. Start with // to send a message beginning with /")
+    })?;
+    (spec.parse)(args.trim()).ok_or_else(|| format!("Usage: {}", spec.usage()))
+}
+
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// Commands whose name starts with the word being typed (`/in` → `/invite`). Empty once
+/// the command name is complete and arguments are being typed.
+pub fn completions(text: &str) -> Vec<&'static CommandSpec> {
+    match text.strip_prefix('/') {
+        Some(prefix) if !prefix.starts_with('/') && !prefix.contains(char::is_whitespace) => {
+            COMMANDS.iter().filter(|c| c.name.starts_with(prefix)).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The command whose arguments are being typed, for inline usage help.
+pub fn current(text: &str) -> Option<&'static CommandSpec> {
+    let rest = text.strip_prefix('/')?;
+    let (name, _) = rest.split_once(char::is_whitespace)?;
+    find(name)
+}
+
+/// Tab-completion: the longest common prefix of the matching names, with a trailing
+/// space once it is unambiguous.
+pub fn complete(text: &str) -> Option<String> {
+    let matches = completions(text);
+    let first = matches.first()?;
+    if matches.len() == 1 {
+        return Some(format!("/{} ", first.name));
+    }
+    let common = matches.iter().skip(1).fold(first.name, |common, c| {
+        let len = common
+            .chars()
+            .zip(c.name.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        &common[..len]
+    });
+    Some(format!("/{common}"))
+}
+
+/// Run a command that acts on the room rather than sending a message.
+pub async fn execute(client: &Client, room_id: &OwnedRoomId, command: Command) -> Message {
+    match command {
+        Command::Join(target) => {
+            return match client.join_room_by_id_or_alias(&target, &[]).await {
+                Ok(room) => Message::RoomJoined(room.room_id().to_owned()),
+                Err(e) => Message::CommandFinished(Err(format!("Failed to join {target}: {e}"))),
+            };
+        }
+        Command::Ignore(user_id) => {
+            let result = client
+                .account()
+                .ignore_user(&user_id)
+                .await
+                .map(|_| format!("Ignoring {user_id}"))
+                .map_err(|e| format!("Failed to ignore {user_id}: {e}"));
+            return Message::CommandFinished(result);
+        }
+        _ => {}
+    }
+
+    let Some(room) = client.get_room(room_id) else {
+        return Message::CommandFinished(Err("Room not found".to_string()));
+    };
+    let result = match command {
+        Command::Leave => {
+            return match room.leave().await {
+                Ok(()) => Message::RoomLeft(room_id.clone()),
+                Err(e) => Message::CommandFinished(Err(format!("Failed to leave: {e}"))),
+            };
+        }
+        Command::Invite(user_id) => room
+            .invite_user_by_id(&user_id)
+            .await
+            .map(|_| format!("Invited {user_id}"))
+            .map_err(|e| format!("Failed to invite {user_id}: {e}")),
+        Command::Nick(name) => set_room_nick(client, &room, name).await,
+        Command::Topic(topic) => room
+            .set_room_topic(&topic)
+            .await
+            .map(|_| "Topic changed".to_string())
+            .map_err(|e| format!("Failed to set topic: {e}")),
+        Command::Kick(user_id, reason) => room
+            .kick_user(&user_id, reason.as_deref())
+            .await
+            .map(|_| format!("Removed {user_id}"))
+            .map_err(|e| format!("Failed to remove {user_id}: {e}")),
+        Command::Ban(user_id, reason) => room
+            .ban_user(&user_id, reason.as_deref())
+            .await
+            .map(|_| format!("Banned {user_id}"))
+            .map_err(|e| format!("Failed to ban {user_id}: {e}")),
+        Command::Op(user_id, level) => room
+            .update_power_levels(vec![(&*user_id, level)])
+            .await
+            .map(|_| format!("Set power level of {user_id} to {level}"))
+            .map_err(|e| format!("Failed to change power level: {e}")),
+        Command::Send(_) | Command::Devtools | Command::Join(_) | Command::Ignore(_) => {
+            Err("Command can't be run here".to_string())
+        }
+    };
+    Message::CommandFinished(result)
+}
+
+/// Per-room display name: re-send our member event with a new `displayname`, keeping
+/// its other fields (`avatar_url`, `is_direct`, …) as they are.
+async fn set_room_nick(
+    client: &Client,
+    room: &matrix_sdk::Room,
+    name: String,
+) -> Result<String, String> {
+    let user_id = client
+        .user_id()
+        .ok_or_else(|| "Not logged in".to_string())?
+        .to_owned();
+    let member = room
+        .get_member_no_sync(&user_id)
+        .await
+        .map_err(|e| format!("Failed to load membership: {e}"))?;
+
+    let mut content = member
+        .as_ref()
+        .and_then(|m| m.event().original_content().cloned())
+        .unwrap_or_else(|| RoomMemberEventContent::new(MembershipState::Join));
+    content.displayname = Some(name.clone());
+    room.send_state_event_for_key(&user_id, content)
+        .await
+        .map(|_| format!("Display name in this room is now {name}"))
+        .map_err(|e| format!("Failed to change display name: {e}"))
+}
+
+/// Room state shown by `/devtools`, as pretty-printed JSON. Membership events are
+/// left out; large rooms have thousands.
+pub async fn load_room_state(client: &Client, room_id: &OwnedRoomId) -> Message {
+    const TYPES: [StateEventType; 12] = [
+        StateEventType::RoomCreate,
+        StateEventType::RoomName,
+        StateEventType::RoomTopic,
+        StateEventType::RoomAvatar,
+        StateEventType::RoomCanonicalAlias,
+        StateEventType::RoomJoinRules,
+        StateEventType::RoomHistoryVisibility,
+        StateEventType::RoomGuestAccess,
+        StateEventType::RoomEncryption,
+        StateEventType::RoomPowerLevels,
+        StateEventType::RoomPinnedEvents,
+        StateEventType::RoomTombstone,
+    ];
+
+    let Some(room) = client.get_room(room_id) else {
+        return Message::DevtoolsLoaded(Err("Room not found".to_string()));
+    };
+    let mut events = Vec::new();
+    for event_type in TYPES {
+        let raw_events = match room.get_state_events(event_type).await {
+            Ok(events) => events,
+            Err(e) => return Message::DevtoolsLoaded(Err(format!("Failed to load state: {e}"))),
+        };
+        for raw in raw_events {
+            let json = match raw {
+                RawAnySyncOrStrippedState::Sync(raw) => raw.json().get().to_string(),
+                RawAnySyncOrStrippedState::Stripped(raw) => raw.json().get().to_string(),
+            };
+            if let Ok(value) = serde_json::from_str::<serde_json::Value>(&json) {
+                events.push(value);
+            }
+        }
+    }
+    let text = serde_json::to_string_pretty(&events).map_err(|e| e.to_string());
+    Message::DevtoolsLoaded(text.map(|text| format!("Room {room_id}\n\n{text}")))
+}
+
+fn non_empty(args: &str) -> Option<&str> {
+    let args = args.trim();
+    (!args.is_empty()).then_some(args)
+}
+
+fn user_id(args: &str) -> Option<OwnedUserId> {
+    OwnedUserId::try_from(args.trim()).ok()
+}
+
+/// `"@user:server rest of text"` → the user and the optional rest.
+fn user_and_reason(args: &str) -> Option<(OwnedUserId, Option<String>)> {
+    let (user, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let rest = rest.trim();
+    Some((user_id(user)?, (!rest.is_empty()).then(|| rest.to_string())))
+}
+
+/// Colour each character along the hue wheel with `data-mx-color`.
+fn rainbow(text: &str) -> MessageType {
+    let count = text.chars().count().max(1) as f32;
+    let mut html = String::new();
+    for (i, ch) in text.chars().enumerate() {
+        if ch.is_whitespace() {
+            html.push(ch);
+            continue;
+        }
+        let (r, g, b) = hue_to_rgb(i as f32 / count);
+        let escaped = match ch {
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '&' => "&amp;".to_string(),
+            '"' => "&quot;".to_string(),
+            c => c.to_string(),
+        };
+        html.push_str(&format!(
+            "<font data-mx-color=\"#{r:02x}{g:02x}{b:02x}\">{escaped}</font>"
+        ));
+    }
+    MessageType::text_html(text, html)
+}
+
+/// Fully saturated colour at `hue` (0.0–1.0).
+fn hue_to_rgb(hue: f32) -> (u8, u8, u8) {
+    let h = hue * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    ((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sent_body(text: &str) -> String {
+        match parse(text) {
+            Ok(Command::Send(msgtype)) => msgtype.body().to_string(),
+            other => panic!("expected a message, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn plain_text_is_a_message() {
+        assert_eq!(sent_body("hello"), "hello");
+    }
+
+    #[test]
+    fn double_slash_escapes_a_leading_slash() {
+        assert_eq!(sent_body("//shrug is a command"), "/shrug is a command");
+        assert_eq!(sent_body("///"), "//");
+    }
+
+    #[test]
+    fn unknown_command_is_an_error() {
+        let err = parse("/frobnicate now").unwrap_err();
+        assert!(err.contains("Unknown command /frobnicate"), "{err}");
+        assert!(err.contains("//"), "{err}");
+    }
+
+    #[test]
+    fn bad_arguments_show_usage() {
+        assert_eq!(parse("/me").unwrap_err(), "Usage: /me <action>");
+        assert_eq!(parse("/invite bob").unwrap_err(), "Usage: /invite <@user:server>");
+        assert_eq!(parse("/leave now").unwrap_err(), "Usage: /leave");
+    }
+
+    #[test]
+    fn emote_and_notice_keep_their_type() {
+        assert!(matches!(parse("/me waves"), Ok(Command::Send(MessageType::Emote(_)))));
+        assert!(matches!(parse("/notice hi"), Ok(Command::Send(MessageType::Notice(_)))));
+    }
+
+    #[test]
+    fn user_and_reason_splits_on_first_whitespace() {
+        let (user, reason) = user_and_reason("@bob:example.org  spamming  links ").unwrap();
+        assert_eq!(user, "@bob:example.org");
+        assert_eq!(reason.as_deref(), Some("spamming  links"));
+
+        let (_, reason) = user_and_reason("@bob:example.org").unwrap();
+        assert_eq!(reason, None);
+
+        assert!(user_and_reason("bob spamming").is_none());
+    }
+
+    #[test]
+    fn kick_carries_reason() {
+        match parse("/kick @bob:example.org bye") {
+            Ok(Command::Kick(user, reason)) => {
+                assert_eq!(user, "@bob:example.org");
+                assert_eq!(reason.as_deref(), Some("bye"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn op_defaults_to_moderator() {
+        match parse("/op @bob:example.org") {
+            Ok(Command::Op(_, level)) => assert_eq!(level, Int::from(50)),
+            other => panic!("unexpected {other:?}"),
+        }
+        match parse("/op @bob:example.org -1") {
+            Ok(Command::Op(_, level)) => assert_eq!(level, Int::from(-1)),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn op_rejects_out_of_range_levels() {
+        let usage = "Usage: /op <@user:server> [power level]";
+        // Int is limited to ±(2^53 - 1)
+        assert_eq!(parse("/op @bob:example.org 9007199254740992").unwrap_err(), usage);
+        assert_eq!(parse("/op @bob:example.org -9007199254740992").unwrap_err(), usage);
+        assert_eq!(parse("/op @bob:example.org 4294967296").map(|_| ()), Ok(()));
+        assert_eq!(parse("/op @bob:example.org high").unwrap_err(), usage);
+    }
+
+    #[test]
+    fn complete_unique_prefix_adds_space() {
+        assert_eq!(complete("/inv").as_deref(), Some("/invite "));
+        assert_eq!(complete("/dev").as_deref(), Some("/devtools "));
+    }
+
+    #[test]
+    fn complete_ambiguous_prefix_to_longest_common_prefix() {
+        // nick, notice
+        assert_eq!(complete("/n").as_deref(), Some("/n"));
+        // The whole list shares no prefix
+        assert_eq!(complete("/").as_deref(), Some("/"));
+        assert_eq!(complete("/k").as_deref(), Some("/kick "));
+    }
+
+    #[test]
+    fn complete_nothing_outside_command_names() {
+        assert_eq!(complete("hello"), None);
+        assert_eq!(complete("/zzz"), None);
+        assert_eq!(complete("//me"), None);
+        assert_eq!(complete("/me waves"), None);
+    }
+
+    #[test]
+    fn current_command_while_typing_arguments() {
+        assert_eq!(current("/kick @bob").map(|c| c.name), Some("kick"));
+        assert!(current("/kick").is_none());
+        assert!(current("hello there").is_none());
+    }
+}
@@ -0,0 +1,306 @@
+//! Parse `formatted_body` (org.matrix.custom.html) into the rich-text model drawn by
+//! the timeline. The HTML is sanitized with the spec's allow-list first.
+
+use ruma::html::{Html, NodeRef, SanitizerConfig};
+
+use crate::highlight;
+use crate::message::{RichBlock, RichSpan};
+
+/// Elements nested deeper than this are flattened to their text, so hostile HTML
+/// can't exhaust the stack.
+const MAX_DEPTH: usize = 32;
+
+/// Inline formatting inherited from enclosing elements.
+#[derive(Clone, Default)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    code: bool,
+    link: Option<String>,
+    /// Number of enclosing elements.
+    depth: usize,
+}
+
+/// Parse and sanitize Matrix HTML. Reply fallbacks (`<mx-reply>`) are dropped.
+pub fn parse_formatted_body(html: &str) -> Vec<RichBlock> {
+    let html = Html::parse(html);
+    html.sanitize_with(&SanitizerConfig::compat().remove_reply_fallback());
+
+    let mut blocks = Vec::new();
+    let mut para = Vec::new();
+    for node in html.children() {
+        walk(&node, &Style::default(), &mut blocks, &mut para);
+    }
+    flush(&mut blocks, &mut para);
+    blocks
+}
+
+fn walk(node: &NodeRef, style: &Style, blocks: &mut Vec<RichBlock>, para: &mut Vec<RichSpan>) {
+    if let Some(text) = node.as_text() {
+        let text = text.borrow().replace('\n', " ");
+        // Whitespace between block elements
+        if para.is_empty() && text.trim().is_empty() {
+            return;
+        }
+        push_span(para, text, style);
+        return;
+    }
+
+    let Some(element) = node.as_element() else {
+        return;
+    };
+    if style.depth >= MAX_DEPTH {
+        push_span(para, text_content(node).replace('\n', " "), style);
+        return;
+    }
+    let style = &Style {
+        depth: style.depth + 1,
+        ..style.clone()
+    };
+    let name = element.name.local.as_ref();
+    let attr = |key: &str| {
+        element
+            .attrs
+            .iter()
+            .find(|a| a.name.local.as_ref() == key)
+            .map(|a| a.value.to_string())
+    };
+
+    match name {
+        "p" | "div" | "details" | "summary" | "table" | "tr" => {
+            flush(blocks, para);
+            walk_children(node, style, blocks, para);
+            flush(blocks, para);
+        }
+        "br" => push_span(para, "\n".to_string(), style),
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            flush(blocks, para);
+            let level = name[1..].parse().unwrap_or(1);
+            let mut spans = Vec::new();
+            let bold = Style {
+                bold: true,
+                ..style.clone()
+            };
+            walk_children(node, &bold, blocks, &mut spans);
+            blocks.push(RichBlock::Heading(level, trim_spans(spans)));
+        }
+        "pre" => {
+            flush(blocks, para);
+            // <pre><code class="language-rust">…</code></pre>
+            let language = node.children().find_map(|child| {
+                let code = child.as_element()?;
+                code.attrs
+                    .iter()
+                    .find(|a| a.name.local.as_ref() == "class")
+                    .and_then(|a| a.value.strip_prefix("language-").map(str::to_string))
+            });
+            let mut code = text_content(node);
+            if code.ends_with('\n') {
+                code.pop();
+            }
+            let lines = highlight::highlight(language.as_deref(), &code);
+            blocks.push(RichBlock::CodeBlock {
+                language,
+                code,
+                lines,
+            });
+        }
+        "blockquote" => {
+            flush(blocks, para);
+            blocks.push(RichBlock::Quote(nested_blocks(node, style)));
+        }
+        "ul" | "ol" => {
+            flush(blocks, para);
+            let start = attr("start").and_then(|s| s.parse().ok()).unwrap_or(1);
+            let items = node
+                .children()
+                .filter(|child| {
+                    child
+                        .as_element()
+                        .is_some_and(|e| e.name.local.as_ref() == "li")
+                })
+                .map(|li| nested_blocks(&li, style))
+                .collect();
+            blocks.push(RichBlock::List {
+                ordered: name == "ol",
+                start,
+                items,
+            });
+        }
+        "hr" => {
+            flush(blocks, para);
+            blocks.push(RichBlock::Rule);
+        }
+        "img" => {
+            let alt = attr("alt").unwrap_or_else(|| "image".to_string());
+            push_span(para, format!("[{alt}]"), style);
+        }
+        "b" | "strong" => walk_children(node, &Style { bold: true, ..style.clone() }, blocks, para),
+        "i" | "em" => walk_children(node, &Style { italic: true, ..style.clone() }, blocks, para),
+        "s" | "del" | "strike" => {
+            walk_children(node, &Style { strike: true, ..style.clone() }, blocks, para)
+        }
+        "code" => walk_children(node, &Style { code: true, ..style.clone() }, blocks, para),
+        "a" => {
+            let link = attr("href").or_else(|| style.link.clone());
+            walk_children(node, &Style { link, ..style.clone() }, blocks, para);
+        }
+        "td" | "th" => {
+            walk_children(node, style, blocks, para);
+            push_span(para, "\t".to_string(), style);
+        }
+        _ => walk_children(node, style, blocks, para),
+    }
+}
+
+fn walk_children(node: &NodeRef, style: &Style, blocks: &mut Vec<RichBlock>, para: &mut Vec<RichSpan>) {
+    for child in node.children() {
+        walk(&child, style, blocks, para);
+    }
+}
+
+/// Blocks inside a container element such as `<blockquote>` or `<li>`.
+fn nested_blocks(node: &NodeRef, style: &Style) -> Vec<RichBlock> {
+    let mut blocks = Vec::new();
+    let mut para = Vec::new();
+    walk_children(node, style, &mut blocks, &mut para);
+    flush(&mut blocks, &mut para);
+    blocks
+}
+
+fn push_span(para: &mut Vec<RichSpan>, text: String, style: &Style) {
+    para.push(RichSpan {
+        text,
+        bold: style.bold,
+        italic: style.italic,
+        strike: style.strike,
+        code: style.code,
+        link: style.link.clone(),
+    });
+}
+
+/// End the current paragraph, if it has any content.
+fn flush(blocks: &mut Vec<RichBlock>, para: &mut Vec<RichSpan>) {
+    let spans = trim_spans(std::mem::take(para));
+    if !spans.is_empty() {
+        blocks.push(RichBlock::Paragraph(spans));
+    }
+}
+
+/// Drop leading/trailing whitespace of a paragraph.
+fn trim_spans(mut spans: Vec<RichSpan>) -> Vec<RichSpan> {
+    if let Some(first) = spans.first_mut() {
+        first.text = first.text.trim_start_matches(' ').to_string();
+    }
+    if let Some(last) = spans.last_mut() {
+        last.text = last.text.trim_end().to_string();
+    }
+    spans.retain(|s| !s.text.is_empty());
+    spans
+}
+
+/// Concatenated text of a node and its descendants, walked without recursion.
+fn text_content(node: &NodeRef) -> String {
+    if let Some(text) = node.as_text() {
+        return text.borrow().to_string();
+    }
+    let mut text = String::new();
+    let mut stack: Vec<NodeRef> = node.children().collect();
+    stack.reverse();
+    while let Some(node) = stack.pop() {
+        match node.as_text() {
+            Some(t) => text.push_str(&t.borrow()),
+            None => {
+                let len = stack.len();
+                stack.extend(node.children());
+                stack[len..].reverse();
+            }
+        }
+    }
+    text
+}
+
+/// Plain-text fallback for HTML written by the user (`/html`).
+pub fn plain_text(html: &str) -> String {
+    let html = Html::parse(html);
+    let text: String = html.children().map(|node| text_content(&node)).collect();
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paragraph_text(block: &RichBlock) -> String {
+        match block {
+            RichBlock::Paragraph(spans) => spans.iter().map(|s| s.text.as_str()).collect(),
+            other => panic!("expected a paragraph, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn drops_reply_fallback() {
+        let blocks =
+            parse_formatted_body("<mx-reply><blockquote>quoted</blockquote></mx-reply>Hello");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(paragraph_text(&blocks[0]), "Hello");
+    }
+
+    #[test]
+    fn parses_lists() {
+        let blocks = parse_formatted_body("<ol start=\"3\"><li>one</li><li>two</li></ol>");
+        let [RichBlock::List { ordered, start, items }] = blocks.as_slice() else {
+            panic!("expected a list, got {blocks:?}");
+        };
+        assert!(*ordered);
+        assert_eq!(*start, 3);
+        assert_eq!(items.len(), 2);
+        assert_eq!(paragraph_text(&items[1][0]), "two");
+
+        let blocks = parse_formatted_body("<ul><li>a</li></ul>");
+        let [RichBlock::List { ordered, start, .. }] = blocks.as_slice() else {
+            panic!("expected a list, got {blocks:?}");
+        };
+        assert!(!*ordered);
+        assert_eq!(*start, 1);
+    }
+
+    #[test]
+    fn reads_code_language() {
+        let blocks =
+            parse_formatted_body("<pre><code class=\"language-rust\">fn main() {}\n</code></pre>");
+        let [RichBlock::CodeBlock { language, code, .. }] = blocks.as_slice() else {
+            panic!("expected a code block, got {blocks:?}");
+        };
+        assert_eq!(language.as_deref(), Some("rust"));
+        assert_eq!(code, "fn main() {}");
+    }
+
+    #[test]
+    fn strips_disallowed_markup() {
+        let blocks = parse_formatted_body(
+            "<marquee>hi</marquee> <a href=\"javascript:alert(1)\" onclick=\"x()\">there</a>",
+        );
+        let [RichBlock::Paragraph(spans)] = blocks.as_slice() else {
+            panic!("expected a paragraph, got {blocks:?}");
+        };
+        let text: String = spans.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(text, "hi there");
+        assert!(spans.iter().all(|s| s.link.is_none()));
+    }
+
+    #[test]
+    fn flattens_deep_nesting() {
+        let depth = MAX_DEPTH * 2;
+        let html = format!("{}deep{}", "<em>".repeat(depth), "</em>".repeat(depth));
+        let blocks = parse_formatted_body(&html);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(paragraph_text(&blocks[0]), "deep");
+    }
+
+    #[test]
+    fn text_content_keeps_order() {
+        assert_eq!(plain_text("<p>a<b>b<i>c</i></b>d</p>e"), "abcde");
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+const CALLBACK_PATH: &str = "/callback";
+/// How long to wait for the user to finish signing in in the browser.
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(300);
+/// How long one connection may take to send its request headers.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_LEN: usize = 8192;
+const RESPONSE_BODY: &str = "<html><body><p>Signed in. You can close this window and return to Cosmic Matrix.</p></body></html>";
+
+/// One-shot HTTP listener on 127.0.0.1 that receives the SSO / OIDC browser redirect.
+pub struct LoopbackListener {
+    listener: TcpListener,
+    redirect_uri: Url,
+}
+
+impl LoopbackListener {
+    pub async fn bind() -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| format!("Failed to start redirect listener: {e}"))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to start redirect listener: {e}"))?
+            .port();
+        let redirect_uri = Url::parse(&format!("http://127.0.0.1:{port}{CALLBACK_PATH}"))
+            .map_err(|e| format!("Invalid redirect URI: {e}"))?;
+        Ok(Self {
+            listener,
+            redirect_uri,
+        })
+    }
+
+    pub fn redirect_uri(&self) -> &Url {
+        &self.redirect_uri
+    }
+
+    /// Wait for the browser to hit the redirect URI and return the full callback URL.
+    /// Each connection is served in its own task, so a browser's idle preconnect
+    /// socket can't hold up the real redirect.
+    pub async fn wait_for_callback(self) -> Result<Url, String> {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<Url>(1);
+        let wait = async {
+            loop {
+                tokio::select! {
+                    accepted = self.listener.accept() => match accepted {
+                        Ok((stream, _)) => {
+                            let tx = tx.clone();
+                            let redirect_uri = self.redirect_uri.clone();
+                            tokio::spawn(async move {
+                                if let Some(url) = serve_connection(stream, &redirect_uri).await {
+                                    let _ = tx.send(url).await;
+                                }
+                            });
+                        }
+                        Err(e) => tracing::debug!("Redirect listener accept failed: {e}"),
+                    },
+                    Some(url) = rx.recv() => return Ok::<_, String>(url),
+                }
+            }
+        };
+
+        tokio::time::timeout(CALLBACK_TIMEOUT, wait)
+            .await
+            .map_err(|_| "Timed out waiting for the browser to sign in".to_string())?
+    }
+}
+
+/// Answer one HTTP request, returning the full URL if it was the callback. Connections
+/// that fail or send nothing in time are dropped.
+async fn serve_connection(mut stream: TcpStream, redirect_uri: &Url) -> Option<Url> {
+    let request = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            tracing::debug!("Redirect listener read failed: {e}");
+            return None;
+        }
+        Err(_) => return None,
+    };
+
+    let Some(url) = callback_url(&request, redirect_uri) else {
+        // e.g. the browser asking for /favicon.ico
+        let _ = stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await;
+        return None;
+    };
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{RESPONSE_BODY}",
+        RESPONSE_BODY.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    Some(url)
+}
+
+/// The callback URL a request asks for, if its target is exactly the redirect URI's
+/// path (with any query).
+fn callback_url(request: &str, redirect_uri: &Url) -> Option<Url> {
+    // Request line: "GET /callback?loginToken=... HTTP/1.1"
+    let mut parts = request.lines().next()?.split_whitespace();
+    let (Some("GET"), Some(target)) = (parts.next(), parts.next()) else {
+        return None;
+    };
+    if !target.starts_with('/') || target.starts_with("//") {
+        return None;
+    }
+    let url = redirect_uri.join(target).ok()?;
+    (url.origin() == redirect_uri.origin() && url.path() == CALLBACK_PATH).then_some(url)
+}
+
+/// Read until the end of the request headers, the connection closes or the buffer
+/// is full.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut buf = Vec::with_capacity(MAX_REQUEST_LEN);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_LEN {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Hand `url` to `xdg-open`. The child is reaped on a background thread so it
+/// doesn't linger as a zombie.
+pub fn open_in_browser(url: &str) -> Result<(), String> {
+    let mut child = std::process::Command::new("xdg-open")
+        .arg(url)
+        .spawn()
+        .map_err(|e| format!("Failed to open browser: {e}"))?;
+    std::thread::spawn(move || {
+        if let Err(e) = child.wait() {
+            tracing::debug!("Failed to wait for xdg-open: {e}");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect_uri() -> Url {
+        Url::parse("http://127.0.0.1:4567/callback").unwrap()
+    }
+
+    #[test]
+    fn accepts_the_callback_with_its_query() {
+        let request = "GET /callback?loginToken=abc&state=1 HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n";
+        let url = callback_url(request, &redirect_uri()).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:4567/callback?loginToken=abc&state=1"
+        );
+        assert_eq!(url.query_pairs().next().unwrap().1, "abc");
+    }
+
+    #[test]
+    fn accepts_the_bare_callback() {
+        let url = callback_url("GET /callback HTTP/1.1\r\n\r\n", &redirect_uri()).unwrap();
+        assert_eq!(url.path(), CALLBACK_PATH);
+    }
+
+    #[test]
+    fn rejects_other_paths() {
+        for target in ["/favicon.ico", "/callbackfoo", "/callback/x", "/", "/Callback"] {
+            let request = format!("GET {target} HTTP/1.1\r\n\r\n");
+            assert!(callback_url(&request, &redirect_uri()).is_none(), "{target}");
+        }
+    }
+
+    #[test]
+    fn rejects_other_hosts_and_absolute_targets() {
+        for target in ["//evil.example/callback", "http://evil.example/callback"] {
+            let request = format!("GET {target} HTTP/1.1\r\n\r\n");
+            assert!(callback_url(&request, &redirect_uri()).is_none(), "{target}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_request_lines() {
+        for request in ["", "\r\n\r\n", "GET", "POST /callback HTTP/1.1\r\n\r\n"] {
+            assert!(callback_url(request, &redirect_uri()).is_none(), "{request:?}");
+        }
+    }
+}
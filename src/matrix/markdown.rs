@@ -0,0 +1,21 @@
+//! Live preview of how the composer text will be formatted once sent.
+
+use matrix_sdk::ruma::events::room::message::MessageType;
+
+use crate::matrix::commands::{self, Command};
+use crate::matrix::html;
+use crate::message::RichBlock;
+
+/// The formatted result of the composer text, or nothing if it would be sent as
+/// plain text or isn't a message at all (e.g. `/invite`).
+pub fn preview(text: &str) -> Vec<RichBlock> {
+    let formatted = match commands::parse(text.trim()) {
+        Ok(Command::Send(MessageType::Text(content))) => content.formatted,
+        Ok(Command::Send(MessageType::Emote(content))) => content.formatted,
+        Ok(Command::Send(MessageType::Notice(content))) => content.formatted,
+        _ => None,
+    };
+    formatted
+        .map(|formatted| html::parse_formatted_body(&formatted.body))
+        .unwrap_or_default()
+}
@@ -0,0 +1,383 @@
+//! Composer autocompletion for `@user`, `#room:server` and `:emoji:`, and turning the
+//! inserted pills into matrix.to links and intentional mentions (`m.mentions`) on send.
+
+use matrix_sdk::ruma::events::room::message::MessageType;
+use matrix_sdk::ruma::events::Mentions;
+
+use crate::matrix::commands::{self, Command};
+use crate::message::{Completion, Pill, PillTarget, RoomEntry, RoomMember};
+
+const MAX_COMPLETIONS: usize = 6;
+
+/// Completions for the word that ends at byte offset `cursor` of the composer text.
+pub fn candidates(
+    text: &str,
+    cursor: usize,
+    members: &[RoomMember],
+    rooms: &[RoomEntry],
+) -> Vec<Completion> {
+    let Some(word) = word_before(text, cursor) else {
+        return Vec::new();
+    };
+    let word = &text[word];
+
+    if let Some(query) = word.strip_prefix('@') {
+        let query = query.to_lowercase();
+        let mut completions: Vec<Completion> = members
+            .iter()
+            .filter(|m| {
+                m.display_name.to_lowercase().contains(&query)
+                    || m.user_id.localpart().to_lowercase().starts_with(&query)
+            })
+            .take(MAX_COMPLETIONS)
+            .map(|m| Completion {
+                label: format!("{} ({})", m.display_name, m.user_id),
+                replacement: m.display_name.clone(),
+                pill: Some(Pill {
+                    text: m.display_name.clone(),
+                    target: PillTarget::User(m.user_id.clone()),
+                    range: 0..0,
+                }),
+            })
+            .collect();
+        if "room".starts_with(&query) {
+            completions.push(Completion {
+                label: "@room (notify everyone in the room)".to_string(),
+                replacement: "@room".to_string(),
+                pill: None,
+            });
+        }
+        return completions;
+    }
+
+    if word.starts_with('#') && word.len() > 1 {
+        let query = word.to_lowercase();
+        return rooms
+            .iter()
+            .filter_map(|room| {
+                let alias = room.canonical_alias.as_ref()?;
+                let matches = alias.to_lowercase().starts_with(&query)
+                    || room.name.to_lowercase().contains(&query[1..]);
+                matches.then(|| Completion {
+                    label: format!("{alias} — {}", room.name),
+                    replacement: alias.clone(),
+                    pill: Some(Pill {
+                        text: alias.clone(),
+                        target: PillTarget::Room(alias.clone()),
+                        range: 0..0,
+                    }),
+                })
+            })
+            .take(MAX_COMPLETIONS)
+            .collect();
+    }
+
+    if let Some(query) = word.strip_prefix(':') {
+        let query = query.trim_end_matches(':');
+        if query.len() < 2 {
+            return Vec::new();
+        }
+        return emojis::iter()
+            .filter_map(|emoji| {
+                let shortcode = emoji.shortcodes().find(|code| code.starts_with(query))?;
+                Some(Completion {
+                    label: format!("{} :{shortcode}:", emoji.as_str()),
+                    replacement: emoji.as_str().to_string(),
+                    pill: None,
+                })
+            })
+            .take(MAX_COMPLETIONS)
+            .collect();
+    }
+
+    Vec::new()
+}
+
+/// Keep pill ranges in step with a composer edit from `before` to `after`. Pills
+/// after the edited span move with it; pills the edit touched are dropped.
+pub fn track_edit(pills: &mut Vec<Pill>, before: &str, after: &str) {
+    let prefix = before
+        .bytes()
+        .zip(after.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let max_suffix = before.len().min(after.len()) - prefix;
+    let suffix = before
+        .bytes()
+        .rev()
+        .zip(after.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let edit_end = before.len() - suffix;
+    pills.retain_mut(|pill| {
+        if pill.range.end <= prefix {
+            true
+        } else if pill.range.start >= edit_end {
+            pill.range.start = pill.range.start + after.len() - before.len();
+            pill.range.end = pill.range.end + after.len() - before.len();
+            true
+        } else {
+            false
+        }
+    });
+}
+
+/// Byte range of the word that ends at `cursor`, unless the cursor follows whitespace.
+pub fn word_before(text: &str, cursor: usize) -> Option<std::ops::Range<usize>> {
+    let before = text.get(..cursor)?;
+    let start = before
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    (start < cursor).then_some(start..cursor)
+}
+
+/// Parse the composer text like [`commands::parse`], then link the pills still present
+/// in a Markdown message and collect its intentional mentions. Pill ranges are
+/// relative to `text`.
+pub fn prepare(text: &str, pills: &[Pill]) -> Result<(Command, Mentions), String> {
+    let command = commands::parse(text)?;
+    let Command::Send(msgtype) = command else {
+        return Ok((command, Mentions::new()));
+    };
+
+    let mut present: Vec<&Pill> = pills.iter().filter(|p| is_intact(text, p)).collect();
+    present.sort_by_key(|p| p.range.start);
+    let mut mentions = Mentions::new();
+    mentions.user_ids = present
+        .iter()
+        .filter_map(|p| match p.target {
+            PillTarget::User(ref user_id) => Some(user_id.clone()),
+            PillTarget::Room(_) => None,
+        })
+        .collect();
+    mentions.room = text.split(char::is_whitespace).any(|word| word == "@room");
+
+    // `/plain` and `/html` are sent exactly as typed.
+    let is_markdown = !matches!(commands::current(text).map(|c| c.name), Some("plain" | "html"));
+    if present.is_empty() || !is_markdown {
+        return Ok((Command::Send(msgtype), mentions));
+    }
+    // The formatted body carries the links; the plain body keeps the names as typed.
+    match commands::parse(&linkify(text, &present)) {
+        Ok(Command::Send(linked)) => {
+            Ok((Command::Send(with_body(linked, msgtype.body().to_string())), mentions))
+        }
+        _ => Ok((Command::Send(msgtype), mentions)),
+    }
+}
+
+/// Whether the pill's text is still in place as a word of its own, outside inline code.
+fn is_intact(text: &str, pill: &Pill) -> bool {
+    if text.get(pill.range.clone()) != Some(pill.text.as_str()) {
+        return false;
+    }
+    let before = text[..pill.range.start].chars().next_back();
+    let after = text[pill.range.end..].chars().next();
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let in_code = text[..pill.range.start].matches('`').count() % 2 == 1;
+    !is_word_char(before) && !is_word_char(after) && !in_code
+}
+
+/// Replace the pills, sorted by position, with Markdown links.
+fn linkify(text: &str, pills: &[&Pill]) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for pill in pills {
+        if pill.range.start < pos {
+            continue;
+        }
+        out.push_str(&text[pos..pill.range.start]);
+        out.push_str(&markdown_link(pill));
+        pos = pill.range.end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+fn markdown_link(pill: &Pill) -> String {
+    let url = match pill.target {
+        PillTarget::User(ref user_id) => format!("https://matrix.to/#/{user_id}"),
+        PillTarget::Room(ref alias) => {
+            format!("https://matrix.to/#/%23{}", alias.trim_start_matches('#'))
+        }
+    };
+    let mut label = String::new();
+    for ch in pill.text.chars() {
+        if matches!(ch, '[' | ']' | '\\' | '*' | '_' | '`') {
+            label.push('\\');
+        }
+        label.push(ch);
+    }
+    format!("[{label}]({url})")
+}
+
+fn with_body(msgtype: MessageType, body: String) -> MessageType {
+    match msgtype {
+        MessageType::Text(mut content) => {
+            content.body = body;
+            MessageType::Text(content)
+        }
+        MessageType::Emote(mut content) => {
+            content.body = body;
+            MessageType::Emote(content)
+        }
+        MessageType::Notice(mut content) => {
+            content.body = body;
+            MessageType::Notice(content)
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pill(text: &str, name: &str) -> Pill {
+        let start = text.find(name).unwrap();
+        Pill {
+            text: name.to_string(),
+            target: PillTarget::User(format!("@{}:example.org", name.len()).try_into().unwrap()),
+            range: start..start + name.len(),
+        }
+    }
+
+    fn tracked(before: &str, after: &str, pill: Pill) -> Option<std::ops::Range<usize>> {
+        let mut pills = vec![pill];
+        track_edit(&mut pills, before, after);
+        pills.pop().map(|p| p.range)
+    }
+
+    #[test]
+    fn insert_before_pill_shifts_it() {
+        let before = "hi Ann!";
+        assert_eq!(tracked(before, "oh hi Ann!", pill(before, "Ann")), Some(6..9));
+    }
+
+    #[test]
+    fn insert_after_pill_keeps_it() {
+        let before = "hi Ann!";
+        assert_eq!(tracked(before, "hi Ann!!", pill(before, "Ann")), Some(3..6));
+    }
+
+    #[test]
+    fn insert_inside_pill_drops_it() {
+        let before = "hi Ann!";
+        assert_eq!(tracked(before, "hi Axnn!", pill(before, "Ann")), None);
+    }
+
+    #[test]
+    fn delete_before_pill_shifts_it() {
+        let before = "hi Ann!";
+        assert_eq!(tracked(before, "Ann!", pill(before, "Ann")), Some(0..3));
+    }
+
+    #[test]
+    fn delete_across_pill_drops_it() {
+        let before = "hi Ann!";
+        assert_eq!(tracked(before, "hi !", pill(before, "Ann")), None);
+        assert_eq!(tracked(before, "hi An!", pill(before, "Ann")), None);
+    }
+
+    #[test]
+    fn multibyte_edits_shift_by_bytes() {
+        let before = "é Zoë ü";
+        let after = "ñé Zoë ü";
+        let mut pills = vec![pill(before, "Zoë")];
+        track_edit(&mut pills, before, after);
+        assert_eq!(pills[0].range, 5..9);
+        assert_eq!(&after[pills[0].range.clone()], "Zoë");
+        assert!(is_intact(after, &pills[0]));
+    }
+
+    #[test]
+    fn pill_must_stand_alone() {
+        let text = "Annual report";
+        let mut ann = pill(text, "Annual");
+        ann.text = "Ann".to_string();
+        ann.range = 0..3;
+        assert!(!is_intact(text, &ann));
+
+        let text = "ask Ann, please";
+        assert!(is_intact(text, &pill(text, "Ann")));
+    }
+
+    #[test]
+    fn pill_inside_inline_code_is_not_linked() {
+        let text = "see `Ann` and Ann";
+        let in_code = Pill {
+            range: 5..8,
+            ..pill(text, "Ann")
+        };
+        let outside = Pill {
+            range: 14..17,
+            ..pill(text, "Ann")
+        };
+        assert!(!is_intact(text, &in_code));
+        assert!(is_intact(text, &outside));
+    }
+
+    #[test]
+    fn stale_range_is_not_intact() {
+        let text = "hi Ann";
+        let stale = Pill {
+            range: 10..13,
+            ..pill(text, "Ann")
+        };
+        assert!(!is_intact(text, &stale));
+    }
+
+    #[test]
+    fn linkify_replaces_only_pill_ranges() {
+        let text = "Ann, Bo and Ann again";
+        let ann = pill(text, "Ann");
+        let bo = pill(text, "Bo");
+        let linked = linkify(text, &[&ann, &bo]);
+        assert_eq!(
+            linked,
+            format!(
+                "{}, {} and Ann again",
+                markdown_link(&ann),
+                markdown_link(&bo)
+            )
+        );
+    }
+
+    #[test]
+    fn linkify_multibyte_name() {
+        let text = "ping Zoë ✨";
+        let zoe = pill(text, "Zoë");
+        assert_eq!(linkify(text, &[&zoe]), format!("ping {} ✨", markdown_link(&zoe)));
+    }
+
+    #[test]
+    fn prepare_mentions_only_intact_pills() {
+        let text = "Ann: the Annual report";
+        let ann = pill(text, "Ann");
+        let moved = Pill {
+            range: 9..12,
+            ..pill(text, "Ann")
+        };
+        let (command, mentions) = prepare(text, &[ann.clone(), moved]).unwrap();
+        assert!(matches!(command, Command::Send(_)));
+        let PillTarget::User(ref user_id) = ann.target else {
+            unreachable!()
+        };
+        assert_eq!(mentions.user_ids.len(), 1);
+        assert!(mentions.user_ids.contains(user_id));
+    }
+
+    #[test]
+    fn word_before_cursor() {
+        let text = "hi @an there\n";
+        assert_eq!(word_before(text, 6), Some(3..6));
+        assert_eq!(word_before(text, 3), None);
+        assert_eq!(word_before(text, 2), Some(0..2));
+        assert_eq!(word_before("é@zo", 5), Some(0..5));
+        // Not a char boundary
+        assert_eq!(word_before("é", 1), None);
+    }
+}
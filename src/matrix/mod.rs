@@ -1,4 +1,11 @@
 pub mod client;
+pub mod commands;
+pub mod html;
+pub mod loopback;
+pub mod markdown;
+pub mod mentions;
+pub mod room_settings;
+pub mod rooms;
 pub mod sync;
 pub mod timeline;
 pub mod verification;
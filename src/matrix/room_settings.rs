@@ -0,0 +1,226 @@
+//! Room settings panel: read a room's `m.room.*` state and change it.
+
+use cosmic::iced::futures::SinkExt;
+use cosmic::iced::stream;
+use cosmic::iced::Subscription;
+use futures::StreamExt;
+use matrix_sdk::ruma::api::client::alias::{create_alias, delete_alias};
+use matrix_sdk::ruma::api::client::room::aliases;
+use matrix_sdk::ruma::events::room::canonical_alias::RoomCanonicalAliasEventContent;
+use matrix_sdk::ruma::events::room::guest_access::{GuestAccess, RoomGuestAccessEventContent};
+use matrix_sdk::ruma::events::room::history_visibility::RoomHistoryVisibilityEventContent;
+use matrix_sdk::ruma::events::room::join_rules::{JoinRule, RoomJoinRulesEventContent};
+use matrix_sdk::ruma::events::StateEventType;
+use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::{Client, Room};
+
+use crate::message::{Message, RoomAccess, RoomSettings, SettingsChange, SettingsPermissions};
+
+/// Settings of `room`, sent once and again whenever sync updates the room's state,
+/// including changes made from other clients.
+pub fn settings_subscription(room: Room) -> Subscription<Message> {
+    let room_id = room.room_id().to_owned();
+    Subscription::run_with_id(
+        (std::any::TypeId::of::<RoomSettingsSubscriptionMarker>(), room_id),
+        stream::channel(10, move |mut output| async move {
+            let mut info = room.subscribe_info();
+            let _ = output.send(load_settings(&room).await).await;
+            while info.next().await.is_some() {
+                let _ = output.send(load_settings(&room).await).await;
+            }
+
+            futures::future::pending::<()>().await;
+        }),
+    )
+}
+
+/// Current settings of `room` from the SDK's state store, with what our power level
+/// lets us change.
+pub async fn load_settings(room: &Room) -> Message {
+    let own_user_id = room.own_user_id();
+    let power_levels = room.power_levels().await.ok();
+    let can = |event_type: StateEventType| {
+        power_levels
+            .as_ref()
+            .is_some_and(|levels| levels.user_can_send_state(own_user_id, event_type))
+    };
+    let permissions = SettingsPermissions {
+        name: can(StateEventType::RoomName),
+        topic: can(StateEventType::RoomTopic),
+        avatar: can(StateEventType::RoomAvatar),
+        aliases: can(StateEventType::RoomCanonicalAlias),
+        join_rule: can(StateEventType::RoomJoinRules),
+        guest_access: can(StateEventType::RoomGuestAccess),
+        history_visibility: can(StateEventType::RoomHistoryVisibility),
+        encryption: can(StateEventType::RoomEncryption),
+    };
+
+    let access = match room.join_rule() {
+        JoinRule::Invite => RoomAccess::Invite,
+        JoinRule::Knock => RoomAccess::Knock,
+        JoinRule::Public => RoomAccess::Public,
+        _ => RoomAccess::Other,
+    };
+    let settings = RoomSettings {
+        name: room.name().unwrap_or_default(),
+        topic: room.topic().unwrap_or_default(),
+        avatar_url: room.avatar_url().map(|u| u.to_string()),
+        canonical_alias: room.canonical_alias(),
+        alt_aliases: room.alt_aliases(),
+        access,
+        guest_access: room.guest_access() == GuestAccess::CanJoin,
+        history_visibility: room.history_visibility(),
+        encrypted: room.is_encrypted().await.unwrap_or(false),
+        permissions,
+    };
+    Message::RoomSettingsLoaded(room.room_id().to_owned(), settings)
+}
+
+/// Aliases for `room_id` in our homeserver's room directory, published or not.
+pub async fn load_local_aliases(client: &Client, room_id: OwnedRoomId) -> Message {
+    let result = client
+        .send(aliases::v3::Request::new(room_id.clone()))
+        .await
+        .map(|response| response.aliases)
+        .map_err(|e| format!("Failed to load local addresses: {e}"));
+    Message::LocalAliasesLoaded(room_id, result)
+}
+
+/// Apply one change from the settings panel. The panel picks up the new state from
+/// sync, so only a confirmation or error comes back.
+pub async fn apply_change(
+    client: &Client,
+    room_id: OwnedRoomId,
+    change: SettingsChange,
+) -> Message {
+    let Some(room) = client.get_room(&room_id) else {
+        return Message::RoomSettingSaved(room_id, Err("Unknown room".to_string()));
+    };
+    let (done, result) = match change {
+        SettingsChange::Name(name) => (
+            "Name updated",
+            room.set_name(name).await.map(|_| ()).map_err(|e| e.to_string()),
+        ),
+        SettingsChange::Topic(topic) => (
+            "Topic updated",
+            room.set_room_topic(&topic).await.map(|_| ()).map_err(|e| e.to_string()),
+        ),
+        SettingsChange::RemoveAvatar => (
+            "Avatar removed",
+            room.remove_avatar().await.map(|_| ()).map_err(|e| e.to_string()),
+        ),
+        SettingsChange::PublishedAliases { alias, alt_aliases } => {
+            let mut content = RoomCanonicalAliasEventContent::new();
+            content.alias = alias;
+            content.alt_aliases = alt_aliases;
+            (
+                "Published addresses updated",
+                room.send_state_event(content).await.map(|_| ()).map_err(|e| e.to_string()),
+            )
+        }
+        SettingsChange::Access(access) => {
+            let rule = match access {
+                RoomAccess::Invite => JoinRule::Invite,
+                RoomAccess::Knock => JoinRule::Knock,
+                RoomAccess::Public => JoinRule::Public,
+                RoomAccess::Other => {
+                    return Message::RoomSettingSaved(
+                        room_id,
+                        Err("This join rule can't be set here".to_string()),
+                    );
+                }
+            };
+            (
+                "Access updated",
+                room.send_state_event(RoomJoinRulesEventContent::new(rule))
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+            )
+        }
+        SettingsChange::GuestAccess(allowed) => {
+            let access = if allowed {
+                GuestAccess::CanJoin
+            } else {
+                GuestAccess::Forbidden
+            };
+            (
+                "Guest access updated",
+                room.send_state_event(RoomGuestAccessEventContent::new(access))
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+            )
+        }
+        SettingsChange::HistoryVisibility(visibility) => (
+            "History visibility updated",
+            room.send_state_event(RoomHistoryVisibilityEventContent::new(visibility))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        ),
+        SettingsChange::EnableEncryption => (
+            "Encryption enabled",
+            room.enable_encryption().await.map_err(|e| e.to_string()),
+        ),
+        SettingsChange::AddLocalAlias(alias) => (
+            "Address added",
+            client
+                .send(create_alias::v3::Request::new(alias, room_id.clone()))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        ),
+        SettingsChange::RemoveLocalAlias(alias) => (
+            "Address removed",
+            client
+                .send(delete_alias::v3::Request::new(alias))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        ),
+    };
+    let result = result
+        .map(|()| done.to_string())
+        .map_err(|e| format!("Failed to update the room: {e}"));
+    Message::RoomSettingSaved(room_id, result)
+}
+
+/// Let the user pick an image and make it the room's avatar.
+pub async fn pick_room_avatar(client: &Client, room_id: OwnedRoomId) -> Message {
+    use cosmic::dialog::file_chooser;
+
+    let Some(room) = client.get_room(&room_id) else {
+        return Message::RoomSettingSaved(room_id, Err("Unknown room".to_string()));
+    };
+    let response = match file_chooser::open::Dialog::new()
+        .title("Choose a room avatar")
+        .open_file()
+        .await
+    {
+        Ok(r) => r,
+        Err(file_chooser::Error::Cancelled) => return Message::None,
+        Err(e) => return Message::RoomSettingSaved(room_id, Err(e.to_string())),
+    };
+    let path = match response.url().to_file_path() {
+        Ok(p) => p,
+        Err(_) => {
+            return Message::RoomSettingSaved(room_id, Err("Could not resolve file path".into()));
+        }
+    };
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let data = match tokio::fs::read(&path).await {
+        Ok(d) => d,
+        Err(e) => {
+            return Message::RoomSettingSaved(room_id, Err(format!("Failed to read file: {e}")));
+        }
+    };
+    let result = room
+        .upload_avatar(&mime, data, None)
+        .await
+        .map(|_| "Avatar updated".to_string())
+        .map_err(|e| format!("Failed to upload avatar: {e}"));
+    Message::RoomSettingSaved(room_id, result)
+}
+
+struct RoomSettingsSubscriptionMarker;
@@ -0,0 +1,309 @@
+//! Room membership actions started from the room list, and the public room directory.
+
+use matrix_sdk::ruma::api::client::directory::get_public_rooms_filtered;
+use matrix_sdk::ruma::api::client::room::{create_room, Visibility};
+use matrix_sdk::ruma::directory::Filter;
+use matrix_sdk::ruma::events::direct::DirectEventContent;
+use matrix_sdk::ruma::events::room::encryption::RoomEncryptionEventContent;
+use matrix_sdk::ruma::events::room::join_rules::JoinRule;
+use matrix_sdk::ruma::events::room::member::MembershipState;
+use matrix_sdk::ruma::events::InitialStateEvent;
+use matrix_sdk::ruma::matrix_uri::MatrixId;
+use matrix_sdk::ruma::{
+    MatrixToUri, MatrixUri, OwnedRoomId, OwnedRoomOrAliasId, OwnedServerName, OwnedUserId,
+};
+use matrix_sdk::{Client, Room, RoomState};
+
+use crate::message::{DirectoryPage, Message, PublicRoom, UserProfile};
+
+/// Rooms requested per public directory page.
+const DIRECTORY_PAGE_SIZE: u32 = 30;
+
+/// Users requested per user directory search.
+const USER_SEARCH_LIMIT: u64 = 20;
+
+/// Power level needed to change every room setting.
+const ADMIN_LEVEL: i64 = 100;
+
+/// Settings from the "New room" dialog.
+pub struct NewRoom {
+    pub name: String,
+    pub topic: String,
+    /// Local part of the room alias, if one should be published.
+    pub alias: Option<String>,
+    pub public: bool,
+    pub encrypted: bool,
+    pub invite: Vec<OwnedUserId>,
+}
+
+/// Join a room we were invited to.
+pub async fn accept_invite(client: &Client, room_id: OwnedRoomId) -> Message {
+    let Some(room) = client.get_room(&room_id) else {
+        return Message::InviteFailed(room_id, "Unknown room".to_string());
+    };
+    match room.join().await {
+        Ok(()) => Message::RoomJoined(room_id),
+        Err(e) => Message::InviteFailed(room_id, format!("Failed to join: {e}")),
+    }
+}
+
+/// Reject an invite. With `ignore`, the inviter is added to our ignore list as well,
+/// which also hides any further invites from them.
+pub async fn decline_invite(
+    client: &Client,
+    room_id: OwnedRoomId,
+    ignore: Option<OwnedUserId>,
+) -> Message {
+    let Some(room) = client.get_room(&room_id) else {
+        return Message::InviteFailed(room_id, "Unknown room".to_string());
+    };
+    if let Err(e) = room.leave().await {
+        return Message::InviteFailed(room_id, format!("Failed to decline: {e}"));
+    }
+    if let Some(user_id) = ignore {
+        if let Err(e) = client.account().ignore_user(&user_id).await {
+            return Message::InviteFailed(room_id, format!("Failed to ignore {user_id}: {e}"));
+        }
+    }
+    Message::RoomLeft(room_id)
+}
+
+/// Leave a room, and with `forget` also ask the server to forget it so it disappears
+/// from our history on every device.
+pub async fn leave_room(client: &Client, room_id: OwnedRoomId, forget: bool) -> Message {
+    let Some(room) = client.get_room(&room_id) else {
+        return Message::LeaveFailed(room_id, "Unknown room".to_string());
+    };
+    if room.state() != RoomState::Left {
+        if let Err(e) = room.leave().await {
+            return Message::LeaveFailed(room_id, format!("Failed to leave: {e}"));
+        }
+    }
+    if forget {
+        if let Err(e) = room.forget().await {
+            return Message::LeaveFailed(room_id, format!("Left, but failed to forget: {e}"));
+        }
+    }
+    Message::RoomLeft(room_id)
+}
+
+/// Check whether leaving `room_id` would leave the room unrecoverable, for the
+/// confirmation prompt.
+pub async fn leave_warning(client: &Client, room_id: OwnedRoomId) -> Message {
+    let warning = match client.get_room(&room_id) {
+        Some(room) if room.state() == RoomState::Joined => unrecoverable_reason(&room).await,
+        _ => None,
+    };
+    Message::LeaveWarning(room_id, warning)
+}
+
+/// We are the last member, or the last admin of a room nobody can join uninvited.
+async fn unrecoverable_reason(room: &Room) -> Option<String> {
+    if room.joined_members_count() <= 1 {
+        return Some(
+            "You are the last member. Once you leave, nobody will be able to join this room again."
+                .to_string(),
+        );
+    }
+    if matches!(room.join_rule(), JoinRule::Public) {
+        return None;
+    }
+    let own_user_id = room.own_user_id();
+    let levels = room.users_with_power_levels().await;
+    if levels.get(own_user_id).copied().unwrap_or(0) < ADMIN_LEVEL {
+        return None;
+    }
+    for (user_id, level) in &levels {
+        if user_id == own_user_id || *level < ADMIN_LEVEL {
+            continue;
+        }
+        if let Ok(Some(member)) = room.get_member_no_sync(user_id).await {
+            if *member.membership() == MembershipState::Join {
+                return None;
+            }
+        }
+    }
+    Some(
+        "You are the only admin of this private room. Nobody will be able to change its \
+         settings, and you can only come back if someone invites you."
+            .to_string(),
+    )
+}
+
+/// Resolve a room address typed by the user: `#alias:server`, `!room:server`, a
+/// `https://matrix.to/#/…` link or a `matrix:` URI. Links may carry `via` servers.
+pub fn parse_join_target(
+    input: &str,
+) -> Result<(OwnedRoomOrAliasId, Vec<OwnedServerName>), String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Enter a room alias, ID or link".to_string());
+    }
+    let (id, via) = if input.starts_with("https://matrix.to/") {
+        let uri = MatrixToUri::parse(input).map_err(|e| format!("Invalid matrix.to link: {e}"))?;
+        (uri.id().clone(), uri.via().to_vec())
+    } else if input.starts_with("matrix:") {
+        let uri = MatrixUri::parse(input).map_err(|e| format!("Invalid matrix: URI: {e}"))?;
+        (uri.id().clone(), uri.via().to_vec())
+    } else {
+        let id = OwnedRoomOrAliasId::try_from(input)
+            .map_err(|_| format!("{input} is not a room alias or ID"))?;
+        return Ok((id, Vec::new()));
+    };
+    match id {
+        MatrixId::Room(room_id) => Ok((room_id.into(), via)),
+        MatrixId::RoomAlias(alias) => Ok((alias.into(), via)),
+        MatrixId::Event(room, _) => Ok((room, via)),
+        _ => Err("The link does not point to a room".to_string()),
+    }
+}
+
+/// Join a room by alias or ID, trying the `via` servers for rooms our homeserver
+/// doesn't know about yet.
+pub async fn join_room(
+    client: &Client,
+    target: OwnedRoomOrAliasId,
+    via: Vec<OwnedServerName>,
+) -> Message {
+    match client.join_room_by_id_or_alias(&target, &via).await {
+        Ok(room) => Message::RoomJoined(room.room_id().to_owned()),
+        Err(e) => Message::JoinFailed(format!("Failed to join {target}: {e}")),
+    }
+}
+
+/// One page of `server`'s public room directory (our homeserver's when empty),
+/// filtered by `search`.
+pub async fn public_rooms(
+    client: &Client,
+    server: String,
+    search: String,
+    since: Option<String>,
+) -> Message {
+    let result = fetch_public_rooms(client, &server, &search, since.clone())
+        .await
+        .map(|(rooms, next_batch)| DirectoryPage {
+            server,
+            search,
+            since,
+            rooms,
+            next_batch,
+        });
+    Message::DirectoryLoaded(result)
+}
+
+async fn fetch_public_rooms(
+    client: &Client,
+    server: &str,
+    search: &str,
+    since: Option<String>,
+) -> Result<(Vec<PublicRoom>, Option<String>), String> {
+    let mut request = get_public_rooms_filtered::v3::Request::new();
+    if !server.is_empty() {
+        request.server = Some(
+            OwnedServerName::try_from(server).map_err(|_| format!("{server} is not a server name"))?,
+        );
+    }
+    request.limit = Some(DIRECTORY_PAGE_SIZE.into());
+    request.since = since;
+    let mut filter = Filter::new();
+    filter.generic_search_term = (!search.is_empty()).then(|| search.to_string());
+    request.filter = filter;
+
+    let response = client
+        .public_rooms_filtered(request)
+        .await
+        .map_err(|e| format!("Failed to load the room directory: {e}"))?;
+    let rooms = response
+        .chunk
+        .into_iter()
+        .map(|chunk| PublicRoom {
+            room_id: chunk.room_id,
+            name: chunk.name,
+            topic: chunk.topic,
+            canonical_alias: chunk.canonical_alias.map(|a| a.to_string()),
+            member_count: chunk.num_joined_members.into(),
+            avatar_url: chunk.avatar_url.map(|u| u.to_string()),
+        })
+        .collect();
+    Ok((rooms, response.next_batch))
+}
+
+/// Create a room and invite the chosen users. Public rooms are published in our
+/// homeserver's directory.
+pub async fn create_room(client: &Client, room: NewRoom) -> Message {
+    let mut request = create_room::v3::Request::new();
+    request.name = (!room.name.trim().is_empty()).then(|| room.name.trim().to_string());
+    request.topic = (!room.topic.trim().is_empty()).then(|| room.topic.trim().to_string());
+    request.room_alias_name = room.alias;
+    request.invite = room.invite;
+    if room.public {
+        request.visibility = Visibility::Public;
+        request.preset = Some(create_room::v3::RoomPreset::PublicChat);
+    } else {
+        request.visibility = Visibility::Private;
+        request.preset = Some(create_room::v3::RoomPreset::PrivateChat);
+    }
+    if room.encrypted {
+        request.initial_state = vec![InitialStateEvent::new(
+            RoomEncryptionEventContent::with_recommended_defaults(),
+        )
+        .to_raw_any()];
+    }
+    match client.create_room(request).await {
+        Ok(room) => Message::RoomJoined(room.room_id().to_owned()),
+        Err(e) => Message::CreateRoomFailed(format!("Failed to create room: {e}")),
+    }
+}
+
+/// Search the homeserver's user directory for the "New DM" dialog.
+pub async fn search_users(client: &Client, query: String) -> Message {
+    let result = client
+        .search_users(&query, USER_SEARCH_LIMIT)
+        .await
+        .map(|response| {
+            response
+                .results
+                .into_iter()
+                .map(|user| UserProfile {
+                    user_id: user.user_id,
+                    display_name: user.display_name,
+                    avatar_url: user.avatar_url.map(|u| u.to_string()),
+                })
+                .collect()
+        })
+        .map_err(|e| format!("User search failed: {e}"));
+    Message::DmSearchResults(query, result)
+}
+
+/// Open our DM with `user_id`: an existing joined room from `m.direct` if there is
+/// one, otherwise a new encrypted DM. The SDK records new DMs in `m.direct`.
+pub async fn start_dm(client: &Client, user_id: OwnedUserId) -> Message {
+    if let Some(room_id) = existing_dm(client, &user_id).await {
+        return Message::RoomJoined(room_id);
+    }
+    match client.create_dm(&user_id).await {
+        Ok(room) => Message::RoomJoined(room.room_id().to_owned()),
+        Err(e) => Message::DmFailed(format!("Failed to start a DM with {user_id}: {e}")),
+    }
+}
+
+/// A joined room listed for `user_id` in our `m.direct` account data.
+async fn existing_dm(client: &Client, user_id: &OwnedUserId) -> Option<OwnedRoomId> {
+    let raw = client
+        .account()
+        .account_data::<DirectEventContent>()
+        .await
+        .ok()??;
+    let content = raw.deserialize().ok()?;
+    content
+        .0
+        .iter()
+        .filter(|(target, _)| target.as_str() == user_id.as_str())
+        .flat_map(|(_, room_ids)| room_ids)
+        .find(|room_id| {
+            client
+                .get_room(room_id)
+                .is_some_and(|room| room.state() == RoomState::Joined)
+        })
+        .cloned()
+}
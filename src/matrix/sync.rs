@@ -4,88 +4,296 @@ use std::sync::Arc;
 use cosmic::iced::futures::SinkExt;
 use cosmic::iced::stream;
 use cosmic::iced::Subscription;
+use eyeball_im::VectorDiff;
+use futures::StreamExt;
 use matrix_sdk::config::SyncSettings;
 use matrix_sdk::ruma::api::client::filter::FilterDefinition;
-use matrix_sdk::ruma::events::{AnySyncTimelineEvent, AnyToDeviceEvent};
-use matrix_sdk::Client;
+use matrix_sdk::ruma::events::key::verification::request::ToDeviceKeyVerificationRequestEvent;
+use matrix_sdk::ruma::events::room::message::OriginalSyncRoomMessageEvent;
+use matrix_sdk::ruma::events::{
+    AnySyncMessageLikeEvent, AnySyncTimelineEvent, AnyToDeviceEvent, SyncMessageLikeEvent,
+};
+use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::sliding_sync::Version as SlidingSyncVersion;
+use matrix_sdk::event_handler::EventHandlerDropGuard;
+use matrix_sdk::{Client, Room, RoomState};
+use matrix_sdk_ui::room_list_service::{filters, Room as RoomListRoom};
+use matrix_sdk_ui::sync_service::{State as SyncServiceState, SyncService};
 
 use crate::matrix::timeline::convert_message_event;
-use crate::message::{Message, RoomEntry, TimelineItem};
+use crate::message::{Inviter, Message, RoomEntry, TimelineItem};
+
+/// Room-list entries requested from the sliding sync list; we render the whole list.
+const ROOM_LIST_PAGE_SIZE: usize = 10_000;
+/// Consecutive sliding sync failures before falling back to v2 sync.
+const MAX_SYNC_FAILURES: u32 = 3;
+/// How long sliding sync must keep running before earlier failures are forgotten.
+const STABLE_SYNC_PERIOD: std::time::Duration = std::time::Duration::from_secs(60);
 
 pub fn sync_subscription(client: Arc<Client>) -> Subscription<Message> {
+    // One sync loop per signed-in account.
+    let user_id = client.user_id().map(|u| u.to_owned());
     Subscription::run_with_id(
-        std::any::TypeId::of::<SyncSubscriptionMarker>(),
+        (std::any::TypeId::of::<SyncSubscriptionMarker>(), user_id),
         stream::channel(100, move |mut output| {
             let client = client.clone();
             async move {
                 let _ = output.send(Message::SyncStarted).await;
 
-                let filter = FilterDefinition::with_lazy_loading();
-                let settings = SyncSettings::default().filter(filter.into());
-
-                match client.sync_once(settings.clone()).await {
-                    Ok(response) => {
-                        let rooms = collect_rooms(&client).await;
-                        let _ = output.send(Message::RoomsUpdated(rooms)).await;
-
-                        for (room_id, update) in &response.rooms.join {
-                            let new_items = extract_new_items_from_events(
-                                &client, room_id, &update.timeline.events,
-                            ).await;
-                            if !new_items.is_empty() {
-                                let _ = output
-                                    .send(Message::IncomingEvents(room_id.clone(), new_items))
-                                    .await;
-                            }
-                        }
+                if enable_sliding_sync(&client).await {
+                    tracing::info!("Using simplified sliding sync");
+                    if let Err(e) = run_room_list_sync(&client, &mut output).await {
+                        tracing::warn!("Sliding sync unavailable, falling back to v2 sync: {e}");
+                        run_v2_sync(&client, &mut output).await;
+                    }
+                } else {
+                    tracing::info!("Homeserver lacks sliding sync, using v2 sync");
+                    run_v2_sync(&client, &mut output).await;
+                }
+
+                futures::future::pending::<()>().await;
+            }
+        }),
+    )
+}
+
+/// Switch the client to native (MSC4186) sliding sync if the homeserver advertises it.
+async fn enable_sliding_sync(client: &Client) -> bool {
+    let supported = client
+        .available_sliding_sync_versions()
+        .await
+        .into_iter()
+        .any(|v| matches!(v, SlidingSyncVersion::Native));
+    if supported {
+        client.set_sliding_sync_version(SlidingSyncVersion::Native);
+    }
+    supported
+}
+
+/// Drive the `SyncService` and push incremental room-list changes into the app.
+/// Only returns (with an error) if the service can't be set up or keeps failing, so
+/// the caller can fall back to v2 sync.
+async fn run_room_list_sync(
+    client: &Client,
+    output: &mut cosmic::iced::futures::channel::mpsc::Sender<Message>,
+) -> Result<(), String> {
+    let sync_service = SyncService::builder(client.clone())
+        .build()
+        .await
+        .map_err(|e| format!("Failed to build sync service: {e}"))?;
+    let all_rooms = sync_service
+        .room_list_service()
+        .all_rooms()
+        .await
+        .map_err(|e| format!("Failed to open room list: {e}"))?;
+
+    let (entries, controller) = all_rooms.entries_with_dynamic_adapters(ROOM_LIST_PAGE_SIZE);
+    controller.set_filter(Box::new(filters::new_filter_non_left()));
+    futures::pin_mut!(entries);
+
+    // The v2 loop reads these from the raw sync response; here they come via handlers.
+    // They are removed when this returns, so a v2 fallback doesn't deliver events twice.
+    let _handlers = register_event_handlers(client, output);
+
+    let mut state = sync_service.state();
+    sync_service.start().await;
+    let mut failures = 0;
+    let mut running_since = std::time::Instant::now();
+
+    // Room IDs in SDK list order, so index-based diffs can be turned into removals.
+    let mut ids: Vec<OwnedRoomId> = Vec::new();
+
+    loop {
+        tokio::select! {
+            Some(diffs) = entries.next() => {
+                let before: HashSet<OwnedRoomId> = ids.iter().cloned().collect();
+                let mut touched = Vec::new();
+                for diff in diffs {
+                    apply_room_list_diff(&mut ids, &mut touched, diff);
+                }
+                let after: HashSet<OwnedRoomId> = ids.iter().cloned().collect();
+
+                let dm_ids = collect_dm_room_ids(client).await;
+                let mut upserted = Vec::new();
+                let mut seen = HashSet::new();
+                for room in touched.iter().rev() {
+                    if after.contains(room.room_id()) && seen.insert(room.room_id().to_owned()) {
+                        upserted.push(room_entry(room, &dm_ids).await);
+                    }
+                }
+                let removed: Vec<OwnedRoomId> = before.difference(&after).cloned().collect();
 
-                        emit_verification_requests(&response.to_device, &mut output).await;
-
-                        let mut settings = settings.token(response.next_batch);
-                        loop {
-                            match client.sync_once(settings.clone()).await {
-                                Ok(response) => {
-                                    settings = settings.token(response.next_batch);
-                                    let rooms = collect_rooms(&client).await;
-                                    let _ = output.send(Message::RoomsUpdated(rooms)).await;
-
-                                    for (room_id, update) in &response.rooms.join {
-                                        let new_items = extract_new_items_from_events(
-                                            &client, room_id, &update.timeline.events,
-                                        ).await;
-                                        if !new_items.is_empty() {
-                                            let _ = output
-                                                .send(Message::IncomingEvents(
-                                                    room_id.clone(),
-                                                    new_items,
-                                                ))
-                                                .await;
-                                        }
-                                    }
-
-                                    emit_verification_requests(&response.to_device, &mut output)
-                                        .await;
-                                }
-                                Err(e) => {
-                                    let _ = output
-                                        .send(Message::SyncError(format!("Sync error: {e}")))
-                                        .await;
-                                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                                }
-                            }
+                let _ = output.send(Message::RoomsChanged { upserted, removed }).await;
+            }
+            Some(new_state) = state.next() => {
+                match new_state {
+                    SyncServiceState::Running => running_since = std::time::Instant::now(),
+                    SyncServiceState::Error | SyncServiceState::Terminated => {
+                        // The service reports Running right after a restart, so only
+                        // a stretch of working sync counts as recovery.
+                        if running_since.elapsed() > STABLE_SYNC_PERIOD {
+                            failures = 0;
+                        }
+                        failures += 1;
+                        if failures >= MAX_SYNC_FAILURES {
+                            return Err(format!(
+                                "Sync service stopped {failures} times in a row"
+                            ));
                         }
+                        let _ = output
+                            .send(Message::SyncError(format!("Sync service stopped: {new_state:?}")))
+                            .await;
+                        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                        sync_service.start().await;
                     }
-                    Err(e) => {
+                    _ => {}
+                }
+            }
+            else => return Err("Room list stream ended".to_string()),
+        }
+    }
+}
+
+/// Mirror one room-list diff into `ids`, recording every room whose entry changed.
+fn apply_room_list_diff(
+    ids: &mut Vec<OwnedRoomId>,
+    touched: &mut Vec<Room>,
+    diff: VectorDiff<RoomListRoom>,
+) {
+    match diff {
+        VectorDiff::Append { values } => {
+            for room in values {
+                ids.push(room.room_id().to_owned());
+                touched.push(room.inner_room().clone());
+            }
+        }
+        VectorDiff::Clear => ids.clear(),
+        VectorDiff::PushFront { value } => {
+            ids.insert(0, value.room_id().to_owned());
+            touched.push(value.inner_room().clone());
+        }
+        VectorDiff::PushBack { value } => {
+            ids.push(value.room_id().to_owned());
+            touched.push(value.inner_room().clone());
+        }
+        VectorDiff::PopFront => {
+            if !ids.is_empty() {
+                ids.remove(0);
+            }
+        }
+        VectorDiff::PopBack => {
+            ids.pop();
+        }
+        VectorDiff::Insert { index, value } => {
+            ids.insert(index.min(ids.len()), value.room_id().to_owned());
+            touched.push(value.inner_room().clone());
+        }
+        VectorDiff::Set { index, value } => {
+            if let Some(slot) = ids.get_mut(index) {
+                *slot = value.room_id().to_owned();
+            }
+            touched.push(value.inner_room().clone());
+        }
+        VectorDiff::Remove { index } => {
+            if index < ids.len() {
+                ids.remove(index);
+            }
+        }
+        VectorDiff::Truncate { length } => ids.truncate(length),
+        VectorDiff::Reset { values } => {
+            ids.clear();
+            for room in values {
+                ids.push(room.room_id().to_owned());
+                touched.push(room.inner_room().clone());
+            }
+        }
+    }
+}
+
+/// Forward new messages (for notifications) and verification requests to the app.
+/// The handlers stay registered until the returned guards are dropped.
+fn register_event_handlers(
+    client: &Client,
+    output: &cosmic::iced::futures::channel::mpsc::Sender<Message>,
+) -> [EventHandlerDropGuard; 2] {
+    let tx = output.clone();
+    let messages = client.add_event_handler(move |ev: OriginalSyncRoomMessageEvent, room: Room| {
+        let mut tx = tx.clone();
+        async move {
+            let (display_names, avatar_urls) =
+                crate::matrix::timeline::build_member_info(&room).await;
+            let ev = AnySyncMessageLikeEvent::RoomMessage(SyncMessageLikeEvent::Original(ev));
+            if let Some(item) = convert_message_event(&ev, &display_names, &avatar_urls) {
+                let _ = tx
+                    .send(Message::IncomingEvents(room.room_id().to_owned(), vec![item]))
+                    .await;
+            }
+        }
+    });
+
+    let tx = output.clone();
+    let verification = client.add_event_handler(move |ev: ToDeviceKeyVerificationRequestEvent| {
+        let mut tx = tx.clone();
+        async move {
+            let _ = tx
+                .send(Message::IncomingVerificationRequest {
+                    flow_id: ev.content.transaction_id.to_string(),
+                    sender: ev.sender.to_string(),
+                })
+                .await;
+        }
+    });
+
+    [
+        client.event_handler_drop_guard(messages),
+        client.event_handler_drop_guard(verification),
+    ]
+}
+
+/// Classic `/sync` v2 loop, used when the homeserver has no sliding sync support.
+async fn run_v2_sync(
+    client: &Client,
+    output: &mut cosmic::iced::futures::channel::mpsc::Sender<Message>,
+) {
+    let filter = FilterDefinition::with_lazy_loading();
+    let mut settings = SyncSettings::default().filter(filter.into());
+    let mut initial = true;
+
+    loop {
+        match client.sync_once(settings.clone()).await {
+            Ok(response) => {
+                initial = false;
+                settings = settings.token(response.next_batch);
+                let rooms = collect_rooms(client).await;
+                let _ = output.send(Message::RoomsUpdated(rooms)).await;
+
+                for (room_id, update) in &response.rooms.join {
+                    let new_items =
+                        extract_new_items_from_events(client, room_id, &update.timeline.events)
+                            .await;
+                    if !new_items.is_empty() {
                         let _ = output
-                            .send(Message::SyncError(format!("Initial sync failed: {e}")))
+                            .send(Message::IncomingEvents(room_id.clone(), new_items))
                             .await;
                     }
                 }
 
-                futures::future::pending::<()>().await;
+                emit_verification_requests(&response.to_device, output).await;
             }
-        }),
-    )
+            Err(e) if initial => {
+                let _ = output
+                    .send(Message::SyncError(format!("Initial sync failed: {e}")))
+                    .await;
+                return;
+            }
+            Err(e) => {
+                let _ = output
+                    .send(Message::SyncError(format!("Sync error: {e}")))
+                    .await;
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            }
+        }
+    }
 }
 
 async fn extract_new_items_from_events(
@@ -127,74 +335,100 @@ async fn collect_dm_room_ids(client: &Client) -> HashSet<String> {
 }
 
 async fn collect_rooms(client: &Client) -> Vec<RoomEntry> {
+    let dm_ids = collect_dm_room_ids(client).await;
+
     let mut entries = Vec::new();
+    for room in client.joined_rooms().into_iter().chain(client.invited_rooms()) {
+        entries.push(room_entry(&room, &dm_ids).await);
+    }
+    entries
+}
 
-    let dm_ids = collect_dm_room_ids(client).await;
+/// Build the sidebar entry for a single room.
+async fn room_entry(room: &Room, dm_ids: &HashSet<String>) -> RoomEntry {
+    let name = room
+        .cached_display_name()
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| room.room_id().to_string());
+
+    let counts = room.unread_notification_counts();
+    let unread_count = counts.notification_count;
+    let mention_count = counts.highlight_count;
 
-    for room in client.joined_rooms() {
-        let name = room
-            .cached_display_name()
-            .map(|n| n.to_string())
-            .unwrap_or_else(|| room.room_id().to_string());
+    let is_encrypted = room.is_encrypted().await.unwrap_or(false);
 
-        let counts = room.unread_notification_counts();
-        let unread_count = counts.notification_count;
-        let mention_count = counts.highlight_count;
+    let topic = room.topic();
+    let canonical_alias = room.canonical_alias().map(|a| a.to_string());
 
-        let is_encrypted = room.is_encrypted().await.unwrap_or(false);
+    let avatar_letter = name.chars().next().unwrap_or('#');
 
-        let topic = room.topic();
+    let is_dm = dm_ids.contains(room.room_id().as_str());
 
-        let avatar_letter = name.chars().next().unwrap_or('#');
+    // Fetch room tags
+    let (is_favourite, is_low_priority) = match room.tags().await {
+        Ok(Some(tags)) => {
+            let fav = tags.contains_key(&matrix_sdk::ruma::events::tag::TagName::Favorite);
+            let low = tags.contains_key(&matrix_sdk::ruma::events::tag::TagName::LowPriority);
+            (fav, low)
+        }
+        _ => (false, false),
+    };
+    let avatar_url = room.avatar_url().map(|u| u.to_string());
 
-        let is_dm = dm_ids.contains(room.room_id().as_str());
+    let is_invite = room.state() == RoomState::Invited;
+    let inviter = if is_invite { inviter(room).await } else { None };
 
-        // Fetch room tags
-        let (is_favourite, is_low_priority) = match room.tags().await {
-            Ok(Some(tags)) => {
-                let fav = tags.contains_key(&matrix_sdk::ruma::events::tag::TagName::Favorite);
-                let low = tags.contains_key(&matrix_sdk::ruma::events::tag::TagName::LowPriority);
-                (fav, low)
-            }
-            _ => (false, false),
-        };
-        let avatar_url = room.avatar_url().map(|u| u.to_string());
-
-        let (last_message, last_message_ts) = room
-            .latest_event()
-            .and_then(|ev| {
-                let timeline_ev = ev.event().raw().deserialize().ok()?;
-                let ts_millis: i64 = timeline_ev.origin_server_ts().0.into();
-                if let AnySyncTimelineEvent::MessageLike(ref msg_ev) = timeline_ev {
-                    // Use empty maps here — sidebar previews don't need resolved names/avatars
-                    if let Some(TimelineItem::Message(m)) =
-                        convert_message_event(msg_ev, &std::collections::HashMap::new(), &std::collections::HashMap::new())
-                    {
-                        return Some((Some(m.body), Some(ts_millis as u64)));
-                    }
+    let (last_message, last_message_ts) = room
+        .latest_event()
+        .and_then(|ev| {
+            let timeline_ev = ev.event().raw().deserialize().ok()?;
+            let ts_millis: i64 = timeline_ev.origin_server_ts().0.into();
+            if let AnySyncTimelineEvent::MessageLike(ref msg_ev) = timeline_ev {
+                // Use empty maps here — sidebar previews don't need resolved names/avatars
+                if let Some(TimelineItem::Message(m)) =
+                    convert_message_event(msg_ev, &std::collections::HashMap::new(), &std::collections::HashMap::new())
+                {
+                    return Some((Some(m.body), Some(ts_millis as u64)));
                 }
-                None
-            })
-            .unwrap_or((None, None));
-
-        entries.push(RoomEntry {
-            room_id: room.room_id().to_owned(),
-            name,
-            unread_count,
-            mention_count,
-            is_encrypted,
-            topic,
-            last_message,
-            last_message_ts,
-            avatar_letter,
-            is_favourite,
-            is_low_priority,
-            is_dm,
-            avatar_url,
-        });
+            }
+            None
+        })
+        .unwrap_or((None, None));
+
+    RoomEntry {
+        room_id: room.room_id().to_owned(),
+        name,
+        unread_count,
+        mention_count,
+        is_encrypted,
+        topic,
+        canonical_alias,
+        last_message,
+        last_message_ts,
+        avatar_letter,
+        is_favourite,
+        is_low_priority,
+        is_dm,
+        avatar_url,
+        is_invite,
+        inviter,
     }
+}
 
-    entries
+/// Sender of our pending invite to `room`, from the stripped invite state.
+async fn inviter(room: &Room) -> Option<Inviter> {
+    let member = match room.invite_details().await {
+        Ok(details) => details.inviter?,
+        Err(e) => {
+            tracing::debug!("Failed to load invite details for {}: {e}", room.room_id());
+            return None;
+        }
+    };
+    Some(Inviter {
+        user_id: member.user_id().to_owned(),
+        display_name: member.display_name().map(str::to_string),
+        avatar_url: member.avatar_url().map(|u| u.to_string()),
+    })
 }
 
 async fn emit_verification_requests(
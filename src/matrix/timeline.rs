@@ -1,59 +1,472 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use matrix_sdk::room::MessagesOptions;
-use matrix_sdk::ruma::events::room::message::MessageType;
-use matrix_sdk::ruma::events::AnySyncTimelineEvent;
+use cosmic::iced::futures::SinkExt;
+use cosmic::iced::stream;
+use cosmic::iced::Subscription;
+use eyeball_im::VectorDiff;
+use futures::StreamExt;
+use matrix_sdk::room::{IncludeRelations, RelationsOptions};
+use matrix_sdk::ruma::api::client::receipt::create_receipt::v3::ReceiptType;
+use matrix_sdk::ruma::events::fully_read::FullyReadEventContent;
+use matrix_sdk::ruma::events::receipt::ReceiptThread;
+use matrix_sdk::ruma::events::relation::RelationType;
+use matrix_sdk::ruma::events::room::encrypted::OriginalSyncRoomEncryptedEvent;
+use matrix_sdk::ruma::events::room::message::{
+    FormattedBody, MessageFormat, MessageType, Relation,
+};
+use matrix_sdk::ruma::events::{
+    AnySyncMessageLikeEvent, AnySyncTimelineEvent, Mentions, SyncMessageLikeEvent,
+};
+use matrix_sdk::ruma::serde::Raw;
+use matrix_sdk::ruma::{EventId, OwnedEventId, OwnedRoomId, OwnedUserId, UserId};
 use matrix_sdk::{Room, RoomMemberships};
+use matrix_sdk_ui::timeline::{
+    EventTimelineItem, MembershipChange, Timeline, TimelineDetails,
+    TimelineItem as SdkTimelineItem, TimelineItemContent, TimelineItemKind, VirtualTimelineItem,
+};
 
-use crate::message::{ImageContent, TimelineItem, TimelineMessage};
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
 
-pub async fn load_room_timeline(
-    room: &Room,
-) -> Result<(Vec<TimelineItem>, Option<String>), String> {
-    let options = MessagesOptions::backward();
-    let messages = room
-        .messages(options)
+use crate::matrix::html;
+use crate::message::{
+    EditHistoryEntry, ImageContent, Message, ReactionGroup, RedactionInfo, RichBlock,
+    RoomMember, TimelineDiff, TimelineItem, TimelineMessage,
+};
+
+/// Build the SDK timeline for a room. Read markers and receipts are tracked so the
+/// SDK can place the unread marker for us.
+pub async fn open_room_timeline(room: &Room) -> Result<Timeline, String> {
+    Timeline::builder(room)
+        .track_read_marker_and_receipts()
+        .build()
         .await
-        .map_err(|e| format!("Failed to load messages: {e}"))?;
+        .map_err(|e| format!("Failed to open timeline: {e}"))
+}
 
-    let (display_names, avatar_urls) = build_member_info(room).await;
+/// Stream the open room's timeline diffs into the app. The first batch is always a `Reset`.
+/// `own_user_id` is used to mark our own reactions.
+pub fn timeline_subscription(
+    room_id: OwnedRoomId,
+    timeline: Arc<Timeline>,
+    own_user_id: OwnedUserId,
+) -> Subscription<Message> {
+    Subscription::run_with_id(
+        (std::any::TypeId::of::<TimelineSubscriptionMarker>(), room_id.clone()),
+        stream::channel(100, move |mut output| async move {
+            let (initial, stream) = timeline.subscribe_batched().await;
+            futures::pin_mut!(stream);
 
-    let mut items = Vec::new();
-    let mut last_date: Option<chrono::NaiveDate> = None;
+            let items = initial
+                .iter()
+                .map(|item| convert_timeline_item(item, &own_user_id))
+                .collect();
+            let _ = output
+                .send(Message::TimelineDiffs(room_id.clone(), vec![TimelineDiff::Reset(items)]))
+                .await;
 
-    // Messages come in reverse order (newest first), so we reverse
-    for event in messages.chunk.iter().rev() {
-        if let Ok(ev) = event.raw().deserialize() {
-            // Extract date for separator logic
-            let ts_millis: i64 = ev.origin_server_ts().0.into();
-            let item_date = ts_to_naive_date(ts_millis);
+            while let Some(batch) = stream.next().await {
+                let diffs = batch
+                    .into_iter()
+                    .map(|diff| convert_diff(diff, &own_user_id))
+                    .collect();
+                let _ = output
+                    .send(Message::TimelineDiffs(room_id.clone(), diffs))
+                    .await;
+            }
 
-            if let Some(date) = item_date {
-                if last_date.as_ref() != Some(&date) {
-                    items.push(TimelineItem::DateSeparator(format_date_label(date)));
-                    last_date = Some(date);
+            futures::future::pending::<()>().await;
+        }),
+    )
+}
+
+/// Users typing in the open room, from the ephemeral events in the sync response.
+/// The SDK leaves out our own user.
+pub fn typing_subscription(room: Room) -> Subscription<Message> {
+    let room_id = room.room_id().to_owned();
+    Subscription::run_with_id(
+        (std::any::TypeId::of::<TypingSubscriptionMarker>(), room_id.clone()),
+        stream::channel(10, move |mut output| async move {
+            let (_guard, mut receiver) = room.subscribe_to_typing_notifications();
+            loop {
+                match receiver.recv().await {
+                    Ok(user_ids) => {
+                        let _ = output
+                            .send(Message::TypingUsers(room_id.clone(), user_ids))
+                            .await;
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
                 }
             }
 
-            match ev {
-                AnySyncTimelineEvent::MessageLike(msg_ev) => {
-                    if let Some(item) = convert_message_event(&msg_ev, &display_names, &avatar_urls) {
-                        items.push(item);
-                    }
+            futures::future::pending::<()>().await;
+        }),
+    )
+}
+
+/// Tell the room whether we are typing. Failures only matter to the log.
+pub async fn send_typing_notice(room: Room, typing: bool) -> Message {
+    if let Err(e) = room.typing_notice(typing).await {
+        tracing::debug!("Failed to send typing notice: {e}");
+    }
+    Message::None
+}
+
+/// Our `m.fully_read` marker for `room`, as last written by any of our clients.
+pub async fn load_fully_read(room: Room) -> Message {
+    let event_id = match room.account_data_static::<FullyReadEventContent>().await {
+        Ok(Some(raw)) => raw.deserialize().ok().map(|ev| ev.content.event_id.to_string()),
+        Ok(None) => None,
+        Err(e) => {
+            tracing::debug!("Failed to load fully-read marker: {e}");
+            None
+        }
+    };
+    Message::FullyReadLoaded(room.room_id().to_owned(), event_id)
+}
+
+/// Our `m.fully_read` marker for `room` whenever sync brings a new one, e.g. after
+/// another of our clients moved it.
+pub fn fully_read_subscription(room: Room) -> Subscription<Message> {
+    use matrix_sdk::ruma::events::fully_read::FullyReadEvent;
+
+    let room_id = room.room_id().to_owned();
+    Subscription::run_with_id(
+        (std::any::TypeId::of::<FullyReadSubscriptionMarker>(), room_id.clone()),
+        stream::channel(10, move |mut output| async move {
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+            let handle = room.add_event_handler(move |event: FullyReadEvent| {
+                let sender = sender.clone();
+                async move {
+                    let _ = sender.send(event.content.event_id.to_string());
                 }
-                AnySyncTimelineEvent::State(state_ev) => {
-                    let desc = format_state_event(&state_ev);
-                    if !desc.is_empty() {
-                        items.push(TimelineItem::StateEvent(desc));
+            });
+            // Unregisters the handler when the room is closed
+            let _guard = room.client().event_handler_drop_guard(handle);
+            while let Some(event_id) = receiver.recv().await {
+                let _ = output
+                    .send(Message::FullyReadLoaded(room_id.clone(), Some(event_id)))
+                    .await;
+            }
+
+            futures::future::pending::<()>().await;
+        }),
+    )
+}
+
+/// Move our `m.fully_read` marker to `event_id`, so every client shows the
+/// "New messages" line in the same place.
+pub async fn send_fully_read(room: Room, event_id: String) -> Message {
+    let Ok(event_id) = OwnedEventId::try_from(event_id) else {
+        return Message::None;
+    };
+    if let Err(e) = room
+        .send_single_receipt(ReceiptType::FullyRead, ReceiptThread::Unthreaded, event_id)
+        .await
+    {
+        tracing::debug!("Failed to move fully-read marker: {e}");
+    }
+    Message::None
+}
+
+fn convert_diff(diff: VectorDiff<Arc<SdkTimelineItem>>, own_user_id: &UserId) -> TimelineDiff {
+    match diff {
+        VectorDiff::Append { values } => {
+            TimelineDiff::Append(values.iter().map(|item| convert_timeline_item(item, own_user_id)).collect())
+        }
+        VectorDiff::Clear => TimelineDiff::Clear,
+        VectorDiff::PushFront { value } => TimelineDiff::PushFront(convert_timeline_item(&value, own_user_id)),
+        VectorDiff::PushBack { value } => TimelineDiff::PushBack(convert_timeline_item(&value, own_user_id)),
+        VectorDiff::PopFront => TimelineDiff::PopFront,
+        VectorDiff::PopBack => TimelineDiff::PopBack,
+        VectorDiff::Insert { index, value } => {
+            TimelineDiff::Insert(index, convert_timeline_item(&value, own_user_id))
+        }
+        VectorDiff::Set { index, value } => TimelineDiff::Set(index, convert_timeline_item(&value, own_user_id)),
+        VectorDiff::Remove { index } => TimelineDiff::Remove(index),
+        VectorDiff::Truncate { length } => TimelineDiff::Truncate(length),
+        VectorDiff::Reset { values } => {
+            TimelineDiff::Reset(values.iter().map(|item| convert_timeline_item(item, own_user_id)).collect())
+        }
+    }
+}
+
+/// Convert one SDK timeline item. Every SDK item maps to exactly one of ours
+/// (possibly `Hidden`) so diff indices stay valid.
+pub fn convert_timeline_item(item: &SdkTimelineItem, own_user_id: &UserId) -> TimelineItem {
+    match item.kind() {
+        TimelineItemKind::Virtual(VirtualTimelineItem::DateDivider(ts)) => {
+            let ts_millis: i64 = ts.0.into();
+            match ts_to_naive_date(ts_millis) {
+                Some(date) => TimelineItem::DateSeparator(format_date_label(date)),
+                None => TimelineItem::Hidden { event_id: None },
+            }
+        }
+        TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker) => TimelineItem::UnreadMarker,
+        TimelineItemKind::Event(event) => convert_event_item(event, own_user_id),
+        #[allow(unreachable_patterns)]
+        _ => TimelineItem::Hidden { event_id: None },
+    }
+}
+
+fn convert_event_item(event: &EventTimelineItem, own_user_id: &UserId) -> TimelineItem {
+    let mut item = convert_event_content(event, own_user_id);
+    if let TimelineItem::Message(ref mut message) = item {
+        message.read_by = event
+            .read_receipts()
+            .keys()
+            .filter(|user_id| user_id.as_ref() != own_user_id)
+            .map(|user_id| user_id.to_string())
+            .collect();
+    }
+    item
+}
+
+fn convert_event_content(event: &EventTimelineItem, own_user_id: &UserId) -> TimelineItem {
+    let event_id = event.event_id().map(|id| id.to_string());
+    match event.content() {
+        TimelineItemContent::Message(msg) => {
+            let (raw_body, is_emote, image, formatted) = describe_msgtype(msg.msgtype());
+
+            let (reply_ctx, body) = strip_reply_fallback(&raw_body);
+            let (reply_to_sender, reply_to_body) = match msg.in_reply_to() {
+                Some(details) => match &details.event {
+                    TimelineDetails::Ready(replied) => {
+                        let preview = match replied.content() {
+                            TimelineItemContent::Message(m) => m.body().chars().take(80).collect(),
+                            _ => String::new(),
+                        };
+                        (Some(replied.sender().to_string()), Some(preview))
                     }
+                    _ => match reply_ctx {
+                        Some((id, preview)) => (Some(id), Some(preview)),
+                        None => (None, None),
+                    },
+                },
+                None => match reply_ctx {
+                    Some((id, preview)) => (Some(id), Some(preview)),
+                    None => (None, None),
+                },
+            };
+
+            let mut message =
+                base_message(event, body, is_emote, image, reply_to_sender, reply_to_body);
+            message.is_edited = msg.is_edited();
+            message.is_notice = matches!(msg.msgtype(), MessageType::Notice(_));
+            message.formatted = formatted;
+            message.reactions = convert_reactions(event, own_user_id);
+            message.thread_root = msg.thread_root().map(|id| id.to_string());
+            message.thread_reply_count = unsigned(event)
+                .and_then(|u| u.relations)
+                .and_then(|r| r.thread)
+                .map(|t| t.count)
+                .unwrap_or(0);
+            TimelineItem::Message(message)
+        }
+        TimelineItemContent::RedactedMessage => {
+            let mut message = base_message(event, String::new(), false, None, None, None);
+            message.can_edit = false;
+            message.redaction = Some(redaction_info(event));
+            TimelineItem::Message(message)
+        }
+        TimelineItemContent::UnableToDecrypt(_) => TimelineItem::Message(base_message(
+            event,
+            "[Unable to decrypt]".to_string(),
+            false,
+            None,
+            None,
+            None,
+        )),
+        TimelineItemContent::MembershipChange(change) => {
+            let user = change.user_id().to_string();
+            let desc = match change.change() {
+                Some(MembershipChange::Joined) => format!("{user} joined the room"),
+                Some(MembershipChange::Left) => format!("{user} left the room"),
+                _ => String::new(),
+            };
+            if desc.is_empty() {
+                TimelineItem::Hidden { event_id }
+            } else {
+                TimelineItem::StateEvent {
+                    event_id,
+                    description: desc,
                 }
             }
         }
+        TimelineItemContent::OtherState(state) => {
+            use matrix_sdk::ruma::events::FullStateEventContent;
+            use matrix_sdk_ui::timeline::AnyOtherFullStateEventContent;
+            match state.content() {
+                AnyOtherFullStateEventContent::RoomName(FullStateEventContent::Original {
+                    content,
+                    ..
+                }) => TimelineItem::StateEvent {
+                    event_id,
+                    description: format!("Room name changed to: {}", content.name),
+                },
+                AnyOtherFullStateEventContent::RoomTopic(FullStateEventContent::Original {
+                    content,
+                    ..
+                }) => TimelineItem::StateEvent {
+                    event_id,
+                    description: format!("Topic changed to: {}", content.topic),
+                },
+                _ => TimelineItem::Hidden { event_id },
+            }
+        }
+        _ => TimelineItem::Hidden { event_id },
+    }
+}
+
+fn base_message(
+    event: &EventTimelineItem,
+    body: String,
+    is_emote: bool,
+    image: Option<ImageContent>,
+    reply_to_sender: Option<String>,
+    reply_to_body: Option<String>,
+) -> TimelineMessage {
+    let sender = event.sender().to_string();
+    let (display_name, sender_avatar_url) = match event.sender_profile() {
+        TimelineDetails::Ready(profile) => (
+            profile.display_name.clone(),
+            profile.avatar_url.as_ref().map(|u| u.to_string()),
+        ),
+        _ => (None, None),
+    };
+    let sender_display =
+        display_name.unwrap_or_else(|| event.sender().localpart().to_string());
+
+    let ts_millis: i64 = event.timestamp().0.into();
+    let datetime = chrono::DateTime::from_timestamp_millis(ts_millis).unwrap_or_default();
+
+    TimelineMessage {
+        event_id: event.event_id().map(|id| id.to_string()).unwrap_or_default(),
+        sender,
+        sender_display,
+        body,
+        timestamp: datetime.format("%H:%M").to_string(),
+        is_emote,
+        is_notice: false,
+        is_continuation: false,
+        reply_to_sender,
+        reply_to_body,
+        image,
+        sender_avatar_url,
+        is_local_echo: event.is_local_echo(),
+        is_edited: false,
+        can_edit: event.is_editable(),
+        is_own: event.is_own(),
+        redaction: None,
+        reactions: Vec::new(),
+        thread_root: None,
+        thread_reply_count: 0,
+        formatted: None,
+        read_by: Vec::new(),
     }
+}
+
+/// Group the item's `m.reaction` annotations by key, in the order they were first sent.
+fn convert_reactions(event: &EventTimelineItem, own_user_id: &UserId) -> Vec<ReactionGroup> {
+    event
+        .reactions()
+        .iter()
+        .filter(|(_, senders)| !senders.is_empty())
+        .map(|(key, senders)| ReactionGroup {
+            key: key.clone(),
+            count: senders.len(),
+            senders: senders.keys().map(|u| u.to_string()).collect(),
+            reacted_by_me: senders.contains_key(own_user_id),
+        })
+        .collect()
+}
 
-    apply_continuation_markers(&mut items);
+/// The parts of an event's `unsigned` object the SDK timeline doesn't expose.
+#[derive(Deserialize)]
+struct Unsigned {
+    redacted_because: Option<RedactedBecause>,
+    #[serde(rename = "m.relations")]
+    relations: Option<BundledRelations>,
+}
+
+#[derive(Deserialize)]
+struct BundledRelations {
+    #[serde(rename = "m.thread")]
+    thread: Option<BundledThread>,
+}
+
+#[derive(Deserialize)]
+struct BundledThread {
+    count: u64,
+}
 
-    Ok((items, messages.end))
+fn unsigned(event: &EventTimelineItem) -> Option<Unsigned> {
+    event
+        .original_json()
+        .and_then(|raw| raw.get_field::<Unsigned>("unsigned").ok().flatten())
+}
+
+#[derive(Deserialize)]
+struct RedactedBecause {
+    sender: String,
+    #[serde(default)]
+    content: RedactionContent,
+}
+
+#[derive(Default, Deserialize)]
+struct RedactionContent {
+    reason: Option<String>,
+}
+
+/// Redactor and reason from the redacted event's JSON, when the server included them.
+fn redaction_info(event: &EventTimelineItem) -> RedactionInfo {
+    let because = unsigned(event).and_then(|unsigned| unsigned.redacted_because);
+    match because {
+        Some(because) => RedactionInfo {
+            redacted_by: Some(because.sender),
+            reason: because.content.reason,
+        },
+        None => RedactionInfo::default(),
+    }
+}
+
+/// Returns (body, is_emote, image, formatted body) for a message type.
+fn describe_msgtype(
+    msgtype: &MessageType,
+) -> (String, bool, Option<ImageContent>, Option<Vec<RichBlock>>) {
+    match msgtype {
+        MessageType::Text(text) => (text.body.clone(), false, None, parse_formatted(&text.formatted)),
+        MessageType::Emote(emote) => {
+            (emote.body.clone(), true, None, parse_formatted(&emote.formatted))
+        }
+        MessageType::Notice(notice) => {
+            (notice.body.clone(), false, None, parse_formatted(&notice.formatted))
+        }
+        MessageType::Image(img) => (
+            img.body.clone(),
+            false,
+            Some(ImageContent {
+                source: img.source.clone(),
+            }),
+            None,
+        ),
+        MessageType::File(_) => ("[File]".to_string(), false, None, None),
+        MessageType::Audio(_) => ("[Audio]".to_string(), false, None, None),
+        MessageType::Video(_) => ("[Video]".to_string(), false, None, None),
+        _ => ("[Unsupported message type]".to_string(), false, None, None),
+    }
+}
+
+/// Rich text for an HTML `formatted_body`; `None` means render the plain body.
+fn parse_formatted(formatted: &Option<FormattedBody>) -> Option<Vec<RichBlock>> {
+    let formatted = formatted.as_ref()?;
+    if formatted.format != MessageFormat::Html {
+        return None;
+    }
+    let blocks = html::parse_formatted_body(&formatted.body);
+    (!blocks.is_empty()).then_some(blocks)
 }
 
 /// Fetch all joined members and return (display_name_map, avatar_url_map).
@@ -71,6 +484,23 @@ pub async fn build_member_info(room: &Room) -> (HashMap<String, String>, HashMap
     (display_names, avatar_urls)
 }
 
+/// Joined members for `@` completion in the composer, sorted by display name.
+pub async fn load_members(room: &Room) -> Message {
+    let (display_names, avatar_urls) = build_member_info(room).await;
+    let mut members: Vec<RoomMember> = display_names
+        .into_iter()
+        .filter_map(|(user_id, display_name)| {
+            Some(RoomMember {
+                avatar_url: avatar_urls.get(&user_id).cloned().flatten(),
+                user_id: user_id.parse().ok()?,
+                display_name,
+            })
+        })
+        .collect();
+    members.sort_by_key(|m| m.display_name.to_lowercase());
+    Message::MembersLoaded(room.room_id().to_owned(), members)
+}
+
 /// Kept for call sites that only need display names (e.g. history loading).
 pub async fn build_display_names(room: &Room) -> HashMap<String, String> {
     build_member_info(room).await.0
@@ -111,6 +541,10 @@ pub fn convert_message_event(
     match event {
         AnySyncMessageLikeEvent::RoomMessage(msg) => {
             let original = msg.as_original()?;
+            // Edits are folded into the original by the SDK timeline; don't treat them as new messages.
+            if matches!(original.content.relates_to, Some(Relation::Replacement(_))) {
+                return None;
+            }
             let sender = original.sender.to_string();
             let sender_display = display_names
                 .get(&sender)
@@ -126,23 +560,8 @@ pub fn convert_message_event(
                 chrono::DateTime::from_timestamp_millis(ts_millis).unwrap_or_default();
             let time_str = datetime.format("%H:%M").to_string();
 
-            let mut image_content: Option<ImageContent> = None;
-
-            let (raw_body, is_emote) = match &original.content.msgtype {
-                MessageType::Text(text) => (text.body.clone(), false),
-                MessageType::Emote(emote) => (emote.body.clone(), true),
-                MessageType::Notice(notice) => (notice.body.clone(), false),
-                MessageType::Image(img) => {
-                    image_content = Some(ImageContent {
-                        source: img.source.clone(),
-                    });
-                    (img.body.clone(), false)
-                }
-                MessageType::File(_) => ("[File]".to_string(), false),
-                MessageType::Audio(_) => ("[Audio]".to_string(), false),
-                MessageType::Video(_) => ("[Video]".to_string(), false),
-                _ => ("[Unsupported message type]".to_string(), false),
-            };
+            let (raw_body, is_emote, image_content, formatted) =
+                describe_msgtype(&original.content.msgtype);
 
             let (reply_ctx, body) = strip_reply_fallback(&raw_body);
             let (reply_to_sender, reply_to_body) = match reply_ctx {
@@ -159,11 +578,22 @@ pub fn convert_message_event(
                 body,
                 timestamp: time_str,
                 is_emote,
+                is_notice: matches!(original.content.msgtype, MessageType::Notice(_)),
                 is_continuation: false,
                 reply_to_sender,
                 reply_to_body,
                 image: image_content,
                 sender_avatar_url,
+                is_local_echo: false,
+                is_edited: false,
+                can_edit: false,
+                is_own: false,
+                redaction: None,
+                reactions: Vec::new(),
+                thread_root: None,
+                thread_reply_count: 0,
+                formatted,
+                read_by: Vec::new(),
             }))
         }
         AnySyncMessageLikeEvent::RoomEncrypted(_) => {
@@ -174,17 +604,334 @@ pub fn convert_message_event(
                 body: "[Unable to decrypt]".to_string(),
                 timestamp: String::new(),
                 is_emote: false,
+                is_notice: false,
                 is_continuation: false,
                 reply_to_sender: None,
                 reply_to_body: None,
                 image: None,
                 sender_avatar_url: None,
+                is_local_echo: false,
+                is_edited: false,
+                can_edit: false,
+                is_own: false,
+                redaction: None,
+                reactions: Vec::new(),
+                thread_root: None,
+                thread_reply_count: 0,
+                formatted: None,
+                read_by: Vec::new(),
             }))
         }
         _ => None,
     }
 }
 
+/// Whether our power level lets us redact our own and other users' events.
+pub async fn load_redact_permissions(room: &Room) -> Message {
+    let (own, other) = match room.client().user_id() {
+        Some(user_id) => (
+            room.can_user_redact_own(user_id).await.unwrap_or(false),
+            room.can_user_redact_other(user_id).await.unwrap_or(false),
+        ),
+        None => (false, false),
+    };
+    Message::RedactPermissionsLoaded {
+        room_id: room.room_id().to_owned(),
+        own,
+        other,
+    }
+}
+
+/// Fetch all replies in a thread, oldest first. Replies are fetched together with their
+/// own relations, so edits and reactions are applied even to replies the live timeline
+/// doesn't hold.
+pub async fn fetch_thread_replies(
+    room: &Room,
+    root: &EventId,
+    own_user_id: &UserId,
+) -> Result<Vec<TimelineItem>, String> {
+    use matrix_sdk::ruma::events::relation::Replacement;
+
+    let (display_names, avatar_urls) = build_member_info(room).await;
+    let mut events = Vec::new();
+    let mut from = None;
+    loop {
+        let relations = room
+            .relations(
+                root.to_owned(),
+                RelationsOptions {
+                    from,
+                    include_relations: IncludeRelations::AllRelations,
+                    recurse: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to fetch thread: {e}"))?;
+        for event in &relations.chunk {
+            if let Some(AnySyncTimelineEvent::MessageLike(event)) =
+                deserialize_related_event(room, event.raw()).await
+            {
+                events.push(event);
+            }
+        }
+        match relations.next_batch_token {
+            Some(token) => from = Some(token),
+            None => break,
+        }
+    }
+    // The relations API returns newest first.
+    events.reverse();
+
+    let mut replies = Vec::new();
+    // Latest replacement per edited event and sender, so edits by others can't hide
+    // the author's own
+    let mut edits: HashMap<(OwnedEventId, OwnedUserId), MessageType> = HashMap::new();
+    // Reaction senders per event and key, in the order the keys were first used
+    let mut reactions: HashMap<OwnedEventId, Vec<(String, Vec<String>)>> = HashMap::new();
+    for event in &events {
+        match event {
+            AnySyncMessageLikeEvent::RoomMessage(SyncMessageLikeEvent::Original(msg)) => {
+                match msg.content.relates_to {
+                    Some(Relation::Replacement(Replacement {
+                        ref event_id,
+                        ref new_content,
+                        ..
+                    })) => {
+                        edits.insert(
+                            (event_id.clone(), msg.sender.clone()),
+                            new_content.msgtype.clone(),
+                        );
+                    }
+                    Some(Relation::Thread(ref thread)) if *thread.event_id == *root => {
+                        replies.extend(convert_message_event(event, &display_names, &avatar_urls));
+                    }
+                    _ => {}
+                }
+            }
+            AnySyncMessageLikeEvent::Reaction(SyncMessageLikeEvent::Original(reaction)) => {
+                let annotation = &reaction.content.relates_to;
+                let groups = reactions.entry(annotation.event_id.clone()).or_default();
+                let sender = reaction.sender.to_string();
+                match groups.iter_mut().find(|(key, _)| *key == annotation.key) {
+                    Some((_, senders)) if senders.contains(&sender) => {}
+                    Some((_, senders)) => senders.push(sender),
+                    None => groups.push((annotation.key.clone(), vec![sender])),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for reply in &mut replies {
+        let TimelineItem::Message(ref mut message) = reply else {
+            continue;
+        };
+        message.thread_root = Some(root.to_string());
+        message.is_own = message.sender == own_user_id.as_str();
+        message.can_edit = message.is_own && message.image.is_none();
+        let (Ok(event_id), Ok(sender)) = (
+            OwnedEventId::try_from(message.event_id.as_str()),
+            OwnedUserId::try_from(message.sender.as_str()),
+        ) else {
+            continue;
+        };
+        // Only the original sender can edit a message
+        if let Some(msgtype) = edits.remove(&(event_id.clone(), sender)) {
+            let (body, is_emote, image, formatted) = describe_msgtype(&msgtype);
+            message.body = body;
+            message.is_emote = is_emote;
+            message.is_notice = matches!(msgtype, MessageType::Notice(_));
+            message.image = image;
+            message.formatted = formatted;
+            message.is_edited = true;
+        }
+        if let Some(groups) = reactions.remove(&event_id) {
+            message.reactions = groups
+                .into_iter()
+                .map(|(key, senders)| ReactionGroup {
+                    key,
+                    count: senders.len(),
+                    reacted_by_me: senders.iter().any(|s| s == own_user_id.as_str()),
+                    senders,
+                })
+                .collect();
+        }
+    }
+    Ok(replies)
+}
+
+/// Add or remove our `key` reaction on an event that isn't in the live timeline,
+/// such as an older thread reply.
+pub async fn toggle_reaction_on_event(
+    room: &Room,
+    event_id: &EventId,
+    key: &str,
+) -> Result<(), String> {
+    use matrix_sdk::ruma::events::reaction::ReactionEventContent;
+    use matrix_sdk::ruma::events::relation::Annotation;
+
+    let own_user_id = room.own_user_id();
+    let mut from = None;
+    loop {
+        let relations = room
+            .relations(
+                event_id.to_owned(),
+                RelationsOptions {
+                    from,
+                    include_relations: IncludeRelations::RelationsOfType(RelationType::Annotation),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to fetch reactions: {e}"))?;
+        for event in &relations.chunk {
+            // Our reactions in encrypted rooms come back as `m.room.encrypted`.
+            let Some(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::Reaction(
+                SyncMessageLikeEvent::Original(reaction),
+            ))) = deserialize_related_event(room, event.raw()).await
+            else {
+                continue;
+            };
+            if *reaction.sender == *own_user_id && reaction.content.relates_to.key == key {
+                room.redact(&reaction.event_id, None, None)
+                    .await
+                    .map_err(|e| format!("Failed to remove reaction: {e}"))?;
+                return Ok(());
+            }
+        }
+        match relations.next_batch_token {
+            Some(token) => from = Some(token),
+            None => break,
+        }
+    }
+
+    let content = ReactionEventContent::new(Annotation::new(event_id.to_owned(), key.to_string()));
+    room.send(content)
+        .await
+        .map_err(|e| format!("Failed to react: {e}"))?;
+    Ok(())
+}
+
+/// Send an `m.replace` edit of an event that isn't in the live timeline, such as an
+/// older thread reply.
+pub async fn edit_event(
+    room: &Room,
+    event_id: &EventId,
+    msgtype: MessageType,
+    mentions: Mentions,
+) -> Result<(), String> {
+    use matrix_sdk::ruma::events::relation::Replacement;
+    use matrix_sdk::ruma::events::room::message::{
+        RoomMessageEventContent, RoomMessageEventContentWithoutRelation,
+    };
+
+    let new_content = RoomMessageEventContentWithoutRelation::new(msgtype.clone())
+        .add_mentions(mentions);
+    let mut content = RoomMessageEventContent::new(msgtype);
+    content.relates_to = Some(Relation::Replacement(Replacement::new(
+        event_id.to_owned(),
+        new_content,
+    )));
+    room.send(content)
+        .await
+        .map_err(|e| format!("Failed to edit: {e}"))?;
+    Ok(())
+}
+
+/// Fetch every version of a message, oldest first: the original (from the open timeline)
+/// followed by each `m.replace` edit by `sender` found via the relations API.
+pub async fn fetch_edit_history(
+    room: &Room,
+    timeline: &Timeline,
+    event_id: &EventId,
+    sender: &UserId,
+) -> Result<Vec<EditHistoryEntry>, String> {
+    let mut entries = Vec::new();
+
+    if let Some(item) = timeline.item_by_event_id(event_id).await {
+        if let Some(raw) = item.original_json() {
+            entries.extend(edit_history_entry(room, raw).await.map(|(ts, _, e)| (ts, e)));
+        }
+    }
+
+    let mut from = None;
+    loop {
+        let relations = room
+            .relations(
+                event_id.to_owned(),
+                RelationsOptions {
+                    from,
+                    include_relations: IncludeRelations::RelationsOfType(RelationType::Replacement),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to fetch edits: {e}"))?;
+        for event in &relations.chunk {
+            // Anyone can send an `m.replace`; only the author's count as edits.
+            if let Some((ts, edit_sender, entry)) = edit_history_entry(room, event.raw()).await {
+                if *edit_sender == *sender {
+                    entries.push((ts, entry));
+                }
+            }
+        }
+        match relations.next_batch_token {
+            Some(token) => from = Some(token),
+            None => break,
+        }
+    }
+
+    entries.sort_by_key(|(ts, _)| *ts);
+    Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+}
+
+/// Deserialize an event returned by the relations API, decrypting it if needed.
+async fn deserialize_related_event(
+    room: &Room,
+    raw: &Raw<AnySyncTimelineEvent>,
+) -> Option<AnySyncTimelineEvent> {
+    match raw.deserialize().ok()? {
+        AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(_)) => {
+            let decrypted = room
+                .decrypt_event(raw.cast_ref::<OriginalSyncRoomEncryptedEvent>())
+                .await
+                .ok()?;
+            decrypted.raw().deserialize_as::<AnySyncTimelineEvent>().ok()
+        }
+        event => Some(event),
+    }
+}
+
+/// Timestamp, sender and body of one version of a message.
+async fn edit_history_entry(
+    room: &Room,
+    raw: &Raw<AnySyncTimelineEvent>,
+) -> Option<(i64, OwnedUserId, EditHistoryEntry)> {
+    let event = deserialize_related_event(room, raw).await?;
+    let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncMessageLikeEvent::Original(message),
+    )) = event
+    else {
+        return None;
+    };
+
+    let body = match message.content.relates_to {
+        Some(Relation::Replacement(replacement)) => replacement.new_content.msgtype.body().to_owned(),
+        _ => strip_reply_fallback(message.content.body()).1,
+    };
+    let ts_millis: i64 = message.origin_server_ts.0.into();
+    let datetime = chrono::DateTime::from_timestamp_millis(ts_millis).unwrap_or_default();
+    Some((
+        ts_millis,
+        message.sender,
+        EditHistoryEntry {
+            body,
+            timestamp: datetime.format("%b %d, %H:%M").to_string(),
+        },
+    ))
+}
+
 pub fn ts_to_naive_date(ts_millis: i64) -> Option<chrono::NaiveDate> {
     chrono::DateTime::from_timestamp_millis(ts_millis).map(|dt| dt.date_naive())
 }
@@ -201,7 +948,7 @@ pub fn format_date_label(date: chrono::NaiveDate) -> String {
 }
 
 /// Set `is_continuation = true` on consecutive messages from the same sender.
-/// A DateSeparator or StateEvent resets the grouping.
+/// A DateSeparator or StateEvent resets the grouping; hidden items are skipped.
 pub fn apply_continuation_markers(items: &mut Vec<TimelineItem>) {
     let mut last_sender: Option<String> = None;
     for item in items.iter_mut() {
@@ -210,6 +957,7 @@ pub fn apply_continuation_markers(items: &mut Vec<TimelineItem>) {
                 msg.is_continuation = last_sender.as_deref() == Some(&msg.sender);
                 last_sender = Some(msg.sender.clone());
             }
+            TimelineItem::Hidden { .. } => {}
             _ => {
                 last_sender = None;
             }
@@ -217,55 +965,6 @@ pub fn apply_continuation_markers(items: &mut Vec<TimelineItem>) {
     }
 }
 
-/// Remove consecutive DateSeparator items with the same label (dedup after prepend).
-pub fn dedup_adjacent_date_separators(items: &mut Vec<TimelineItem>) {
-    let mut i = 0;
-    while i + 1 < items.len() {
-        let is_dup = matches!(
-            (&items[i], &items[i + 1]),
-            (TimelineItem::DateSeparator(a), TimelineItem::DateSeparator(b)) if a == b
-        );
-        if is_dup {
-            items.remove(i);
-        } else {
-            i += 1;
-        }
-    }
-}
-
-fn format_state_event(event: &ruma::events::AnySyncStateEvent) -> String {
-    use ruma::events::AnySyncStateEvent;
-    match event {
-        AnySyncStateEvent::RoomMember(ev) => {
-            if let Some(original) = ev.as_original() {
-                let user = original.state_key.to_string();
-                match original.content.membership {
-                    ruma::events::room::member::MembershipState::Join => {
-                        format!("{user} joined the room")
-                    }
-                    ruma::events::room::member::MembershipState::Leave => {
-                        format!("{user} left the room")
-                    }
-                    _ => String::new(),
-                }
-            } else {
-                String::new()
-            }
-        }
-        AnySyncStateEvent::RoomName(ev) => {
-            if let Some(original) = ev.as_original() {
-                format!("Room name changed to: {}", &original.content.name)
-            } else {
-                String::new()
-            }
-        }
-        AnySyncStateEvent::RoomTopic(ev) => {
-            if let Some(original) = ev.as_original() {
-                format!("Topic changed to: {}", original.content.topic)
-            } else {
-                String::new()
-            }
-        }
-        _ => String::new(),
-    }
-}
+struct TimelineSubscriptionMarker;
+struct TypingSubscriptionMarker;
+struct FullyReadSubscriptionMarker;
@@ -1,7 +1,13 @@
+use std::sync::Arc;
+
 use cosmic::iced::widget::scrollable::RelativeOffset;
+use cosmic::iced::widget::text_editor;
+use matrix_sdk::ruma::events::room::history_visibility::HistoryVisibility;
 use matrix_sdk::ruma::events::room::MediaSource;
-use matrix_sdk::ruma::{OwnedRoomId, OwnedUserId};
+use matrix_sdk::ruma::{OwnedRoomAliasId, OwnedRoomId, OwnedUserId};
 use matrix_sdk::Client;
+use matrix_sdk_ui::timeline::Timeline;
+use serde::{Deserialize, Serialize};
 
 use crate::config::SortMode;
 
@@ -15,6 +21,16 @@ impl std::fmt::Debug for MatrixClient {
     }
 }
 
+/// Wrapper for the SDK room timeline that implements Debug.
+#[derive(Clone)]
+pub struct MatrixTimeline(pub Arc<Timeline>);
+
+impl std::fmt::Debug for MatrixTimeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MatrixTimeline(..)")
+    }
+}
+
 // ---- Verification types ----
 
 #[derive(Clone, Debug)]
@@ -51,7 +67,7 @@ pub enum VerificationStateUpdate {
 
 // ---- Reply context ----
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ReplyContext {
     pub event_id: String,
     pub sender_id: String,
@@ -59,6 +75,51 @@ pub struct ReplyContext {
     pub body_preview: String,
 }
 
+// ---- Edit history ----
+
+/// One version of an edited message, oldest first in a history list.
+#[derive(Clone, Debug)]
+pub struct EditHistoryEntry {
+    pub body: String,
+    pub timestamp: String,
+}
+
+// ---- Composer completion ----
+
+/// Joined room member offered by `@` completion.
+#[derive(Clone, Debug)]
+pub struct RoomMember {
+    pub user_id: OwnedUserId,
+    pub display_name: String,
+    /// mxc:// URI string.
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PillTarget {
+    User(OwnedUserId),
+    /// `#alias:server`
+    Room(String),
+}
+
+/// Text inserted by a completion that is sent as a matrix.to link.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pill {
+    pub text: String,
+    pub target: PillTarget,
+    /// Byte range of `text` in the composer; set when the completion is inserted.
+    pub range: std::ops::Range<usize>,
+}
+
+/// One entry of the composer autocomplete popup.
+#[derive(Clone, Debug)]
+pub struct Completion {
+    pub label: String,
+    /// Replaces the word being typed.
+    pub replacement: String,
+    pub pill: Option<Pill>,
+}
+
 // ---- Core app messages ----
 
 #[derive(Clone, Debug)]
@@ -71,14 +132,37 @@ pub enum Message {
     UsernameChanged(String),
     PasswordChanged(String),
     TogglePasswordVisibility,
+    DiscoverLoginFlows,
+    LoginFlowsDiscovered(Result<LoginFlows, String>),
     LoginSubmit,
+    /// SSO login, optionally with a specific identity provider ID.
+    LoginSso(Option<String>),
+    LoginOidc,
     LoginResult(Result<(MatrixClient, LoginSuccess), String>),
-    SessionRestored(MatrixClient),
+    SessionRestored(Vec<RestoredAccount>),
+    /// The homeserver rejected our access token and it could not be refreshed.
+    SessionExpired { soft_logout: bool },
+    /// Result of signing in again to a soft-logged-out account, by account ID.
+    ReauthResult(String, Result<(), String>),
+    DismissAccountNotice,
     Logout,
 
+    // -- Accounts --
+    /// Message from an account's sync/session subscription, tagged with its account ID.
+    ForAccount(String, Box<Message>),
+    SwitchAccount(String),
+    AddAccount,
+    CancelAddAccount,
+
     // -- Sync --
     SyncStarted,
+    /// Full room list (v2 sync).
     RoomsUpdated(Vec<RoomEntry>),
+    /// Incremental room-list change (sliding sync).
+    RoomsChanged {
+        upserted: Vec<RoomEntry>,
+        removed: Vec<OwnedRoomId>,
+    },
     SyncError(String),
 
     // -- Room list --
@@ -90,14 +174,110 @@ pub enum Message {
     ToggleSection(String), // section key
 
     // -- Timeline --
-    TimelineUpdated(OwnedRoomId, Vec<TimelineItem>, Option<String>),
+    TimelineOpened(OwnedRoomId, MatrixTimeline),
+    TimelineOpenFailed(OwnedRoomId, String),
+    TimelineDiffs(OwnedRoomId, Vec<TimelineDiff>),
+    /// New events from sync; only used for desktop notifications.
     IncomingEvents(OwnedRoomId, Vec<TimelineItem>),
-    ComposerChanged(String),
+    ComposerAction(text_editor::Action),
     SendMessage,
+    /// Tab in the composer: complete a slash command name.
+    CompleteCommand,
+    /// Insert a mention, room or emoji completion into the composer.
+    ApplyCompletion(Completion),
+    MembersLoaded(OwnedRoomId, Vec<RoomMember>),
+    /// Other users currently typing in a room.
+    TypingUsers(OwnedRoomId, Vec<OwnedUserId>),
+    SetSendTypingNotifications(bool),
+    SetPrivateReadReceipts(bool),
+    /// Outcome of a slash command, shown under the composer.
+    CommandFinished(Result<String, String>),
+    DismissCommandFeedback,
+    RoomJoined(OwnedRoomId),
+    RoomLeft(OwnedRoomId),
+    /// Open or close a room's action menu in the room list.
+    ToggleRoomMenu(OwnedRoomId),
+    /// Ask before leaving a room; with `true` it is forgotten as well.
+    ConfirmLeave(OwnedRoomId, bool),
+    /// Why leaving the room may be permanent, if it is.
+    LeaveWarning(OwnedRoomId, Option<String>),
+    CancelLeave,
+    /// Leave (and maybe forget) the room being confirmed.
+    LeaveRoom,
+    LeaveFailed(OwnedRoomId, String),
+    AcceptInvite(OwnedRoomId),
+    /// Reject an invite; with `true` the inviter is also ignored.
+    DeclineInvite(OwnedRoomId, bool),
+    InviteFailed(OwnedRoomId, String),
+
+    // -- Join dialog --
+    ShowJoinDialog,
+    CloseJoinDialog,
+    JoinAddressChanged(String),
+    /// Join the room typed into the address field.
+    JoinSubmit,
+    /// Join a room from the public directory, via the directory's server.
+    JoinPublicRoom(OwnedRoomId),
+    JoinFailed(String),
+    DirectoryServerChanged(String),
+    DirectorySearchChanged(String),
+    /// Reload the directory from its first page.
+    DirectorySearch,
+    DirectoryLoadMore,
+    DirectoryLoaded(Result<DirectoryPage, String>),
+
+    // -- Room and DM creation --
+    ShowCreateRoomDialog,
+    CloseCreateRoomDialog,
+    CreateRoomNameChanged(String),
+    CreateRoomTopicChanged(String),
+    CreateRoomAliasChanged(String),
+    CreateRoomInviteesChanged(String),
+    SetCreateRoomPublic(bool),
+    SetCreateRoomEncrypted(bool),
+    CreateRoomSubmit,
+    CreateRoomFailed(String),
+    ShowNewDmDialog,
+    CloseNewDmDialog,
+    DmSearchChanged(String),
+    /// The search field has kept this value for `DM_SEARCH_DELAY`.
+    DmSearchDue(String),
+    /// User directory results for the given search term.
+    DmSearchResults(String, Result<Vec<UserProfile>, String>),
+    StartDm(OwnedUserId),
+    DmFailed(String),
+
+    // -- Room settings --
+    ShowRoomSettings,
+    CloseRoomSettings,
+    /// Settings of a room, sent when the panel opens and whenever sync changes them.
+    RoomSettingsLoaded(OwnedRoomId, RoomSettings),
+    /// Aliases pointing at a room in our homeserver's directory.
+    LocalAliasesLoaded(OwnedRoomId, Result<Vec<OwnedRoomAliasId>, String>),
+    RoomSettingsNameChanged(String),
+    RoomSettingsTopicChanged(String),
+    RoomSettingsNewAliasChanged(String),
+    /// Add the alias typed into the settings panel to the room directory.
+    AddRoomAlias,
+    UpdateRoomSetting(SettingsChange),
+    PickRoomAvatar,
+    RoomSettingSaved(OwnedRoomId, Result<String, String>),
+    DevtoolsLoaded(Result<String, String>),
+    CloseDevtools,
     MessageSent(OwnedRoomId),
     SendError(String),
     LoadMoreHistory,
-    HistoryLoaded(OwnedRoomId, Vec<TimelineItem>, Option<String>),
+    /// Back-pagination finished; bool is true once the start of the room is reached.
+    HistoryLoaded(OwnedRoomId, bool),
+    HistoryFailed(OwnedRoomId, String),
+    /// Periodic flush of batched writes, such as the fully-read marker.
+    FlushPending,
+    /// Tick while the composer preview lags behind the text.
+    RefreshPreview,
+    /// Our `m.fully_read` event in a room, if any.
+    FullyReadLoaded(OwnedRoomId, Option<String>),
+    /// Scroll to the "New messages" line, paginating back until it is loaded.
+    JumpToUnread,
     TimelineScrolled(RelativeOffset),
     ScrollToBottom,
 
@@ -105,6 +285,44 @@ pub enum Message {
     ReplyTo(ReplyContext),
     CancelReply,
 
+    // -- Edits --
+    /// Load one of our messages into the composer for editing.
+    StartEdit { event_id: String, body: String },
+    /// Up-arrow in an empty composer: edit our latest message.
+    EditLastMessage,
+    CancelEdit,
+    ShowEditHistory(String), // event_id
+    EditHistoryLoaded(String, Result<Vec<EditHistoryEntry>, String>),
+    CloseEditHistory,
+
+    // -- Redactions --
+    /// Ask for confirmation (and an optional reason) before deleting a message.
+    RedactMessage(String), // event_id
+    RedactionReasonChanged(String),
+    ConfirmRedaction,
+    CancelRedaction,
+    MessageRedacted,
+    RedactionFailed(OwnedRoomId, String),
+    /// Whether we may redact our own / other users' events in the room.
+    RedactPermissionsLoaded { room_id: OwnedRoomId, own: bool, other: bool },
+
+    // -- Reactions --
+    /// Add our reaction with `key`, or remove it if we already reacted.
+    ToggleReaction { event_id: String, key: String },
+    ReactionFailed(OwnedRoomId, String),
+    /// Show or hide the quick-reaction picker for a message.
+    ToggleReactionPicker(String), // event_id
+    /// Open a link from a formatted message in the browser.
+    OpenLink(String),
+    CopyToClipboard(String),
+
+    // -- Threads --
+    OpenThread(String), // root event_id
+    ThreadLoaded(String, Result<Vec<TimelineItem>, String>),
+    /// Refetch the open thread after changing a reply the live timeline doesn't hold.
+    ReloadThread,
+    CloseThread,
+
     // -- Attachments --
     PickAttachment,
     AttachmentSent(OwnedRoomId),
@@ -152,12 +370,39 @@ pub enum Message {
     CancelVerification,
 }
 
+/// Login methods offered by a homeserver.
+#[derive(Clone, Debug, Default)]
+pub struct LoginFlows {
+    pub password: bool,
+    pub sso: bool,
+    /// Empty when the homeserver offers SSO without naming providers.
+    pub identity_providers: Vec<IdentityProviderInfo>,
+    /// Set when the homeserver delegates auth to an OIDC provider (MSC3861).
+    pub oidc_issuer: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IdentityProviderInfo {
+    pub id: String,
+    pub name: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct LoginSuccess {
+    pub account_id: String,
+    pub homeserver: String,
     pub user_id: OwnedUserId,
     pub device_id: String,
 }
 
+/// An account whose stored session was restored at startup.
+#[derive(Clone, Debug)]
+pub struct RestoredAccount {
+    pub account_id: String,
+    pub homeserver: String,
+    pub client: MatrixClient,
+}
+
 #[derive(Clone, Debug)]
 pub struct RoomEntry {
     pub room_id: OwnedRoomId,
@@ -168,6 +413,8 @@ pub struct RoomEntry {
     pub mention_count: u64,
     pub is_encrypted: bool,
     pub topic: Option<String>,
+    /// Main `#alias:server`, if the room has one.
+    pub canonical_alias: Option<String>,
     pub last_message: Option<String>,
     pub last_message_ts: Option<u64>,
     pub avatar_letter: char,
@@ -179,14 +426,210 @@ pub struct RoomEntry {
     pub is_dm: bool,
     /// mxc:// URI for the room avatar, if set.
     pub avatar_url: Option<String>,
+    /// We are invited to the room but have not joined it.
+    pub is_invite: bool,
+    /// Who sent the invite, when known.
+    pub inviter: Option<Inviter>,
+}
+
+/// A room listed in a server's public room directory.
+#[derive(Clone, Debug)]
+pub struct PublicRoom {
+    pub room_id: OwnedRoomId,
+    pub name: Option<String>,
+    pub topic: Option<String>,
+    pub canonical_alias: Option<String>,
+    pub member_count: u64,
+    /// mxc:// URI for the room avatar, if set.
+    pub avatar_url: Option<String>,
+}
+
+impl PublicRoom {
+    pub fn display_name(&self) -> &str {
+        self.name
+            .as_deref()
+            .or(self.canonical_alias.as_deref())
+            .unwrap_or(self.room_id.as_str())
+    }
+}
+
+/// One page of public room directory results.
+#[derive(Clone, Debug)]
+pub struct DirectoryPage {
+    /// Server and search term the page was requested for.
+    pub server: String,
+    pub search: String,
+    /// Pagination token the page was requested with; `None` for the first page.
+    pub since: Option<String>,
+    pub rooms: Vec<PublicRoom>,
+    pub next_batch: Option<String>,
+}
+
+/// A user found in the homeserver's user directory.
+#[derive(Clone, Debug)]
+pub struct UserProfile {
+    pub user_id: OwnedUserId,
+    pub display_name: Option<String>,
+    /// mxc:// URI for the user's avatar, if set.
+    pub avatar_url: Option<String>,
+}
+
+/// Who may join a room, as offered in the room settings panel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoomAccess {
+    #[default]
+    Invite,
+    Knock,
+    Public,
+    /// Restricted to members of other rooms, or a rule we don't offer.
+    Other,
+}
+
+/// What our power level allows us to change in a room.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SettingsPermissions {
+    pub name: bool,
+    pub topic: bool,
+    pub avatar: bool,
+    pub aliases: bool,
+    pub join_rule: bool,
+    pub guest_access: bool,
+    pub history_visibility: bool,
+    pub encryption: bool,
+}
+
+/// A room's settings as currently synced.
+#[derive(Clone, Debug)]
+pub struct RoomSettings {
+    pub name: String,
+    pub topic: String,
+    /// mxc:// URI for the room avatar, if set.
+    pub avatar_url: Option<String>,
+    /// Main published address.
+    pub canonical_alias: Option<OwnedRoomAliasId>,
+    /// Other published addresses.
+    pub alt_aliases: Vec<OwnedRoomAliasId>,
+    pub access: RoomAccess,
+    pub guest_access: bool,
+    pub history_visibility: HistoryVisibility,
+    pub encrypted: bool,
+    pub permissions: SettingsPermissions,
+}
+
+/// A change made in the room settings panel.
+#[derive(Clone, Debug)]
+pub enum SettingsChange {
+    Name(String),
+    Topic(String),
+    RemoveAvatar,
+    /// New `m.room.canonical_alias` content.
+    PublishedAliases {
+        alias: Option<OwnedRoomAliasId>,
+        alt_aliases: Vec<OwnedRoomAliasId>,
+    },
+    Access(RoomAccess),
+    GuestAccess(bool),
+    HistoryVisibility(HistoryVisibility),
+    EnableEncryption,
+    /// Create an alias in our homeserver's room directory.
+    AddLocalAlias(OwnedRoomAliasId),
+    RemoveLocalAlias(OwnedRoomAliasId),
+}
+
+/// Sender of a pending room invite.
+#[derive(Clone, Debug)]
+pub struct Inviter {
+    pub user_id: OwnedUserId,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl Inviter {
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(self.user_id.as_str())
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum TimelineItem {
     Message(TimelineMessage),
     DateSeparator(String),
-    StateEvent(String),
+    StateEvent {
+        event_id: Option<String>,
+        description: String,
+    },
     UnreadMarker,
+    /// SDK item we don't render; keeps indices aligned with the SDK timeline.
+    Hidden { event_id: Option<String> },
+}
+
+impl TimelineMessage {
+    /// Composer text for editing this message. Emotes and notices get their command
+    /// back so the edit keeps the message type; a leading `/` is escaped.
+    pub fn edit_text(&self) -> String {
+        if self.is_emote {
+            format!("/me {}", self.body)
+        } else if self.is_notice {
+            format!("/notice {}", self.body)
+        } else if self.body.starts_with('/') {
+            format!("/{}", self.body)
+        } else {
+            self.body.clone()
+        }
+    }
+}
+
+impl TimelineItem {
+    /// ID of the event behind this item, for events that have one.
+    pub fn event_id(&self) -> Option<&str> {
+        match self {
+            TimelineItem::Message(m) if !m.event_id.is_empty() => Some(&m.event_id),
+            TimelineItem::StateEvent { event_id, .. } | TimelineItem::Hidden { event_id } => {
+                event_id.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the item gets a row in the main timeline. Thread replies live in the
+    /// thread panel instead.
+    pub fn is_rendered(&self) -> bool {
+        match self {
+            TimelineItem::Hidden { .. } => false,
+            TimelineItem::Message(m) => m.thread_root.is_none(),
+            _ => true,
+        }
+    }
+}
+
+/// Index-based change to the open room's timeline, mirroring the SDK's `VectorDiff`.
+#[derive(Clone, Debug)]
+pub enum TimelineDiff {
+    Append(Vec<TimelineItem>),
+    Clear,
+    PushFront(TimelineItem),
+    PushBack(TimelineItem),
+    PopFront,
+    PopBack,
+    Insert(usize, TimelineItem),
+    Set(usize, TimelineItem),
+    Remove(usize),
+    Truncate(usize),
+    Reset(Vec<TimelineItem>),
+}
+
+impl TimelineDiff {
+    /// Items carried by this diff (new or replaced values).
+    pub fn items(&self) -> &[TimelineItem] {
+        match self {
+            TimelineDiff::Append(items) | TimelineDiff::Reset(items) => items,
+            TimelineDiff::PushFront(item)
+            | TimelineDiff::PushBack(item)
+            | TimelineDiff::Insert(_, item)
+            | TimelineDiff::Set(_, item) => std::slice::from_ref(item),
+            _ => &[],
+        }
+    }
 }
 
 /// Metadata for an image message. The image bytes are fetched separately.
@@ -204,6 +647,8 @@ pub struct TimelineMessage {
     pub body: String,
     pub timestamp: String,
     pub is_emote: bool,
+    /// Sent as `m.notice`, e.g. by a bot or with `/notice`.
+    pub is_notice: bool,
     pub is_continuation: bool,
     pub reply_to_sender: Option<String>,
     pub reply_to_body: Option<String>,
@@ -211,4 +656,89 @@ pub struct TimelineMessage {
     pub image: Option<ImageContent>,
     /// mxc:// URI for the sender's avatar, if available.
     pub sender_avatar_url: Option<String>,
+    /// Not yet acknowledged by the server (no event_id yet).
+    pub is_local_echo: bool,
+    /// Has been replaced by an `m.replace` edit; `body` is the latest version.
+    pub is_edited: bool,
+    /// Our own message that we are allowed to edit.
+    pub can_edit: bool,
+    pub is_own: bool,
+    /// Set when the message has been deleted; `body` is then empty.
+    pub redaction: Option<RedactionInfo>,
+    pub reactions: Vec<ReactionGroup>,
+    /// Root event of the thread this message is a reply in.
+    pub thread_root: Option<String>,
+    /// Replies in the thread rooted at this message, as bundled by the server.
+    pub thread_reply_count: u64,
+    /// Parsed `formatted_body`; `body` is the plain-text fallback.
+    pub formatted: Option<Vec<RichBlock>>,
+    /// Other users whose latest read receipt is on this message.
+    pub read_by: Vec<String>,
+}
+
+/// Block-level element of a formatted message.
+#[derive(Clone, Debug)]
+pub enum RichBlock {
+    Paragraph(Vec<RichSpan>),
+    Heading(u8, Vec<RichSpan>),
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+        /// `code` split into lines of highlighted tokens.
+        lines: Vec<Vec<CodeToken>>,
+    },
+    Quote(Vec<RichBlock>),
+    List {
+        ordered: bool,
+        start: u64,
+        items: Vec<Vec<RichBlock>>,
+    },
+    Rule,
+}
+
+/// Run of text with uniform inline formatting.
+#[derive(Clone, Debug, Default)]
+pub struct RichSpan {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub strike: bool,
+    pub code: bool,
+    pub link: Option<String>,
+}
+
+/// Run of code with a single highlighting class.
+#[derive(Clone, Debug)]
+pub struct CodeToken {
+    pub text: String,
+    pub kind: TokenKind,
+}
+
+/// Syntax class of a code token; mapped to a colour when drawn so it follows the theme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Constant,
+    Function,
+    Type,
+}
+
+/// All reactions with the same key on one message.
+#[derive(Clone, Debug)]
+pub struct ReactionGroup {
+    pub key: String,
+    pub count: usize,
+    /// MXIDs of the reacting users.
+    pub senders: Vec<String>,
+    pub reacted_by_me: bool,
+}
+
+/// Who deleted a message, and why.
+#[derive(Clone, Debug, Default)]
+pub struct RedactionInfo {
+    pub redacted_by: Option<String>,
+    pub reason: Option<String>,
 }
@@ -0,0 +1,201 @@
+//! Session secrets kept in the freedesktop Secret Service rather than on disk.
+//! One keyring item per account (service = app ID, user = local account ID) holding a
+//! JSON blob.
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::config::APP_ID;
+
+const PASSPHRASE_LEN: usize = 32;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SessionSecrets {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// Passphrase for the SQLite store. `None` for stores created before secrets
+    /// moved to the keyring, which are unencrypted.
+    #[serde(default)]
+    pub store_passphrase: Option<String>,
+}
+
+/// Backend holding one JSON secrets blob per key.
+trait SecretStore {
+    fn get(&self, key: &str) -> Result<Option<String>, String>;
+    fn set(&self, key: &str, json: &str) -> Result<(), String>;
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// The Secret Service, via `keyring`.
+struct Keyring;
+
+impl Keyring {
+    fn entry(key: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(APP_ID, key).map_err(|e| format!("Keyring unavailable: {e}"))
+    }
+}
+
+impl SecretStore for Keyring {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        match Self::entry(key)?.get_password() {
+            Ok(json) => Ok(Some(json)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read secrets from keyring: {e}")),
+        }
+    }
+
+    fn set(&self, key: &str, json: &str) -> Result<(), String> {
+        Self::entry(key)?
+            .set_password(json)
+            .map_err(|e| format!("Failed to store secrets in keyring: {e}"))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        match Self::entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to delete keyring item: {e}")),
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn with_store<R>(f: impl FnOnce(&dyn SecretStore) -> R) -> R {
+    f(&Keyring)
+}
+
+/// Tests never touch the real keyring; each test thread gets its own map.
+#[cfg(test)]
+fn with_store<R>(f: impl FnOnce(&dyn SecretStore) -> R) -> R {
+    thread_local! {
+        static MEMORY: MemoryStore = MemoryStore::default();
+    }
+    MEMORY.with(|store| f(store))
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryStore(std::cell::RefCell<std::collections::HashMap<String, String>>);
+
+#[cfg(test)]
+impl SecretStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.0.borrow().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, json: &str) -> Result<(), String> {
+        self.0
+            .borrow_mut()
+            .insert(key.to_string(), json.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        self.0.borrow_mut().remove(key);
+        Ok(())
+    }
+}
+
+pub fn save_secrets(key: &str, secrets: &SessionSecrets) -> Result<(), String> {
+    let json = serde_json::to_string(secrets).map_err(|e| e.to_string())?;
+    with_store(|store| store.set(key, &json))
+}
+
+pub fn load_secrets(key: &str) -> Result<Option<SessionSecrets>, String> {
+    match with_store(|store| store.get(key))? {
+        Some(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| format!("Corrupt keyring item: {e}")),
+        None => Ok(None),
+    }
+}
+
+pub fn delete_secrets(key: &str) {
+    if let Err(e) = with_store(|store| store.delete(key)) {
+        tracing::warn!("{e}");
+    }
+}
+
+/// Random passphrase for a newly created SQLite store.
+pub fn generate_passphrase() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(PASSPHRASE_LEN)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> SessionSecrets {
+        SessionSecrets {
+            access_token: "syt_token".to_string(),
+            refresh_token: Some("refresh".to_string()),
+            store_passphrase: Some(generate_passphrase()),
+        }
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let saved = secrets();
+        save_secrets("@alice:example.org", &saved).unwrap();
+
+        let loaded = load_secrets("@alice:example.org").unwrap().unwrap();
+        assert_eq!(loaded.access_token, saved.access_token);
+        assert_eq!(loaded.refresh_token, saved.refresh_token);
+        assert_eq!(loaded.store_passphrase, saved.store_passphrase);
+    }
+
+    #[test]
+    fn load_missing_is_none() {
+        assert!(load_secrets("@nobody:example.org").unwrap().is_none());
+    }
+
+    #[test]
+    fn delete_removes_only_that_account() {
+        save_secrets("@alice:example.org", &secrets()).unwrap();
+        save_secrets("@bob:example.org", &secrets()).unwrap();
+
+        delete_secrets("@alice:example.org");
+        assert!(load_secrets("@alice:example.org").unwrap().is_none());
+        assert!(load_secrets("@bob:example.org").unwrap().is_some());
+
+        // Deleting again is not an error.
+        delete_secrets("@alice:example.org");
+    }
+
+    #[test]
+    fn corrupt_item_is_an_error() {
+        with_store(|store| store.set("@alice:example.org", "not json")).unwrap();
+        assert!(load_secrets("@alice:example.org").is_err());
+    }
+
+    /// Talks to a real Secret Service. `just test-keyring` runs it against a
+    /// throwaway gnome-keyring in a private D-Bus session.
+    #[test]
+    #[ignore = "needs a Secret Service; run with `just test-keyring`"]
+    fn keyring_round_trips() {
+        let key = format!("test-{}", generate_passphrase());
+        let store = Keyring;
+        assert_eq!(store.get(&key).unwrap(), None);
+
+        store.set(&key, "{}").unwrap();
+        assert_eq!(store.get(&key).unwrap().as_deref(), Some("{}"));
+
+        store.delete(&key).unwrap();
+        assert_eq!(store.get(&key).unwrap(), None);
+        // Deleting a missing item is not an error.
+        store.delete(&key).unwrap();
+    }
+
+    #[test]
+    fn passphrase_is_random_alphanumeric() {
+        let a = generate_passphrase();
+        assert_eq!(a.len(), PASSPHRASE_LEN);
+        assert!(a.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(a, generate_passphrase());
+    }
+}
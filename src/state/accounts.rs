@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use matrix_sdk::ruma::OwnedUserId;
+use matrix_sdk::Client;
+
+use crate::state::rooms::RoomsState;
+
+/// A signed-in account. The active account's room list lives on the app itself;
+/// the others keep theirs here so their unread counts stay current.
+pub struct Account {
+    pub account_id: String,
+    pub user_id: OwnedUserId,
+    pub homeserver: String,
+    pub client: Arc<Client>,
+    pub rooms_state: RoomsState,
+    /// The server soft-logged-out this device. Its sync is paused and its store kept
+    /// until the user signs in again.
+    pub needs_reauth: bool,
+}
+
+impl Account {
+    /// Short name for the account switcher and notifications.
+    pub fn label(&self) -> String {
+        self.user_id.localpart().to_string()
+    }
+}
@@ -0,0 +1,62 @@
+use matrix_sdk::ruma::OwnedUserId;
+
+use crate::message::UserProfile;
+
+/// "New room" dialog.
+pub struct CreateRoomState {
+    pub name: String,
+    pub topic: String,
+    /// Local part of the room's `#alias:server`; empty for none.
+    pub alias: String,
+    /// Listed in the room directory and joinable by anyone.
+    pub public: bool,
+    pub encrypted: bool,
+    /// User IDs to invite, separated by spaces or commas.
+    pub invitees: String,
+    pub creating: bool,
+    pub error: Option<String>,
+}
+
+impl Default for CreateRoomState {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            topic: String::new(),
+            alias: String::new(),
+            public: false,
+            encrypted: true,
+            invitees: String::new(),
+            creating: false,
+            error: None,
+        }
+    }
+}
+
+impl CreateRoomState {
+    /// Parsed invitees, or an error naming the first entry that is not a user ID.
+    pub fn invitee_ids(&self) -> Result<Vec<OwnedUserId>, String> {
+        self.invitees
+            .split([',', ' ', '\n'])
+            .filter(|s| !s.is_empty())
+            .map(|s| OwnedUserId::try_from(s).map_err(|_| format!("{s} is not a user ID")))
+            .collect()
+    }
+
+    /// Alias local part as typed, without a leading `#` or trailing `:server`.
+    pub fn alias_localpart(&self) -> Option<String> {
+        let alias = self.alias.trim().trim_start_matches('#');
+        let alias = alias.split(':').next().unwrap_or_default();
+        (!alias.is_empty()).then(|| alias.to_string())
+    }
+}
+
+/// "New DM" dialog: user directory search.
+#[derive(Default)]
+pub struct NewDmState {
+    pub query: String,
+    pub results: Vec<UserProfile>,
+    pub searching: bool,
+    /// Set while the DM is being looked up or created.
+    pub starting: bool,
+    pub error: Option<String>,
+}
@@ -0,0 +1,50 @@
+use crate::message::{DirectoryPage, PublicRoom};
+
+/// "Join room" dialog: a room address field and the public room directory browser.
+#[derive(Default)]
+pub struct JoinState {
+    /// `#alias:server`, `!room:server` or a matrix.to link.
+    pub address: String,
+    pub joining: bool,
+    pub error: Option<String>,
+    /// Directory server; empty for our own homeserver.
+    pub server: String,
+    pub search: String,
+    /// Server and search term `rooms` were requested for. Joins and further pages use
+    /// these, whatever is in the fields now.
+    pub listed_server: String,
+    pub listed_search: String,
+    pub rooms: Vec<PublicRoom>,
+    /// Token for the next directory page, if there is one.
+    pub next_batch: Option<String>,
+    pub loading: bool,
+    pub directory_error: Option<String>,
+}
+
+impl JoinState {
+    /// Start listing the directory for the server and search term in the fields.
+    pub fn start_search(&mut self) {
+        self.listed_server = self.server.trim().to_string();
+        self.listed_search = self.search.trim().to_string();
+        self.rooms.clear();
+        self.next_batch = None;
+        self.loading = true;
+        self.directory_error = None;
+    }
+
+    /// Take in a directory page, unless a newer search was started since it was
+    /// requested. Returns false for such stale pages.
+    pub fn apply_page(&mut self, page: DirectoryPage) -> bool {
+        if page.server != self.listed_server || page.search != self.listed_search {
+            return false;
+        }
+        if page.since.is_none() {
+            self.rooms.clear();
+        }
+        self.rooms.extend(page.rooms);
+        self.next_batch = page.next_batch;
+        self.loading = false;
+        self.directory_error = None;
+        true
+    }
+}
@@ -1,2 +1,6 @@
+pub mod accounts;
+pub mod create;
+pub mod join;
+pub mod room_settings;
 pub mod rooms;
 pub mod timeline;
@@ -0,0 +1,96 @@
+use matrix_sdk::ruma::{OwnedRoomAliasId, OwnedRoomId};
+
+use crate::message::{RoomSettings, SettingsChange};
+
+/// Room settings panel for the open room.
+pub struct RoomSettingsState {
+    pub room_id: OwnedRoomId,
+    /// Settings as last synced; `None` until loaded.
+    pub current: Option<RoomSettings>,
+    /// Name and topic being edited.
+    pub name: String,
+    pub topic: String,
+    /// Local address being added, as typed.
+    pub new_alias: String,
+    /// Aliases in our homeserver's directory.
+    pub local_aliases: Vec<OwnedRoomAliasId>,
+    pub local_aliases_error: Option<String>,
+    /// Changes sent but not yet confirmed.
+    pub saving: usize,
+    /// Result of the last change.
+    pub feedback: Option<Result<String, String>>,
+}
+
+impl RoomSettingsState {
+    pub fn new(room_id: OwnedRoomId) -> Self {
+        Self {
+            room_id,
+            current: None,
+            name: String::new(),
+            topic: String::new(),
+            new_alias: String::new(),
+            local_aliases: Vec::new(),
+            local_aliases_error: None,
+            saving: 0,
+            feedback: None,
+        }
+    }
+
+    /// Take in freshly synced settings. Name and topic fields follow along unless the
+    /// user has started editing them.
+    pub fn update(&mut self, settings: RoomSettings) {
+        let (old_name, old_topic) = match self.current {
+            Some(ref current) => (current.name.as_str(), current.topic.as_str()),
+            None => ("", ""),
+        };
+        if self.name == old_name {
+            self.name = settings.name.clone();
+        }
+        if self.topic == old_topic {
+            self.topic = settings.topic.clone();
+        }
+        self.current = Some(settings);
+    }
+
+    /// Published addresses with `alias` made the main one.
+    pub fn make_main_alias(&self, alias: &OwnedRoomAliasId) -> Option<SettingsChange> {
+        let current = self.current.as_ref()?;
+        let mut alt_aliases: Vec<OwnedRoomAliasId> =
+            current.alt_aliases.iter().filter(|a| *a != alias).cloned().collect();
+        if let Some(ref main) = current.canonical_alias {
+            if main != alias {
+                alt_aliases.insert(0, main.clone());
+            }
+        }
+        Some(SettingsChange::PublishedAliases {
+            alias: Some(alias.clone()),
+            alt_aliases,
+        })
+    }
+
+    /// Published addresses with `alias` added, or removed when `publish` is false.
+    /// The first published address becomes the main one.
+    pub fn publish_alias(
+        &self,
+        alias: &OwnedRoomAliasId,
+        publish: bool,
+    ) -> Option<SettingsChange> {
+        let current = self.current.as_ref()?;
+        let mut main = current.canonical_alias.clone();
+        let mut alt_aliases: Vec<OwnedRoomAliasId> =
+            current.alt_aliases.iter().filter(|a| *a != alias).cloned().collect();
+        if publish {
+            if main.is_none() {
+                main = Some(alias.clone());
+            } else if main.as_ref() != Some(alias) {
+                alt_aliases.push(alias.clone());
+            }
+        } else if main.as_ref() == Some(alias) {
+            main = None;
+        }
+        Some(SettingsChange::PublishedAliases {
+            alias: main,
+            alt_aliases,
+        })
+    }
+}
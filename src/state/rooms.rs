@@ -1,13 +1,27 @@
 use matrix_sdk::ruma::OwnedRoomId;
 
-use crate::config::SortMode;
+use crate::config::{Draft, SortMode};
 use crate::message::RoomEntry;
 
+pub const SECTION_INVITES: &str = "invites";
 pub const SECTION_FAVOURITES: &str = "favourites";
 pub const SECTION_DMS: &str = "dms";
 pub const SECTION_ROOMS: &str = "rooms";
 pub const SECTION_LOW_PRIORITY: &str = "low_priority";
 
+/// Leave/forget confirmation for a room.
+#[derive(Debug, Clone)]
+pub struct LeaveConfirm {
+    pub room_id: OwnedRoomId,
+    /// Also forget the room after leaving it.
+    pub forget: bool,
+    /// Why leaving may be permanent; `None` once checked and nothing was found.
+    pub warning: Option<String>,
+    pub checking: bool,
+    pub leaving: bool,
+    pub error: Option<String>,
+}
+
 /// A section of the room list.
 #[derive(Debug, Clone)]
 pub struct RoomSection {
@@ -24,6 +38,15 @@ pub struct RoomsState {
     pub sort_mode: SortMode,
     /// section key → collapsed
     pub sections_collapsed: std::collections::HashMap<String, bool>,
+    /// Unsent composer contents by room, persisted per account.
+    pub drafts: std::collections::HashMap<OwnedRoomId, Draft>,
+    /// Invite being accepted or declined.
+    pub invite_pending: Option<OwnedRoomId>,
+    /// Last failed invite action, shown in that invite's preview.
+    pub invite_error: Option<(OwnedRoomId, String)>,
+    /// Room whose action menu is open in the room list.
+    pub room_menu: Option<OwnedRoomId>,
+    pub leaving: Option<LeaveConfirm>,
 }
 
 impl Default for RoomsState {
@@ -34,15 +57,65 @@ impl Default for RoomsState {
             filter: String::new(),
             sort_mode: SortMode::default(),
             sections_collapsed: std::collections::HashMap::new(),
+            drafts: std::collections::HashMap::new(),
+            invite_pending: None,
+            invite_error: None,
+            room_menu: None,
+            leaving: None,
         }
     }
 }
 
 impl RoomsState {
+    /// Room-list state for an account, with its saved drafts.
+    pub fn for_account(account_id: &str) -> Self {
+        Self {
+            drafts: crate::config::load_drafts(account_id),
+            ..Self::default()
+        }
+    }
+
     pub fn update_rooms(&mut self, rooms: Vec<RoomEntry>) {
         self.rooms = rooms;
     }
 
+    /// Apply an incremental room-list change: replace or add `upserted`, drop `removed`.
+    pub fn apply_room_changes(&mut self, upserted: Vec<RoomEntry>, removed: &[OwnedRoomId]) {
+        self.rooms.retain(|r| !removed.contains(&r.room_id));
+        for entry in upserted {
+            match self.rooms.iter_mut().find(|r| r.room_id == entry.room_id) {
+                Some(existing) => *existing = entry,
+                None => self.rooms.push(entry),
+            }
+        }
+    }
+
+    /// Drop a room we left, with everything kept for it. Returns whether it had a draft.
+    pub fn remove_room(&mut self, room_id: &OwnedRoomId) -> bool {
+        self.rooms.retain(|r| &r.room_id != room_id);
+        if self.selected.as_ref() == Some(room_id) {
+            self.selected = None;
+        }
+        if self.room_menu.as_ref() == Some(room_id) {
+            self.room_menu = None;
+        }
+        if self.leaving.as_ref().is_some_and(|l| &l.room_id == room_id) {
+            self.leaving = None;
+        }
+        if self.invite_pending.as_ref() == Some(room_id) {
+            self.invite_pending = None;
+        }
+        if self.invite_error.as_ref().is_some_and(|(id, _)| id == room_id) {
+            self.invite_error = None;
+        }
+        self.drafts.remove(room_id).is_some()
+    }
+
+    /// Unread notification count across all rooms.
+    pub fn unread_total(&self) -> u64 {
+        self.rooms.iter().map(|r| r.unread_count).sum()
+    }
+
     pub fn is_section_collapsed(&self, key: &str) -> bool {
         self.sections_collapsed.get(key).copied().unwrap_or(false)
     }
@@ -61,6 +134,7 @@ impl RoomsState {
             Some(self.filter.to_lowercase())
         };
 
+        let mut invites: Vec<&RoomEntry> = Vec::new();
         let mut favs: Vec<&RoomEntry> = Vec::new();
         let mut dms: Vec<&RoomEntry> = Vec::new();
         let mut rooms: Vec<&RoomEntry> = Vec::new();
@@ -72,7 +146,9 @@ impl RoomsState {
                     continue;
                 }
             }
-            if room.is_favourite {
+            if room.is_invite {
+                invites.push(room);
+            } else if room.is_favourite {
                 favs.push(room);
             } else if room.is_low_priority {
                 low.push(room);
@@ -93,6 +169,7 @@ impl RoomsState {
             }
         };
 
+        invites.sort_by(sort_fn);
         favs.sort_by(sort_fn);
         dms.sort_by(sort_fn);
         rooms.sort_by(sort_fn);
@@ -108,6 +185,9 @@ impl RoomsState {
         };
 
         let mut sections = Vec::new();
+        if !invites.is_empty() {
+            sections.push(to_section(SECTION_INVITES, "Invites", invites));
+        }
         if !favs.is_empty() {
             sections.push(to_section(SECTION_FAVOURITES, "Favourites", favs));
         }
@@ -136,6 +216,14 @@ impl RoomsState {
         }
     }
 
+    /// The selected room, if it is a pending invite rather than a joined room.
+    pub fn selected_invite(&self) -> Option<&RoomEntry> {
+        let selected = self.selected.as_ref()?;
+        self.rooms
+            .iter()
+            .find(|r| &r.room_id == selected && r.is_invite)
+    }
+
     pub fn selected_room_name(&self) -> Option<&str> {
         self.selected.as_ref().and_then(|sel| {
             self.rooms
@@ -1,65 +1,513 @@
-use matrix_sdk::ruma::OwnedRoomId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::message::{ReplyContext, TimelineItem};
+use cosmic::iced::widget::text_editor;
+use matrix_sdk::ruma::{OwnedRoomId, OwnedUserId};
+use matrix_sdk_ui::timeline::Timeline;
+
+use crate::config::Draft;
+use crate::matrix::{markdown, mentions};
+use crate::message::{
+    Completion, EditHistoryEntry, Pill, ReplyContext, RichBlock, RoomEntry, RoomMember,
+    TimelineDiff, TimelineItem, TimelineMessage,
+};
+
+/// Pause in typing after which the composer preview is re-rendered.
+pub const PREVIEW_DELAY: Duration = Duration::from_millis(300);
+
+/// Edit history view for one message.
+pub struct EditHistory {
+    pub event_id: String,
+    pub entries: Vec<EditHistoryEntry>,
+    pub loading: bool,
+    pub error: Option<String>,
+}
+
+/// Room state explorer opened with `/devtools`.
+pub struct Devtools {
+    pub loading: bool,
+    pub text: String,
+    pub error: Option<String>,
+}
+
+/// Open thread panel.
+pub struct ThreadState {
+    pub root_event_id: String,
+    /// Replies fetched through the relations API.
+    pub fetched: Vec<TimelineItem>,
+    /// Root followed by the replies: fetched ones merged with live ones from the main timeline.
+    pub items: Vec<TimelineItem>,
+    pub loading: bool,
+}
 
 pub struct TimelineState {
     pub room_id: Option<OwnedRoomId>,
+    /// SDK timeline backing the open room; `items` mirrors it index-for-index.
+    pub timeline: Option<Arc<Timeline>>,
     pub items: Vec<TimelineItem>,
-    pub composer: String,
-    pub pagination_token: Option<String>,
+    pub composer: text_editor::Content,
+    /// Formatted rendering of the composer text; empty when it would be sent as plain text.
+    pub composer_preview: Vec<RichBlock>,
+    /// Last edit not yet reflected in `composer_preview`. The preview catches up once
+    /// typing pauses for `PREVIEW_DELAY`.
+    pub preview_stale_since: Option<Instant>,
+    /// Autocomplete entries for the word before the cursor, refreshed on composer
+    /// actions rather than on every render.
+    pub suggestions: Vec<Completion>,
+    /// Set once back-pagination has reached the start of the room.
+    pub at_timeline_start: bool,
     pub loading: bool,
     pub sending: bool,
     pub attachment_sending: bool,
     pub at_bottom: bool,
-    pub unread_marker_inserted: bool,
     pub reply_to: Option<ReplyContext>,
+    /// Event ID of the message being edited in the composer.
+    pub editing: Option<String>,
+    /// Composer contents from before the edit started; restored when it ends.
+    pub stashed_draft: Option<Draft>,
+    /// The draft changed since it was last written to disk.
+    pub draft_dirty: bool,
+    pub edit_history: Option<EditHistory>,
+    /// Message pending deletion: (event_id, reason being typed).
+    pub redacting: Option<(String, String)>,
+    /// Power levels allow redacting our own / others' events.
+    pub can_redact_own: bool,
+    pub can_redact_other: bool,
+    /// Message whose quick-reaction picker is open.
+    pub reaction_picker: Option<String>,
+    pub thread: Option<ThreadState>,
+    /// Result of the last slash command: confirmation or error.
+    pub command_feedback: Option<Result<String, String>>,
+    /// Joined members of the open room, for `@` completion.
+    pub members: Vec<RoomMember>,
+    /// Mentions and room links inserted into the composer by completion.
+    pub pills: Vec<Pill>,
+    /// Other users typing in the open room.
+    pub typing_users: Vec<OwnedUserId>,
+    /// Whether we told the room we are typing and haven't stopped since.
+    pub typing_sent: bool,
+    pub devtools: Option<Devtools>,
+    /// Our `m.fully_read` marker for the room, as synced between our clients.
+    pub fully_read: Option<String>,
+    /// Latest event seen at the bottom of the timeline; becomes the fully-read
+    /// marker when we leave the room.
+    pub seen_up_to: Option<String>,
+    /// Set while paginating back to reach the fully-read marker.
+    pub jumping_to_unread: bool,
+    /// History pages fetched by the current jump to the fully-read marker.
+    pub jump_pages: usize,
 }
 
 impl Default for TimelineState {
     fn default() -> Self {
         Self {
             room_id: None,
+            timeline: None,
             items: Vec::new(),
-            composer: String::new(),
-            pagination_token: None,
+            composer: text_editor::Content::new(),
+            composer_preview: Vec::new(),
+            preview_stale_since: None,
+            suggestions: Vec::new(),
+            at_timeline_start: false,
             loading: false,
             sending: false,
             attachment_sending: false,
             at_bottom: true,
-            unread_marker_inserted: false,
             reply_to: None,
+            editing: None,
+            stashed_draft: None,
+            draft_dirty: false,
+            edit_history: None,
+            redacting: None,
+            can_redact_own: false,
+            can_redact_other: false,
+            reaction_picker: None,
+            thread: None,
+            command_feedback: None,
+            devtools: None,
+            members: Vec::new(),
+            pills: Vec::new(),
+            typing_users: Vec::new(),
+            typing_sent: false,
+            fully_read: None,
+            seen_up_to: None,
+            jumping_to_unread: false,
+            jump_pages: 0,
         }
     }
 }
 
 impl TimelineState {
+    /// Whether the Delete action should be offered for a message.
+    pub fn can_redact(&self, msg: &TimelineMessage) -> bool {
+        if msg.event_id.is_empty() || msg.redaction.is_some() {
+            return false;
+        }
+        if msg.is_own {
+            self.can_redact_own
+        } else {
+            self.can_redact_other
+        }
+    }
+
+    /// Replace the composer text, with the cursor at the end, and refresh its preview.
+    pub fn set_composer(&mut self, text: String) {
+        self.composer_preview = markdown::preview(&text);
+        self.preview_stale_since = None;
+        self.suggestions.clear();
+        self.composer = text_editor::Content::with_text(&text);
+        self.composer
+            .perform(text_editor::Action::Move(text_editor::Motion::DocumentEnd));
+        self.pills.clear();
+    }
+
+    /// Replace the word before the cursor with a completion, remembering its pill.
+    pub fn apply_completion(&mut self, completion: Completion) {
+        let before = self.composer.text();
+        let cursor = self.composer_cursor();
+        let word = mentions::word_before(&before, cursor).unwrap_or(cursor..cursor);
+
+        // Select the word and paste over it, so the rest of the text and the cursor
+        // stay where they are.
+        for _ in before[word.clone()].chars() {
+            self.composer
+                .perform(text_editor::Action::Select(text_editor::Motion::Left));
+        }
+        let mut replacement = completion.replacement.clone();
+        if !before[cursor..].starts_with(' ') {
+            replacement.push(' ');
+        }
+        self.composer.perform(text_editor::Action::Edit(
+            text_editor::Edit::Paste(Arc::new(replacement)),
+        ));
+
+        let text = self.composer.text();
+        mentions::track_edit(&mut self.pills, &before, &text);
+        if let Some(mut pill) = completion.pill {
+            pill.range = word.start..word.start + pill.text.len();
+            self.pills.push(pill);
+        }
+        self.composer_preview = markdown::preview(&text);
+        self.preview_stale_since = None;
+        self.suggestions.clear();
+        self.draft_dirty = true;
+    }
+
+    /// Recompute the autocomplete entries for the word before the cursor.
+    pub fn refresh_suggestions(&mut self, rooms: &[RoomEntry]) {
+        self.suggestions = mentions::candidates(
+            &self.composer.text(),
+            self.composer_cursor(),
+            &self.members,
+            rooms,
+        );
+    }
+
+    /// Byte offset of the composer cursor in `composer.text()`.
+    pub fn composer_cursor(&self) -> usize {
+        let (line, column) = self.composer.cursor_position();
+        let text = self.composer.text();
+        let line_start: usize = text.split('\n').take(line).map(|l| l.len() + 1).sum();
+        let mut offset = (line_start + column).min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    /// Apply an editor action (typing, cursor movement, paste…) to the composer.
+    pub fn composer_action(&mut self, action: text_editor::Action) {
+        let is_edit = action.is_edit();
+        let before = (is_edit && !self.pills.is_empty()).then(|| self.composer.text());
+        self.composer.perform(action);
+        if is_edit {
+            let text = self.composer.text();
+            if let Some(before) = before {
+                mentions::track_edit(&mut self.pills, &before, &text);
+            }
+            self.preview_stale_since = Some(Instant::now());
+            self.draft_dirty = true;
+        }
+    }
+
+    /// Re-render the composer preview if typing has paused since the last edit.
+    pub fn refresh_preview_if_idle(&mut self) {
+        if self
+            .preview_stale_since
+            .is_some_and(|since| since.elapsed() >= PREVIEW_DELAY)
+        {
+            self.composer_preview = markdown::preview(&self.composer.text());
+            self.preview_stale_since = None;
+        }
+    }
+
+    pub fn composer_text(&self) -> String {
+        self.composer.text().trim().to_string()
+    }
+
+    /// The pills with their ranges moved into [`Self::composer_text`], which drops
+    /// leading whitespace.
+    pub fn composer_pills(&self) -> Vec<Pill> {
+        let text = self.composer.text();
+        let offset = text.len() - text.trim_start().len();
+        self.pills
+            .iter()
+            .filter(|p| p.range.start >= offset)
+            .map(|p| Pill {
+                range: p.range.start - offset..p.range.end - offset,
+                ..p.clone()
+            })
+            .collect()
+    }
+
+    /// What to keep for this room when leaving it. During an edit that is what the
+    /// composer held before the edit started.
+    pub fn draft(&self) -> Option<Draft> {
+        if self.editing.is_some() {
+            return self.stashed_draft.clone();
+        }
+        let text = self.composer.text().trim_end().to_string();
+        if text.is_empty() && self.reply_to.is_none() {
+            return None;
+        }
+        Some(Draft {
+            text,
+            reply_to: self.reply_to.clone(),
+            pills: self.pills.clone(),
+        })
+    }
+
+    pub fn restore_draft(&mut self, draft: Draft) {
+        self.set_composer(draft.text);
+        self.reply_to = draft.reply_to;
+        self.pills = draft.pills;
+    }
+
+    /// Load one of our messages into the composer for editing, setting aside what
+    /// was being typed.
+    pub fn start_edit(&mut self, event_id: String, text: String) {
+        if self.editing.is_none() {
+            self.stashed_draft = self.draft();
+        }
+        self.reply_to = None;
+        self.editing = Some(event_id);
+        self.set_composer(text);
+    }
+
+    /// Leave edit mode and bring back the composer contents from before the edit.
+    pub fn finish_edit(&mut self) {
+        self.editing = None;
+        match self.stashed_draft.take() {
+            Some(draft) => self.restore_draft(draft),
+            None => {
+                self.set_composer(String::new());
+                self.reply_to = None;
+            }
+        }
+    }
+
+    /// "Alice and Bob are typing…" for the typing line, using member display names.
+    pub fn typing_label(&self) -> Option<String> {
+        let names: Vec<&str> = self
+            .typing_users
+            .iter()
+            .map(|user_id| {
+                self.members
+                    .iter()
+                    .find(|m| &m.user_id == user_id)
+                    .map_or(user_id.as_str(), |m| m.display_name.as_str())
+            })
+            .collect();
+        match names.as_slice() {
+            [] => None,
+            [one] => Some(format!("{one} is typing…")),
+            [a, b] => Some(format!("{a} and {b} are typing…")),
+            [a, b, rest @ ..] => Some(format!("{a}, {b} and {} others are typing…", rest.len())),
+        }
+    }
+
+    /// Index of the "New messages" line, or of the fully-read event itself, once it
+    /// has been paginated in. The marker may point at any event, shown or not.
+    pub fn fully_read_index(&self) -> Option<usize> {
+        let event_id = self.fully_read.as_deref()?;
+        self.items.iter().position(|item| {
+            matches!(item, TimelineItem::UnreadMarker) || item.event_id() == Some(event_id)
+        })
+    }
+
+    /// The message with `event_id` in the main timeline or the open thread.
+    pub fn message(&self, event_id: &str) -> Option<&TimelineMessage> {
+        let thread_items = self.thread.iter().flat_map(|thread| &thread.items);
+        self.items.iter().chain(thread_items).find_map(|item| match item {
+            TimelineItem::Message(m) if m.event_id == event_id => Some(m),
+            _ => None,
+        })
+    }
+
+    /// Whether `event_id` comes after the fully-read marker in the loaded timeline. A
+    /// marker outside the loaded items is older than all of them.
+    pub fn is_after_fully_read(&self, event_id: &str) -> bool {
+        let position = |id: &str| self.items.iter().position(|i| i.event_id() == Some(id));
+        let Some(seen) = position(event_id) else {
+            return false;
+        };
+        match self.fully_read.as_deref() {
+            Some(marker) => position(marker).map_or(true, |marker| seen > marker),
+            None => true,
+        }
+    }
+
+    /// Row of the fully-read marker among the rendered timeline rows, with the number of
+    /// rows. A marker on a hidden event or thread reply maps to the next rendered row.
+    pub fn fully_read_row(&self) -> Option<(usize, usize)> {
+        let index = self.fully_read_index()?;
+        let row = self.items[..index].iter().filter(|i| i.is_rendered()).count();
+        let rows = self.items.iter().filter(|i| i.is_rendered()).count();
+        Some((row, rows))
+    }
+
+    /// Our most recent editable text message in the open thread, or else the main timeline.
+    pub fn last_editable_message(&self) -> Option<&TimelineMessage> {
+        let items = match self.thread {
+            Some(ref thread) => &thread.items,
+            None => &self.items,
+        };
+        items.iter().rev().find_map(|item| match item {
+            TimelineItem::Message(m)
+                if m.can_edit
+                    && !m.event_id.is_empty()
+                    && m.image.is_none()
+                    && (self.thread.is_some() || m.thread_root.is_none()) =>
+            {
+                Some(m)
+            }
+            _ => None,
+        })
+    }
+
     pub fn clear(&mut self) {
         self.room_id = None;
+        self.timeline = None;
         self.items.clear();
-        self.composer.clear();
-        self.pagination_token = None;
+        self.set_composer(String::new());
+        self.at_timeline_start = false;
         self.loading = false;
         self.sending = false;
         self.attachment_sending = false;
         self.at_bottom = true;
-        self.unread_marker_inserted = false;
         self.reply_to = None;
+        self.editing = None;
+        self.stashed_draft = None;
+        self.draft_dirty = false;
+        self.edit_history = None;
+        self.redacting = None;
+        self.can_redact_own = false;
+        self.can_redact_other = false;
+        self.reaction_picker = None;
+        self.thread = None;
+        self.command_feedback = None;
+        self.devtools = None;
+        self.members.clear();
+        self.typing_users.clear();
+        self.typing_sent = false;
+        self.fully_read = None;
+        self.seen_up_to = None;
+        self.jumping_to_unread = false;
+        self.jump_pages = 0;
     }
 
-    pub fn set_timeline(&mut self, room_id: OwnedRoomId, items: Vec<TimelineItem>, token: Option<String>) {
+    pub fn set_timeline(&mut self, room_id: OwnedRoomId, timeline: Arc<Timeline>) {
         self.room_id = Some(room_id);
-        self.items = items;
-        self.pagination_token = token;
-        self.loading = false;
+        self.timeline = Some(timeline);
+        self.items.clear();
+        self.at_timeline_start = false;
         self.at_bottom = true;
-        self.unread_marker_inserted = false;
-        self.reply_to = None;
     }
 
-    pub fn prepend_items(&mut self, mut items: Vec<TimelineItem>, token: Option<String>) {
-        items.append(&mut self.items);
-        self.items = items;
-        self.pagination_token = token;
-        self.loading = false;
+    /// Rebuild the open thread's items from its fetched replies and the live timeline.
+    /// Live versions win, since they carry reactions and edits.
+    pub fn refresh_thread(&mut self) {
+        let Some(ref mut thread) = self.thread else {
+            return;
+        };
+        let root = thread.root_event_id.as_str();
+        let live = |id: &str| {
+            self.items.iter().find(|item| {
+                matches!(item, TimelineItem::Message(m) if m.event_id == id)
+            })
+        };
+
+        let mut items: Vec<TimelineItem> = Vec::new();
+        if let Some(root_item) = live(root) {
+            items.push(root_item.clone());
+        }
+        for reply in &thread.fetched {
+            if let TimelineItem::Message(m) = reply {
+                items.push(live(&m.event_id).cloned().unwrap_or_else(|| reply.clone()));
+            }
+        }
+        for item in &self.items {
+            if let TimelineItem::Message(m) = item {
+                let in_thread = m.thread_root.as_deref() == Some(root);
+                let known = items.iter().any(|i| {
+                    matches!(i, TimelineItem::Message(x) if x.event_id == m.event_id && !m.event_id.is_empty())
+                });
+                if in_thread && !known {
+                    items.push(item.clone());
+                }
+            }
+        }
+        crate::matrix::timeline::apply_continuation_markers(&mut items);
+        thread.items = items;
+    }
+
+    /// Latest event in the open thread (the root if it has no replies yet).
+    pub fn thread_latest_event_id(&self) -> Option<String> {
+        let thread = self.thread.as_ref()?;
+        thread
+            .items
+            .iter()
+            .rev()
+            .find_map(|item| match item {
+                TimelineItem::Message(m) if !m.event_id.is_empty() => Some(m.event_id.clone()),
+                _ => None,
+            })
+            .or_else(|| Some(thread.root_event_id.clone()))
+    }
+
+    /// Apply a batch of diffs streamed from the SDK timeline.
+    pub fn apply_diffs(&mut self, diffs: Vec<TimelineDiff>) {
+        for diff in diffs {
+            match diff {
+                TimelineDiff::Append(mut items) => self.items.append(&mut items),
+                TimelineDiff::Clear => self.items.clear(),
+                TimelineDiff::PushFront(item) => self.items.insert(0, item),
+                TimelineDiff::PushBack(item) => self.items.push(item),
+                TimelineDiff::PopFront => {
+                    if !self.items.is_empty() {
+                        self.items.remove(0);
+                    }
+                }
+                TimelineDiff::PopBack => {
+                    self.items.pop();
+                }
+                TimelineDiff::Insert(index, item) => {
+                    let index = index.min(self.items.len());
+                    self.items.insert(index, item);
+                }
+                TimelineDiff::Set(index, item) => {
+                    if let Some(slot) = self.items.get_mut(index) {
+                        *slot = item;
+                    }
+                }
+                TimelineDiff::Remove(index) => {
+                    if index < self.items.len() {
+                        self.items.remove(index);
+                    }
+                }
+                TimelineDiff::Truncate(len) => self.items.truncate(len),
+                TimelineDiff::Reset(items) => self.items = items,
+            }
+        }
     }
 }
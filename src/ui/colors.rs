@@ -1,5 +1,7 @@
 use cosmic::iced::Color;
 
+use crate::message::TokenKind;
+
 pub fn sender_color(user_id: &str) -> Color {
     const PALETTE: [(f32, f32, f32); 8] = [
         (0.306, 0.663, 0.863), // sky blue
@@ -24,3 +26,25 @@ fn hash_user_id(user_id: &str) -> usize {
     }
     hash as usize
 }
+
+/// Colour for a highlighted code token, picked for the active light/dark theme.
+/// Plain tokens use the default text colour.
+pub fn code_token_color(kind: TokenKind) -> Option<Color> {
+    let dark = cosmic::theme::active().cosmic().is_dark;
+    let (r, g, b) = match (kind, dark) {
+        (TokenKind::Plain, _) => return None,
+        (TokenKind::Keyword, true) => (0.776, 0.573, 0.918),
+        (TokenKind::Keyword, false) => (0.537, 0.220, 0.741),
+        (TokenKind::String, true) => (0.596, 0.765, 0.475),
+        (TokenKind::String, false) => (0.243, 0.510, 0.180),
+        (TokenKind::Comment, true) => (0.498, 0.537, 0.588),
+        (TokenKind::Comment, false) => (0.447, 0.482, 0.522),
+        (TokenKind::Constant, true) => (0.820, 0.604, 0.400),
+        (TokenKind::Constant, false) => (0.694, 0.365, 0.090),
+        (TokenKind::Function, true) => (0.380, 0.686, 0.937),
+        (TokenKind::Function, false) => (0.153, 0.392, 0.722),
+        (TokenKind::Type, true) => (0.898, 0.753, 0.482),
+        (TokenKind::Type, false) => (0.588, 0.420, 0.000),
+    };
+    Some(Color::from_rgb(r, g, b))
+}
@@ -1,19 +1,76 @@
+use cosmic::iced::keyboard::key::Named;
+use cosmic::iced::keyboard::Key;
+use cosmic::iced::widget::text_editor::{Binding, KeyPress};
 use cosmic::iced::{Alignment, Length};
 use cosmic::prelude::*;
 use cosmic::widget;
 
-use crate::message::Message;
+use crate::matrix::commands::{self, CommandSpec};
+use crate::message::{Completion, Message};
 use crate::state::timeline::TimelineState;
+use crate::ui::rich_text;
+
+/// The editor grows with its content up to this height, then scrolls.
+const MAX_COMPOSER_HEIGHT: f32 = 200.0;
+
+/// Show at most this many matching commands while a command name is typed.
+const MAX_COMMAND_HINTS: usize = 6;
+
+fn command_hint(spec: &CommandSpec) -> Element<'static, Message> {
+    let spacing = cosmic::theme::spacing();
+    widget::row()
+        .push(widget::text::caption(spec.usage()).font(cosmic::iced::Font::MONOSPACE))
+        .push(widget::text::caption(spec.description))
+        .spacing(spacing.space_s)
+        .padding([0, spacing.space_xs])
+        .into()
+}
+
+/// Enter sends, Shift+Enter inserts a newline, Up in an empty composer edits our
+/// last message, Tab completes a command name or takes the first autocomplete entry;
+/// everything else keeps the editor defaults.
+fn composer_key_binding(
+    press: KeyPress,
+    is_empty: bool,
+    completing: bool,
+    first_completion: Option<&Completion>,
+) -> Option<Binding<Message>> {
+    match press.key.as_ref() {
+        Key::Named(Named::Tab) if completing => Some(Binding::Custom(Message::CompleteCommand)),
+        Key::Named(Named::Tab) if first_completion.is_some() => first_completion
+            .cloned()
+            .map(|c| Binding::Custom(Message::ApplyCompletion(c))),
+        Key::Named(Named::Enter) if !press.modifiers.shift() => {
+            Some(Binding::Custom(Message::SendMessage))
+        }
+        Key::Named(Named::ArrowUp) if is_empty && press.modifiers.is_empty() => {
+            Some(Binding::Custom(Message::EditLastMessage))
+        }
+        _ => Binding::from_key_press(press),
+    }
+}
 
 pub fn composer_view<'a>(state: &'a TimelineState) -> Element<'a, Message> {
     let spacing = cosmic::theme::spacing();
 
-    let input = widget::text_input::text_input("Send a message...", &state.composer)
-        .on_input(Message::ComposerChanged)
-        .on_submit(|_| Message::SendMessage);
+    let text = state.composer_text();
+    let is_empty = text.is_empty();
+    let completions = commands::completions(&text);
+    let completing = !completions.is_empty();
+    let first_suggestion = state.suggestions.first().cloned();
+    let input = cosmic::iced::widget::text_editor(&state.composer)
+        .placeholder("Send a message (Markdown)... Shift+Enter for a new line")
+        .on_action(Message::ComposerAction)
+        .key_binding(move |press| {
+            composer_key_binding(press, is_empty, completing, first_suggestion.as_ref())
+        })
+        .height(Length::Shrink)
+        .max_height(MAX_COMPOSER_HEIGHT)
+        .padding(spacing.space_xxs);
 
-    let mut send_btn = widget::button::suggested("Send");
-    if !state.composer.trim().is_empty() && !state.sending {
+    let send_label = if state.editing.is_some() { "Save" } else { "Send" };
+    let mut send_btn = widget::button::suggested(send_label);
+    if !is_empty && !state.sending {
         send_btn = send_btn.on_press(Message::SendMessage);
     }
 
@@ -29,6 +86,62 @@ pub fn composer_view<'a>(state: &'a TimelineState) -> Element<'a, Message> {
 
     let mut col = widget::column().spacing(spacing.space_xxs);
 
+    if let Some((_, ref reason)) = state.redacting {
+        col = col.push(
+            widget::container(
+                widget::row()
+                    .push(widget::text::caption("Delete this message?"))
+                    .push(
+                        widget::text_input::text_input("Reason (optional)", reason)
+                            .on_input(Message::RedactionReasonChanged)
+                            .on_submit(|_| Message::ConfirmRedaction),
+                    )
+                    .push(widget::button::text("Cancel").on_press(Message::CancelRedaction))
+                    .push(widget::button::destructive("Delete").on_press(Message::ConfirmRedaction))
+                    .spacing(spacing.space_xs)
+                    .align_y(Alignment::Center),
+            )
+            .padding([spacing.space_xxs, spacing.space_xs])
+            .width(Length::Fill),
+        );
+    }
+
+    if state.thread.is_some() && state.editing.is_none() {
+        col = col.push(
+            widget::container(
+                widget::row()
+                    .push(widget::text::caption("💬 Replying in thread"))
+                    .push(widget::horizontal_space())
+                    .push(
+                        widget::button::text("×")
+                            .on_press(Message::CloseThread)
+                            .padding([0, spacing.space_xxs]),
+                    )
+                    .align_y(Alignment::Center),
+            )
+            .padding([spacing.space_xxs, spacing.space_xs])
+            .width(Length::Fill),
+        );
+    }
+
+    if state.editing.is_some() {
+        col = col.push(
+            widget::container(
+                widget::row()
+                    .push(widget::text::caption("✎ Editing message"))
+                    .push(widget::horizontal_space())
+                    .push(
+                        widget::button::text("×")
+                            .on_press(Message::CancelEdit)
+                            .padding([0, spacing.space_xxs]),
+                    )
+                    .align_y(Alignment::Center),
+            )
+            .padding([spacing.space_xxs, spacing.space_xs])
+            .width(Length::Fill),
+        );
+    }
+
     if let Some(ref ctx) = state.reply_to {
         let preview = if ctx.body_preview.is_empty() {
             String::new()
@@ -54,13 +167,75 @@ pub fn composer_view<'a>(state: &'a TimelineState) -> Element<'a, Message> {
         );
     }
 
+    // Slash command help: matching commands while the name is typed, then its usage
+    for spec in completions.iter().take(MAX_COMMAND_HINTS) {
+        col = col.push(command_hint(spec));
+    }
+    if let Some(spec) = commands::current(&text) {
+        col = col.push(command_hint(spec));
+    }
+
+    // Mention / room / emoji autocomplete
+    if !state.suggestions.is_empty() {
+        let mut list = widget::column().spacing(2);
+        for completion in &state.suggestions {
+            list = list.push(
+                widget::button::text(completion.label.clone())
+                    .on_press(Message::ApplyCompletion(completion.clone()))
+                    .width(Length::Fill),
+            );
+        }
+        col = col.push(
+            widget::container(list)
+                .padding(spacing.space_xxs)
+                .width(Length::Fill)
+                .class(cosmic::theme::Container::Card),
+        );
+    }
+
+    if let Some(ref feedback) = state.command_feedback {
+        let label = match feedback {
+            Ok(info) => info.clone(),
+            Err(e) => format!("⚠ {e}"),
+        };
+        col = col.push(
+            widget::container(
+                widget::row()
+                    .push(widget::text::caption(label))
+                    .push(widget::horizontal_space())
+                    .push(
+                        widget::button::text("×")
+                            .on_press(Message::DismissCommandFeedback)
+                            .padding([0, spacing.space_xxs]),
+                    )
+                    .align_y(Alignment::Center),
+            )
+            .padding([spacing.space_xxs, spacing.space_xs])
+            .width(Length::Fill),
+        );
+    }
+
+    if !state.composer_preview.is_empty() {
+        col = col.push(
+            widget::container(
+                widget::column()
+                    .push(widget::text::caption("Preview"))
+                    .push(rich_text::rich_blocks_view(&state.composer_preview))
+                    .spacing(spacing.space_xxs),
+            )
+            .padding([spacing.space_xxs, spacing.space_xs])
+            .width(Length::Fill)
+            .class(cosmic::theme::Container::Card),
+        );
+    }
+
     col = col.push(
         widget::row()
             .push(attach_btn)
             .push(input)
             .push(send_btn)
             .spacing(spacing.space_xs)
-            .align_y(Alignment::Center),
+            .align_y(Alignment::End),
     );
 
     widget::container(col)
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use cosmic::iced::widget::image::Handle as ImageHandle;
+use cosmic::iced::{Alignment, ContentFit, Length};
+use cosmic::prelude::*;
+use cosmic::widget;
+use matrix_sdk::ruma::{OwnedUserId, UserId};
+
+use crate::message::Message;
+use crate::state::create::{CreateRoomState, NewDmState};
+
+/// "New room" dialog. `server_name` completes the alias preview.
+pub fn create_room_view<'a>(
+    state: &'a CreateRoomState,
+    server_name: &str,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut col = widget::column().spacing(spacing.space_s).max_width(480.0);
+    col = col.push(
+        widget::row()
+            .push(widget::text::title3("New room"))
+            .push(widget::horizontal_space())
+            .push(widget::button::text("Close").on_press(Message::CloseCreateRoomDialog))
+            .align_y(Alignment::Center),
+    );
+
+    col = col.push(
+        widget::text_input("Name", &state.name).on_input(Message::CreateRoomNameChanged),
+    );
+    col = col.push(
+        widget::text_input("Topic (optional)", &state.topic)
+            .on_input(Message::CreateRoomTopicChanged),
+    );
+    col = col.push(
+        widget::text_input("Address (optional)", &state.alias)
+            .on_input(Message::CreateRoomAliasChanged),
+    );
+    if let Some(alias) = state.alias_localpart() {
+        col = col.push(widget::text::caption(format!("#{alias}:{server_name}")));
+    }
+    col = col.push(
+        widget::text_input("Invite (user IDs, optional)", &state.invitees)
+            .on_input(Message::CreateRoomInviteesChanged),
+    );
+
+    col = col.push(
+        widget::column()
+            .push(widget::settings::item(
+                "Public (anyone can find and join)",
+                widget::toggler(state.public).on_toggle(Message::SetCreateRoomPublic),
+            ))
+            .push(widget::settings::item(
+                "End-to-end encryption",
+                widget::toggler(state.encrypted).on_toggle(Message::SetCreateRoomEncrypted),
+            )),
+    );
+    if state.public && state.encrypted {
+        col = col.push(widget::text::caption(
+            "Anyone can join a public room and read it, so encryption adds little privacy there",
+        ));
+    }
+
+    if let Some(ref err) = state.error {
+        col = col.push(widget::text::body(err.as_str()));
+    }
+
+    let mut create = widget::button::suggested(if state.creating { "Creating…" } else { "Create" });
+    if !state.creating && !state.name.trim().is_empty() {
+        create = create.on_press(Message::CreateRoomSubmit);
+    }
+    col = col.push(widget::row().push(widget::horizontal_space()).push(create));
+
+    dialog_container(col)
+}
+
+/// "New DM" dialog: search the user directory and pick someone.
+pub fn new_dm_view<'a>(
+    state: &'a NewDmState,
+    avatars: &'a HashMap<String, ImageHandle>,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut col = widget::column().spacing(spacing.space_s).max_width(480.0);
+    col = col.push(
+        widget::row()
+            .push(widget::text::title3("New direct message"))
+            .push(widget::horizontal_space())
+            .push(widget::button::text("Close").on_press(Message::CloseNewDmDialog))
+            .align_y(Alignment::Center),
+    );
+    col = col.push(
+        widget::text_input::search_input("Name or @user:server", &state.query)
+            .on_input(Message::DmSearchChanged)
+            .on_clear(Message::DmSearchChanged(String::new())),
+    );
+
+    let mut list = widget::column().spacing(spacing.space_xxs);
+
+    // A full user ID works even when the directory doesn't list the user
+    let query = state.query.trim();
+    if let Ok(user_id) = UserId::parse(query) {
+        if !state.results.iter().any(|u| u.user_id == user_id) {
+            let name = user_id.to_string();
+            list = list.push(user_row(&name, None, None, user_id, state.starting));
+        }
+    }
+    for user in &state.results {
+        let avatar = user.avatar_url.as_ref().and_then(|url| avatars.get(url));
+        list = list.push(user_row(
+            user.user_id.as_str(),
+            user.display_name.as_deref(),
+            avatar,
+            user.user_id.clone(),
+            state.starting,
+        ));
+    }
+    if state.searching {
+        list = list.push(widget::text::body("Searching…"));
+    } else if state.results.is_empty() && !query.is_empty() {
+        list = list.push(widget::text::body("No users found"));
+    }
+    if let Some(ref err) = state.error {
+        list = list.push(widget::text::body(err.as_str()));
+    }
+    col = col.push(widget::scrollable(list).height(Length::Fill));
+
+    dialog_container(col)
+}
+
+fn user_row<'a>(
+    user_id: &str,
+    display_name: Option<&str>,
+    avatar: Option<&'a ImageHandle>,
+    target: OwnedUserId,
+    starting: bool,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let avatar_elem: Element<_> = if let Some(handle) = avatar {
+        cosmic::iced::widget::image(handle.clone())
+            .content_fit(ContentFit::Cover)
+            .width(Length::Fixed(32.0))
+            .height(Length::Fixed(32.0))
+            .into()
+    } else {
+        let letter = display_name
+            .unwrap_or(user_id.trim_start_matches('@'))
+            .chars()
+            .next()
+            .unwrap_or('?')
+            .to_uppercase()
+            .to_string();
+        widget::container(widget::text::heading(letter))
+            .width(Length::Fixed(32.0))
+            .height(Length::Fixed(32.0))
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into()
+    };
+
+    let mut info = widget::column().spacing(2).width(Length::Fill);
+    if let Some(name) = display_name {
+        info = info.push(widget::text::heading(name.to_string()));
+    }
+    info = info.push(widget::text::caption(user_id.to_string()));
+
+    let mut btn = widget::button::suggested("Message");
+    if !starting {
+        btn = btn.on_press(Message::StartDm(target));
+    }
+
+    widget::row()
+        .push(avatar_elem)
+        .push(info)
+        .push(btn)
+        .spacing(spacing.space_xs)
+        .align_y(Alignment::Center)
+        .into()
+}
+
+fn dialog_container<'a>(col: widget::Column<'a, Message>) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    widget::container(col)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(Alignment::Center)
+        .padding(spacing.space_l)
+        .class(cosmic::theme::Container::Background)
+        .into()
+}
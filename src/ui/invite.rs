@@ -0,0 +1,99 @@
+use cosmic::iced::widget::image::Handle as ImageHandle;
+use cosmic::iced::{Alignment, ContentFit, Length};
+use cosmic::prelude::*;
+use cosmic::widget;
+
+use crate::message::{Message, RoomEntry};
+
+/// Preview of a room we are invited to, shown instead of its timeline.
+pub fn invite_preview_view<'a>(
+    room: &'a RoomEntry,
+    avatar: Option<&'a ImageHandle>,
+    inviter_avatar: Option<&'a ImageHandle>,
+    pending: bool,
+    error: Option<&'a str>,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut col = widget::column()
+        .spacing(spacing.space_s)
+        .align_x(Alignment::Center)
+        .max_width(480.0);
+
+    let avatar_elem: Element<_> = if let Some(handle) = avatar {
+        cosmic::iced::widget::image(handle.clone())
+            .content_fit(ContentFit::Cover)
+            .width(Length::Fixed(96.0))
+            .height(Length::Fixed(96.0))
+            .into()
+    } else {
+        widget::container(widget::text::title1(room.avatar_letter.to_string()))
+            .width(Length::Fixed(96.0))
+            .height(Length::Fixed(96.0))
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into()
+    };
+    col = col.push(avatar_elem);
+    col = col.push(widget::text::title3(room.name.clone()));
+    if let Some(ref alias) = room.canonical_alias {
+        col = col.push(widget::text::caption(alias.clone()));
+    }
+    if let Some(ref topic) = room.topic {
+        col = col.push(widget::text::body(topic.clone()));
+    }
+
+    // "Alice (@alice:example.org) invited you"
+    if let Some(ref inviter) = room.inviter {
+        let mut row = widget::row()
+            .spacing(spacing.space_xxs)
+            .align_y(Alignment::Center);
+        if let Some(handle) = inviter_avatar {
+            row = row.push(
+                cosmic::iced::widget::image(handle.clone())
+                    .content_fit(ContentFit::Cover)
+                    .width(Length::Fixed(20.0))
+                    .height(Length::Fixed(20.0)),
+            );
+        }
+        let label = match inviter.display_name {
+            Some(ref name) => format!("{name} ({}) invited you", inviter.user_id),
+            None => format!("{} invited you", inviter.user_id),
+        };
+        row = row.push(widget::text::body(label));
+        col = col.push(row);
+    } else {
+        col = col.push(widget::text::body("You have been invited to this room"));
+    }
+
+    let room_id = room.room_id.clone();
+    let mut accept = widget::button::suggested("Accept");
+    let mut decline = widget::button::standard("Decline");
+    let mut decline_ignore = widget::button::destructive("Decline and ignore user");
+    if !pending {
+        accept = accept.on_press(Message::AcceptInvite(room_id.clone()));
+        decline = decline.on_press(Message::DeclineInvite(room_id.clone(), false));
+        if room.inviter.is_some() {
+            decline_ignore = decline_ignore.on_press(Message::DeclineInvite(room_id, true));
+        }
+    }
+    col = col.push(
+        widget::row()
+            .push(decline_ignore)
+            .push(decline)
+            .push(accept)
+            .spacing(spacing.space_xs),
+    );
+
+    if let Some(error) = error {
+        col = col.push(widget::text::body(error));
+    }
+
+    widget::container(col)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(Alignment::Center)
+        .align_y(Alignment::Center)
+        .padding(spacing.space_l)
+        .into()
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use cosmic::iced::widget::image::Handle as ImageHandle;
+use cosmic::iced::{Alignment, ContentFit, Length};
+use cosmic::prelude::*;
+use cosmic::widget;
+
+use crate::message::{Message, PublicRoom};
+use crate::state::join::JoinState;
+use crate::state::rooms::RoomsState;
+
+/// "Join room" dialog: join by address, or pick a room from a public directory.
+pub fn join_dialog_view<'a>(
+    state: &'a JoinState,
+    rooms_state: &'a RoomsState,
+    avatars: &'a HashMap<String, ImageHandle>,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut col = widget::column().spacing(spacing.space_s).max_width(560.0);
+    col = col.push(
+        widget::row()
+            .push(widget::text::title3("Join room"))
+            .push(widget::horizontal_space())
+            .push(widget::button::text("Close").on_press(Message::CloseJoinDialog))
+            .align_y(Alignment::Center),
+    );
+
+    // Join by address
+    let mut address =
+        widget::text_input("#room:example.org or https://matrix.to/#/…", &state.address);
+    let mut join_btn = widget::button::suggested(if state.joining { "Joining…" } else { "Join" });
+    if !state.joining {
+        address = address
+            .on_input(Message::JoinAddressChanged)
+            .on_submit(|_| Message::JoinSubmit);
+        if !state.address.trim().is_empty() {
+            join_btn = join_btn.on_press(Message::JoinSubmit);
+        }
+    }
+    col = col.push(
+        widget::row()
+            .push(address)
+            .push(join_btn)
+            .spacing(spacing.space_xs)
+            .align_y(Alignment::Center),
+    );
+    if let Some(ref err) = state.error {
+        col = col.push(widget::text::body(err.as_str()));
+    }
+
+    col = col.push(widget::divider::horizontal::default());
+
+    // Public room directory
+    col = col.push(widget::text::heading("Public rooms"));
+    col = col.push(
+        widget::row()
+            .push(
+                widget::text_input("Server (default: yours)", &state.server)
+                    .on_input(Message::DirectoryServerChanged)
+                    .on_submit(|_| Message::DirectorySearch)
+                    .width(Length::Fixed(180.0)),
+            )
+            .push(
+                widget::text_input::search_input("Search rooms…", &state.search)
+                    .on_input(Message::DirectorySearchChanged)
+                    .on_submit(|_| Message::DirectorySearch)
+                    .on_clear(Message::DirectorySearchChanged(String::new())),
+            )
+            .spacing(spacing.space_xs)
+            .align_y(Alignment::Center),
+    );
+
+    let mut list = widget::column().spacing(spacing.space_xxs);
+    for room in &state.rooms {
+        let joined = rooms_state
+            .rooms
+            .iter()
+            .any(|r| r.room_id == room.room_id && !r.is_invite);
+        let avatar = room.avatar_url.as_ref().and_then(|url| avatars.get(url));
+        list = list.push(directory_row(room, avatar, joined, state.joining));
+    }
+    if state.loading {
+        list = list.push(widget::text::body("Loading…"));
+    } else if let Some(ref err) = state.directory_error {
+        list = list.push(widget::text::body(err.as_str()));
+    } else if state.rooms.is_empty() {
+        list = list.push(widget::text::body("No rooms found"));
+    } else if state.next_batch.is_some() {
+        list = list.push(widget::button::text("Load more").on_press(Message::DirectoryLoadMore));
+    }
+    col = col.push(widget::scrollable(list).height(Length::Fill));
+
+    widget::container(col)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(Alignment::Center)
+        .padding(spacing.space_l)
+        .class(cosmic::theme::Container::Background)
+        .into()
+}
+
+/// Avatar, name, alias, member count and topic of a directory entry, with a Join
+/// button (or Open when we are already in the room).
+fn directory_row<'a>(
+    room: &'a PublicRoom,
+    avatar: Option<&'a ImageHandle>,
+    joined: bool,
+    joining: bool,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let avatar_elem: Element<_> = if let Some(handle) = avatar {
+        cosmic::iced::widget::image(handle.clone())
+            .content_fit(ContentFit::Cover)
+            .width(Length::Fixed(32.0))
+            .height(Length::Fixed(32.0))
+            .into()
+    } else {
+        let letter = room
+            .display_name()
+            .trim_start_matches(['#', '!'])
+            .chars()
+            .next()
+            .unwrap_or('#');
+        widget::container(widget::text::heading(letter.to_string()))
+            .width(Length::Fixed(32.0))
+            .height(Length::Fixed(32.0))
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into()
+    };
+
+    let mut info = widget::column().spacing(2).width(Length::Fill);
+    info = info.push(widget::text::heading(room.display_name().to_string()));
+    let mut details = format!("{} members", room.member_count);
+    if let Some(ref alias) = room.canonical_alias {
+        details = format!("{alias} · {details}");
+    }
+    info = info.push(widget::text::caption(details));
+    if let Some(ref topic) = room.topic {
+        let mut short: String = topic.chars().take(160).collect();
+        if short.len() < topic.len() {
+            short.push('\u{2026}');
+        }
+        info = info.push(widget::text::body(short));
+    }
+
+    let action = if joined {
+        widget::button::standard("Open").on_press(Message::SelectRoom(room.room_id.clone()))
+    } else {
+        let mut btn = widget::button::suggested("Join");
+        if !joining {
+            btn = btn.on_press(Message::JoinPublicRoom(room.room_id.clone()));
+        }
+        btn
+    };
+
+    widget::row()
+        .push(avatar_elem)
+        .push(info)
+        .push(action)
+        .spacing(spacing.space_xs)
+        .align_y(Alignment::Center)
+        .into()
+}
@@ -0,0 +1,71 @@
+use cosmic::iced::{Alignment, Length};
+use cosmic::prelude::*;
+use cosmic::widget;
+
+use crate::message::Message;
+use crate::state::rooms::LeaveConfirm;
+
+/// Confirmation before leaving (or leaving and forgetting) a room.
+pub fn leave_confirm_view<'a>(
+    confirm: &'a LeaveConfirm,
+    room_name: &'a str,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let title = if confirm.forget {
+        format!("Forget {room_name}?")
+    } else {
+        format!("Leave {room_name}?")
+    };
+    let mut col = widget::column()
+        .spacing(spacing.space_s)
+        .max_width(420.0)
+        .push(widget::text::title3(title));
+
+    if confirm.forget {
+        col = col.push(widget::text::body(
+            "You will leave the room and it will be removed from your room history on all devices.",
+        ));
+    }
+    if confirm.checking {
+        col = col.push(widget::text::caption("Checking room…"));
+    } else if let Some(ref warning) = confirm.warning {
+        col = col.push(widget::text::heading(warning.as_str()));
+    }
+    if let Some(ref err) = confirm.error {
+        col = col.push(widget::text::body(err.as_str()));
+    }
+
+    let label = match (confirm.forget, confirm.leaving) {
+        (_, true) => "Leaving…",
+        (true, false) => "Forget",
+        (false, false) => "Leave",
+    };
+    let mut confirm_btn = widget::button::destructive(label);
+    if !confirm.leaving && !confirm.checking {
+        confirm_btn = confirm_btn.on_press(Message::LeaveRoom);
+    }
+    let mut cancel = widget::button::standard("Cancel");
+    if !confirm.leaving {
+        cancel = cancel.on_press(Message::CancelLeave);
+    }
+    col = col.push(
+        widget::row()
+            .push(widget::horizontal_space())
+            .push(cancel)
+            .push(confirm_btn)
+            .spacing(spacing.space_xs),
+    );
+
+    widget::container(
+        widget::container(col)
+            .padding(spacing.space_m)
+            .class(cosmic::theme::Container::Card),
+    )
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .align_x(Alignment::Center)
+    .align_y(Alignment::Center)
+    .class(cosmic::theme::Container::Background)
+    .into()
+}
@@ -2,7 +2,17 @@ use cosmic::iced::{Alignment, Length};
 use cosmic::prelude::*;
 use cosmic::widget;
 
-use crate::message::Message;
+use matrix_sdk::ruma::OwnedUserId;
+
+use crate::message::{LoginFlows, Message};
+
+/// An account the server soft-logged-out. Signing in again reuses its device, so its
+/// store and encryption keys are kept.
+#[derive(Clone, Debug)]
+pub struct ReauthTarget {
+    pub account_id: String,
+    pub user_id: OwnedUserId,
+}
 
 pub struct LoginState {
     pub homeserver: String,
@@ -11,6 +21,13 @@ pub struct LoginState {
     pub password_visible: bool,
     pub error: Option<String>,
     pub loading: bool,
+    /// Login methods of `homeserver`; `None` until discovered.
+    pub flows: Option<LoginFlows>,
+    pub discovering: bool,
+    /// Adding another account while already signed in; offers a way back.
+    pub can_cancel: bool,
+    /// Signing in again to an expired account rather than adding a new one.
+    pub reauth: Option<ReauthTarget>,
 }
 
 impl Default for LoginState {
@@ -22,6 +39,10 @@ impl Default for LoginState {
             password_visible: false,
             error: None,
             loading: false,
+            flows: None,
+            discovering: false,
+            can_cancel: false,
+            reauth: None,
         }
     }
 }
@@ -35,35 +56,66 @@ pub fn login_view(state: &LoginState) -> Element<'_, Message> {
         .align_x(Alignment::Center);
 
     form = form.push(widget::text::title2("Cosmic Matrix"));
-    form = form.push(widget::text::body("Sign in to your Matrix account"));
+    if let Some(ref target) = state.reauth {
+        form = form.push(widget::text::body(format!(
+            "Your session for {} has expired. Sign in again to keep this device and its encryption keys.",
+            target.user_id
+        )));
+    } else {
+        form = form.push(widget::text::body(if state.can_cancel {
+            "Sign in to another Matrix account"
+        } else {
+            "Sign in to your Matrix account"
+        }));
+    }
     form = form.push(widget::vertical_space().height(Length::Fixed(spacing.space_m as f32)));
 
-    // Homeserver input
+    // Homeserver input; fixed when signing in to an existing account again
     form = form.push(widget::text::caption_heading("Homeserver"));
-    form = form.push(
-        widget::text_input::text_input("matrix.org", &state.homeserver)
-            .on_input(Message::HomeserverChanged),
-    );
-
-    // Username input
-    form = form.push(widget::text::caption_heading("Username"));
-    form = form.push(
-        widget::text_input::text_input("@user:matrix.org", &state.username)
-            .on_input(Message::UsernameChanged),
-    );
-
-    // Password input
-    form = form.push(widget::text::caption_heading("Password"));
-    form = form.push(
-        widget::text_input::secure_input(
-            "Password",
-            &state.password,
-            Some(Message::TogglePasswordVisibility),
-            !state.password_visible,
-        )
-        .on_input(Message::PasswordChanged)
-        .on_submit(|_| Message::LoginSubmit),
-    );
+    let mut homeserver = widget::text_input::text_input("matrix.org", &state.homeserver);
+    if state.reauth.is_none() {
+        homeserver = homeserver
+            .on_input(Message::HomeserverChanged)
+            .on_submit(|_| Message::DiscoverLoginFlows);
+    }
+    form = form.push(homeserver);
+
+    let Some(ref flows) = state.flows else {
+        form = form.push(widget::vertical_space().height(Length::Fixed(spacing.space_s as f32)));
+        if let Some(ref err) = state.error {
+            form = form.push(widget::text::body(err.as_str()));
+        }
+        let label = if state.discovering { "Checking homeserver..." } else { "Continue" };
+        let mut btn = widget::button::suggested(label).width(Length::Fill);
+        if !state.discovering && !state.homeserver.is_empty() {
+            btn = btn.on_press(Message::DiscoverLoginFlows);
+        }
+        form = form.push(btn);
+        return centered(with_cancel(form, state));
+    };
+
+    if flows.password && state.reauth.is_none() {
+        // Username input
+        form = form.push(widget::text::caption_heading("Username"));
+        form = form.push(
+            widget::text_input::text_input("@user:matrix.org", &state.username)
+                .on_input(Message::UsernameChanged),
+        );
+    }
+    if flows.password {
+        // Password input
+        form = form.push(widget::text::caption_heading("Password"));
+        form = form.push(
+            widget::text_input::secure_input(
+                "Password",
+                &state.password,
+                Some(Message::TogglePasswordVisibility),
+                !state.password_visible,
+            )
+            .on_input(Message::PasswordChanged)
+            .on_submit(|_| Message::LoginSubmit),
+        );
+    }
 
     form = form.push(widget::vertical_space().height(Length::Fixed(spacing.space_s as f32)));
 
@@ -72,12 +124,18 @@ pub fn login_view(state: &LoginState) -> Element<'_, Message> {
         form = form.push(widget::text::body(err.as_str()));
     }
 
-    // Login button
     if state.loading {
         form = form.push(widget::button::suggested("Signing in...").width(Length::Fill));
-    } else {
+        form = form.push(widget::text::caption(
+            "Finish signing in in your browser if a window opened.",
+        ));
+        return centered(form);
+    }
+
+    // Login button
+    if flows.password {
         let can_submit = !state.homeserver.is_empty()
-            && !state.username.is_empty()
+            && (state.reauth.is_some() || !state.username.is_empty())
             && !state.password.is_empty();
 
         let mut btn = widget::button::suggested("Sign In").width(Length::Fill);
@@ -87,6 +145,61 @@ pub fn login_view(state: &LoginState) -> Element<'_, Message> {
         form = form.push(btn);
     }
 
+    // Browser-based methods
+    if flows.oidc_issuer.is_some() {
+        form = form.push(
+            widget::button::standard("Continue with your account provider")
+                .on_press(Message::LoginOidc)
+                .width(Length::Fill),
+        );
+    }
+    if flows.sso {
+        if flows.identity_providers.is_empty() {
+            form = form.push(
+                widget::button::standard("Sign in with SSO")
+                    .on_press(Message::LoginSso(None))
+                    .width(Length::Fill),
+            );
+        }
+        for idp in &flows.identity_providers {
+            form = form.push(
+                widget::button::standard(format!("Continue with {}", idp.name))
+                    .on_press(Message::LoginSso(Some(idp.id.clone())))
+                    .width(Length::Fill),
+            );
+        }
+    }
+
+    if !flows.password && !flows.sso && flows.oidc_issuer.is_none() {
+        form = form.push(widget::text::body(
+            "This homeserver offers no login method supported by Cosmic Matrix.",
+        ));
+    }
+
+    centered(with_cancel(form, state))
+}
+
+fn with_cancel<'a>(form: widget::Column<'a, Message>, state: &LoginState) -> widget::Column<'a, Message> {
+    let form = if state.reauth.is_some() {
+        form.push(
+            widget::button::text("Sign out and delete this device's data")
+                .on_press(Message::Logout)
+                .width(Length::Fill),
+        )
+    } else {
+        form
+    };
+    if !state.can_cancel {
+        return form;
+    }
+    form.push(
+        widget::button::text("Cancel")
+            .on_press(Message::CancelAddAccount)
+            .width(Length::Fill),
+    )
+}
+
+fn centered<'a>(form: widget::Column<'a, Message>) -> Element<'a, Message> {
     widget::container(form)
         .width(Length::Fill)
         .height(Length::Fill)
@@ -1,7 +1,13 @@
 pub mod colors;
 pub mod composer;
+pub mod create;
+pub mod invite;
+pub mod join;
+pub mod leave;
 pub mod login;
 pub mod room_header;
+pub mod room_settings;
 pub mod timeline;
 pub mod verification;
 pub mod profile;
+pub mod rich_text;
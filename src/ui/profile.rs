@@ -8,6 +8,8 @@ use crate::message::Message;
 pub fn profile_panel_view<'a>(
     own_user_id: &'a str,
     own_avatar: Option<&'a ImageHandle>,
+    send_typing_notifications: bool,
+    private_read_receipts: bool,
 ) -> Element<'a, Message> {
     let spacing = cosmic::theme::spacing();
 
@@ -53,6 +55,22 @@ pub fn profile_panel_view<'a>(
         widget::button::text("Clear avatar")
             .on_press(Message::ClearAvatar),
     );
+    col = col.push(
+        widget::container(
+            widget::column()
+                .push(widget::settings::item(
+                    "Send typing notifications",
+                    widget::toggler(send_typing_notifications)
+                        .on_toggle(Message::SetSendTypingNotifications),
+                ))
+                .push(widget::settings::item(
+                    "Private read receipts",
+                    widget::toggler(private_read_receipts)
+                        .on_toggle(Message::SetPrivateReadReceipts),
+                )),
+        )
+        .width(Length::Fixed(360.0)),
+    );
     col = col.push(
         widget::button::text("Close")
             .on_press(Message::CloseProfilePanel),
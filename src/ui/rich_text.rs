@@ -0,0 +1,147 @@
+use cosmic::iced::font::{Style, Weight};
+use cosmic::iced::widget::scrollable::{Direction, Scrollbar};
+use cosmic::iced::widget::text::Span;
+use cosmic::iced::{Alignment, Font, Length};
+use cosmic::prelude::*;
+use cosmic::widget;
+
+use crate::message::{CodeToken, Message, RichBlock, RichSpan};
+use crate::ui::colors;
+
+/// Body text size for paragraphs; headings scale up from it.
+const BODY_SIZE: f32 = 14.0;
+
+/// Render a parsed `formatted_body`.
+pub fn rich_blocks_view<'a>(blocks: &'a [RichBlock]) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let mut col = widget::column().spacing(spacing.space_xxs);
+    for block in blocks {
+        col = col.push(rich_block_view(block));
+    }
+    col.into()
+}
+
+fn rich_block_view<'a>(block: &'a RichBlock) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    match block {
+        RichBlock::Paragraph(spans) => paragraph(spans, BODY_SIZE),
+        RichBlock::Heading(level, spans) => {
+            let size = match level {
+                1 => 22.0,
+                2 => 19.0,
+                3 => 17.0,
+                _ => 15.0,
+            };
+            paragraph(spans, size)
+        }
+        RichBlock::CodeBlock {
+            language,
+            code,
+            lines,
+        } => code_block(language.as_deref(), code, lines),
+        RichBlock::Quote(blocks) => widget::row()
+            .push(widget::divider::vertical::default())
+            .push(rich_blocks_view(blocks))
+            .spacing(spacing.space_xs)
+            .into(),
+        RichBlock::List {
+            ordered,
+            start,
+            items,
+        } => {
+            let mut col = widget::column().spacing(2);
+            for (i, item) in items.iter().enumerate() {
+                let marker = if *ordered {
+                    format!("{}.", start + i as u64)
+                } else {
+                    "•".to_string()
+                };
+                col = col.push(
+                    widget::row()
+                        .push(
+                            widget::container(widget::text::body(marker))
+                                .width(Length::Fixed(24.0)),
+                        )
+                        .push(rich_blocks_view(item)),
+                );
+            }
+            col.into()
+        }
+        RichBlock::Rule => widget::divider::horizontal::default().into(),
+    }
+}
+
+/// Highlighted, horizontally scrollable code with a copy button.
+fn code_block<'a>(
+    language: Option<&'a str>,
+    code: &'a str,
+    lines: &'a [Vec<CodeToken>],
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut spans: Vec<Span<'a, String, Font>> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            spans.push(cosmic::iced::widget::span("\n"));
+        }
+        for token in line {
+            spans.push(
+                cosmic::iced::widget::span(token.text.as_str())
+                    .color_maybe(colors::code_token_color(token.kind)),
+            );
+        }
+    }
+    let code_text = cosmic::iced::widget::rich_text(spans).font(Font::MONOSPACE);
+
+    let header = widget::row()
+        .push(widget::text::caption(language.unwrap_or("code")))
+        .push(widget::horizontal_space())
+        .push(
+            widget::button::text("Copy")
+                .on_press(Message::CopyToClipboard(code.to_string()))
+                .padding([0, spacing.space_xxs]),
+        )
+        .align_y(Alignment::Center);
+
+    widget::container(
+        widget::column()
+            .push(header)
+            .push(
+                cosmic::iced::widget::scrollable(code_text)
+                    .direction(Direction::Horizontal(Scrollbar::new()))
+                    .width(Length::Fill),
+            )
+            .spacing(spacing.space_xxs),
+    )
+    .padding(spacing.space_xs)
+    .width(Length::Fill)
+    .class(cosmic::theme::Container::Card)
+    .into()
+}
+
+fn paragraph<'a>(spans: &'a [RichSpan], size: f32) -> Element<'a, Message> {
+    let spans: Vec<Span<'a, String, Font>> = spans.iter().map(span).collect();
+    cosmic::iced::widget::rich_text(spans)
+        .size(size)
+        .on_link_click(Message::OpenLink)
+        .into()
+}
+
+fn span<'a>(rich: &'a RichSpan) -> Span<'a, String, Font> {
+    let font = if rich.code {
+        Font::MONOSPACE
+    } else {
+        Font {
+            weight: if rich.bold { Weight::Bold } else { Weight::Normal },
+            style: if rich.italic { Style::Italic } else { Style::Normal },
+            ..Font::DEFAULT
+        }
+    };
+    let mut span = cosmic::iced::widget::span(rich.text.as_str())
+        .font(font)
+        .strikethrough(rich.strike);
+    if let Some(ref url) = rich.link {
+        span = span.underline(true).link(url.clone());
+    }
+    span
+}
@@ -2,14 +2,17 @@ use cosmic::iced::widget::image::Handle as ImageHandle;
 use cosmic::iced::{Alignment, Length};
 use cosmic::prelude::*;
 use cosmic::widget;
+use matrix_sdk::ruma::OwnedRoomId;
 
 use crate::message::Message;
 
 pub fn room_header_view<'a>(
+    room_id: Option<&'a OwnedRoomId>,
     room_name: &'a str,
     is_encrypted: bool,
     topic: Option<&'a str>,
     avatar: Option<&'a ImageHandle>,
+    has_unread: bool,
 ) -> Element<'a, Message> {
     let spacing = cosmic::theme::spacing();
 
@@ -45,6 +48,25 @@ pub fn room_header_view<'a>(
         row = row.push(widget::text::caption("Encrypted"));
     }
 
+    row = row.push(widget::horizontal_space());
+    if has_unread {
+        row = row.push(
+            widget::button::text("Jump to first unread").on_press(Message::JumpToUnread),
+        );
+    }
+    if let Some(room_id) = room_id {
+        row = row
+            .push(widget::button::text("Settings").on_press(Message::ShowRoomSettings))
+            .push(
+                widget::button::text("Leave")
+                    .on_press(Message::ConfirmLeave(room_id.clone(), false)),
+            )
+            .push(
+                widget::button::text("Forget")
+                    .on_press(Message::ConfirmLeave(room_id.clone(), true)),
+            );
+    }
+
     let mut col = widget::column().spacing(2);
     col = col.push(row);
 
@@ -0,0 +1,316 @@
+use cosmic::iced::widget::image::Handle as ImageHandle;
+use cosmic::iced::{Alignment, ContentFit, Length};
+use cosmic::prelude::*;
+use cosmic::widget;
+use matrix_sdk::ruma::events::room::history_visibility::HistoryVisibility;
+
+use crate::message::{Message, RoomAccess, RoomSettings, SettingsChange};
+use crate::state::room_settings::RoomSettingsState;
+
+const ACCESS_OPTIONS: [(RoomAccess, &str); 3] = [
+    (RoomAccess::Invite, "Private (invite only)"),
+    (RoomAccess::Knock, "Ask to join"),
+    (RoomAccess::Public, "Public"),
+];
+const ACCESS_LABELS: [&str; 3] = [ACCESS_OPTIONS[0].1, ACCESS_OPTIONS[1].1, ACCESS_OPTIONS[2].1];
+
+const HISTORY_LABELS: [&str; 4] = [
+    "Anyone",
+    "Members, all history",
+    "Members, since they were invited",
+    "Members, since they joined",
+];
+
+fn history_options() -> [HistoryVisibility; 4] {
+    [
+        HistoryVisibility::WorldReadable,
+        HistoryVisibility::Shared,
+        HistoryVisibility::Invited,
+        HistoryVisibility::Joined,
+    ]
+}
+
+/// Settings panel for the open room. Controls our power level doesn't allow are shown
+/// read-only.
+pub fn room_settings_view<'a>(
+    state: &'a RoomSettingsState,
+    avatar: Option<&'a ImageHandle>,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut col = widget::column().spacing(spacing.space_m).max_width(560.0);
+    col = col.push(
+        widget::row()
+            .push(widget::text::title3("Room settings"))
+            .push(widget::horizontal_space())
+            .push(widget::button::text("Close").on_press(Message::CloseRoomSettings))
+            .align_y(Alignment::Center),
+    );
+
+    match state.feedback {
+        Some(Ok(ref msg)) => col = col.push(widget::text::caption(msg.as_str())),
+        Some(Err(ref err)) => col = col.push(widget::text::body(err.as_str())),
+        None if state.saving > 0 => col = col.push(widget::text::caption("Saving…")),
+        None => {}
+    }
+
+    let Some(ref current) = state.current else {
+        col = col.push(widget::text::body("Loading…"));
+        return panel_container(col);
+    };
+
+    col = col
+        .push(general_section(state, current, avatar))
+        .push(addresses_section(state, current))
+        .push(access_section(current));
+
+    panel_container(widget::column().push(widget::scrollable(col).height(Length::Fill)))
+}
+
+/// Avatar, name and topic.
+fn general_section<'a>(
+    state: &'a RoomSettingsState,
+    current: &'a RoomSettings,
+    avatar: Option<&'a ImageHandle>,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let can = current.permissions;
+
+    let avatar_elem: Element<_> = if let Some(handle) = avatar {
+        cosmic::iced::widget::image(handle.clone())
+            .content_fit(ContentFit::Cover)
+            .width(Length::Fixed(64.0))
+            .height(Length::Fixed(64.0))
+            .into()
+    } else {
+        let letter = current.name.chars().next().unwrap_or('#').to_string();
+        widget::container(widget::text::title3(letter))
+            .width(Length::Fixed(64.0))
+            .height(Length::Fixed(64.0))
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into()
+    };
+    let mut change_avatar = widget::button::standard("Change avatar");
+    let mut remove_avatar = widget::button::standard("Remove");
+    if can.avatar {
+        change_avatar = change_avatar.on_press(Message::PickRoomAvatar);
+        if current.avatar_url.is_some() {
+            remove_avatar =
+                remove_avatar.on_press(Message::UpdateRoomSetting(SettingsChange::RemoveAvatar));
+        }
+    }
+
+    let mut name = widget::text_input("Room name", &state.name);
+    let mut save_name = widget::button::standard("Save");
+    if can.name {
+        name = name.on_input(Message::RoomSettingsNameChanged);
+        if state.name.trim() != current.name && !state.name.trim().is_empty() {
+            save_name = save_name.on_press(Message::UpdateRoomSetting(SettingsChange::Name(
+                state.name.trim().to_string(),
+            )));
+        }
+    }
+
+    let mut topic = widget::text_input("Topic", &state.topic);
+    let mut save_topic = widget::button::standard("Save");
+    if can.topic {
+        topic = topic.on_input(Message::RoomSettingsTopicChanged);
+        if state.topic.trim() != current.topic {
+            save_topic = save_topic.on_press(Message::UpdateRoomSetting(SettingsChange::Topic(
+                state.topic.trim().to_string(),
+            )));
+        }
+    }
+
+    widget::column()
+        .push(widget::text::heading("General"))
+        .push(
+            widget::row()
+                .push(avatar_elem)
+                .push(change_avatar)
+                .push(remove_avatar)
+                .spacing(spacing.space_xs)
+                .align_y(Alignment::Center),
+        )
+        .push(
+            widget::row()
+                .push(name)
+                .push(save_name)
+                .spacing(spacing.space_xs)
+                .align_y(Alignment::Center),
+        )
+        .push(
+            widget::row()
+                .push(topic)
+                .push(save_topic)
+                .spacing(spacing.space_xs)
+                .align_y(Alignment::Center),
+        )
+        .spacing(spacing.space_xs)
+        .into()
+}
+
+/// Published addresses (`m.room.canonical_alias`) and local ones in our homeserver's
+/// room directory.
+fn addresses_section<'a>(
+    state: &'a RoomSettingsState,
+    current: &'a RoomSettings,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let can_publish = current.permissions.aliases;
+
+    let mut col = widget::column()
+        .spacing(spacing.space_xs)
+        .push(widget::text::heading("Published addresses"));
+
+    let published = current.canonical_alias.iter().chain(&current.alt_aliases);
+    let mut any_published = false;
+    for alias in published {
+        any_published = true;
+        let is_main = current.canonical_alias.as_ref() == Some(alias);
+        let mut row = widget::row()
+            .push(widget::text::body(alias.to_string()))
+            .spacing(spacing.space_xs)
+            .align_y(Alignment::Center);
+        if is_main {
+            row = row.push(widget::text::caption("Main address"));
+        }
+        row = row.push(widget::horizontal_space());
+        if can_publish {
+            if !is_main {
+                if let Some(change) = state.make_main_alias(alias) {
+                    row = row.push(
+                        widget::button::text("Make main")
+                            .on_press(Message::UpdateRoomSetting(change)),
+                    );
+                }
+            }
+            if let Some(change) = state.publish_alias(alias, false) {
+                row = row.push(
+                    widget::button::text("Unpublish").on_press(Message::UpdateRoomSetting(change)),
+                );
+            }
+        }
+        col = col.push(row);
+    }
+    if !any_published {
+        col = col.push(widget::text::caption("No published addresses"));
+    }
+
+    col = col.push(widget::text::heading("Local addresses"));
+    if let Some(ref err) = state.local_aliases_error {
+        col = col.push(widget::text::body(err.as_str()));
+    }
+    for alias in &state.local_aliases {
+        let is_published = current.canonical_alias.as_ref() == Some(alias)
+            || current.alt_aliases.contains(alias);
+        let mut row = widget::row()
+            .push(widget::text::body(alias.to_string()))
+            .push(widget::horizontal_space())
+            .spacing(spacing.space_xs)
+            .align_y(Alignment::Center);
+        if can_publish && !is_published {
+            if let Some(change) = state.publish_alias(alias, true) {
+                row = row.push(
+                    widget::button::text("Publish").on_press(Message::UpdateRoomSetting(change)),
+                );
+            }
+        }
+        row = row.push(widget::button::text("Delete").on_press(Message::UpdateRoomSetting(
+            SettingsChange::RemoveLocalAlias(alias.clone()),
+        )));
+        col = col.push(row);
+    }
+
+    let mut add = widget::button::standard("Add");
+    if !state.new_alias.trim().is_empty() {
+        add = add.on_press(Message::AddRoomAlias);
+    }
+    col = col.push(
+        widget::row()
+            .push(
+                widget::text_input("#new-address:server", &state.new_alias)
+                    .on_input(Message::RoomSettingsNewAliasChanged)
+                    .on_submit(|_| Message::AddRoomAlias),
+            )
+            .push(add)
+            .spacing(spacing.space_xs)
+            .align_y(Alignment::Center),
+    );
+
+    col.into()
+}
+
+/// Join rule, guest access, history visibility and encryption.
+fn access_section<'a>(current: &'a RoomSettings) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let can = current.permissions;
+
+    let access_index = ACCESS_OPTIONS.iter().position(|(a, _)| *a == current.access);
+    let access: Element<_> = if can.join_rule {
+        widget::dropdown(&ACCESS_LABELS, access_index, |i| {
+            Message::UpdateRoomSetting(SettingsChange::Access(ACCESS_OPTIONS[i].0))
+        })
+        .into()
+    } else {
+        let label = access_index.map_or("Restricted", |i| ACCESS_LABELS[i]);
+        widget::text::body(label).into()
+    };
+
+    let mut guests = widget::toggler(current.guest_access);
+    if can.guest_access {
+        guests = guests.on_toggle(|allowed| {
+            Message::UpdateRoomSetting(SettingsChange::GuestAccess(allowed))
+        });
+    }
+
+    let history_index = history_options()
+        .iter()
+        .position(|v| *v == current.history_visibility);
+    let history: Element<_> = if can.history_visibility {
+        widget::dropdown(&HISTORY_LABELS, history_index, |i| {
+            Message::UpdateRoomSetting(SettingsChange::HistoryVisibility(
+                history_options()[i].clone(),
+            ))
+        })
+        .into()
+    } else {
+        widget::text::body(history_index.map_or("Custom", |i| HISTORY_LABELS[i])).into()
+    };
+
+    let encryption: Element<_> = if current.encrypted {
+        widget::text::body("Enabled").into()
+    } else {
+        let mut enable = widget::button::standard("Enable");
+        if can.encryption {
+            enable = enable.on_press(Message::UpdateRoomSetting(SettingsChange::EnableEncryption));
+        }
+        enable.into()
+    };
+
+    let mut col = widget::column()
+        .spacing(spacing.space_xs)
+        .push(widget::text::heading("Access"))
+        .push(widget::settings::item("Who can join", access))
+        .push(widget::settings::item("Allow guests", guests))
+        .push(widget::settings::item("Who can read history", history))
+        .push(widget::settings::item("End-to-end encryption", encryption));
+    if !current.encrypted && can.encryption {
+        col = col.push(widget::text::caption(
+            "Once enabled, encryption can't be turned off for this room",
+        ));
+    }
+    col.into()
+}
+
+fn panel_container<'a>(col: widget::Column<'a, Message>) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    widget::container(col)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(Alignment::Center)
+        .padding(spacing.space_l)
+        .class(cosmic::theme::Container::Background)
+        .into()
+}
@@ -8,12 +8,15 @@ use cosmic::widget;
 use cosmic::widget::Id;
 
 use crate::message::{Message, ReplyContext, TimelineItem, TimelineMessage};
-use crate::state::timeline::TimelineState;
-use crate::ui::colors;
+use crate::state::timeline::{Devtools, EditHistory, TimelineState};
+use crate::ui::{colors, rich_text};
 
 pub static TIMELINE_SCROLLABLE_ID: LazyLock<Id> =
     LazyLock::new(|| Id::new("timeline"));
 
+/// Offered by the quick-reaction picker.
+const QUICK_REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🎉"];
+
 pub fn timeline_view<'a>(
     state: &'a TimelineState,
     images: &'a HashMap<String, ImageHandle>,
@@ -30,7 +33,7 @@ pub fn timeline_view<'a>(
                 .align_x(Alignment::Center)
                 .padding(spacing.space_s),
         );
-    } else if state.pagination_token.is_some() {
+    } else if !state.at_timeline_start && state.timeline.is_some() {
         col = col.push(
             widget::container(
                 widget::button::text("Load earlier messages")
@@ -51,8 +54,9 @@ pub fn timeline_view<'a>(
                 .align_y(Alignment::Center),
         );
     } else {
-        for item in &state.items {
-            col = col.push(render_timeline_item(item, images, avatars));
+        // Thread replies live in the thread panel, behind their root's reply count.
+        for item in state.items.iter().filter(|i| i.is_rendered()) {
+            col = col.push(render_timeline_item(item, state, images, avatars));
         }
     }
 
@@ -66,13 +70,14 @@ pub fn timeline_view<'a>(
 
 fn render_timeline_item<'a>(
     item: &'a TimelineItem,
+    state: &'a TimelineState,
     images: &'a HashMap<String, ImageHandle>,
     avatars: &'a HashMap<String, ImageHandle>,
 ) -> Element<'a, Message> {
     let spacing = cosmic::theme::spacing();
 
     match item {
-        TimelineItem::Message(msg) => render_message(msg, images, avatars),
+        TimelineItem::Message(msg) => render_message(msg, state, images, avatars),
         TimelineItem::DateSeparator(date) => {
             widget::container(
                 widget::row()
@@ -89,8 +94,8 @@ fn render_timeline_item<'a>(
             .width(Length::Fill)
             .into()
         }
-        TimelineItem::StateEvent(desc) => widget::container(
-            widget::text::caption(desc.clone())
+        TimelineItem::StateEvent { description, .. } => widget::container(
+            widget::text::caption(description.clone())
                 .width(Length::Fill),
         )
         .padding([spacing.space_xxs, spacing.space_s])
@@ -113,11 +118,326 @@ fn render_timeline_item<'a>(
             .width(Length::Fill)
             .into()
         }
+        TimelineItem::Hidden { .. } => widget::Space::new(Length::Shrink, Length::Shrink).into(),
+    }
+}
+
+/// "(edited)" link that opens the edit history.
+fn edited_marker<'a>(msg: &'a TimelineMessage) -> Option<Element<'a, Message>> {
+    if !msg.is_edited {
+        return None;
+    }
+    let spacing = cosmic::theme::spacing();
+    Some(
+        widget::button::text("(edited)")
+            .on_press(Message::ShowEditHistory(msg.event_id.clone()))
+            .padding([0, spacing.space_xxs])
+            .into(),
+    )
+}
+
+/// Edit (own messages only), delete (power-level gated) and reply buttons.
+fn message_actions<'a>(msg: &'a TimelineMessage, can_redact: bool) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let mut row = widget::row().spacing(spacing.space_xxs);
+    if msg.redaction.is_some() {
+        return row.into();
+    }
+    if msg.can_edit && !msg.event_id.is_empty() && msg.image.is_none() {
+        row = row.push(
+            widget::button::text("✎")
+                .on_press(Message::StartEdit {
+                    event_id: msg.event_id.clone(),
+                    body: msg.edit_text(),
+                })
+                .padding([0, spacing.space_xxs]),
+        );
+    }
+    if !msg.event_id.is_empty() && msg.thread_root.is_none() {
+        row = row.push(
+            widget::button::text("💬")
+                .on_press(Message::OpenThread(msg.event_id.clone()))
+                .padding([0, spacing.space_xxs]),
+        );
+    }
+    if !msg.event_id.is_empty() {
+        row = row.push(
+            widget::button::text("☺")
+                .on_press(Message::ToggleReactionPicker(msg.event_id.clone()))
+                .padding([0, spacing.space_xxs]),
+        );
+    }
+    if can_redact {
+        row = row.push(
+            widget::button::text("🗑")
+                .on_press(Message::RedactMessage(msg.event_id.clone()))
+                .padding([0, spacing.space_xxs]),
+        );
+    }
+    let reply_ctx = ReplyContext {
+        event_id: msg.event_id.clone(),
+        sender_id: msg.sender.clone(),
+        sender_display: msg.sender_display.clone(),
+        body_preview: msg.body.chars().take(80).collect(),
+    };
+    row.push(
+        widget::button::text("↩")
+            .on_press(Message::ReplyTo(reply_ctx))
+            .padding([0, spacing.space_xxs]),
+    )
+    .into()
+}
+
+/// Reaction chips (emoji + count, reactors on hover); clicking toggles our own reaction.
+fn reactions_row<'a>(msg: &'a TimelineMessage) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let mut row = widget::row().spacing(spacing.space_xxs);
+    for group in &msg.reactions {
+        let label = format!("{} {}", group.key, group.count);
+        let chip = if group.reacted_by_me {
+            widget::button::suggested(label)
+        } else {
+            widget::button::standard(label)
+        }
+        .on_press(Message::ToggleReaction {
+            event_id: msg.event_id.clone(),
+            key: group.key.clone(),
+        })
+        .padding([0, spacing.space_xxs]);
+
+        let reactors = group
+            .senders
+            .iter()
+            .map(|s| {
+                s.strip_prefix('@')
+                    .and_then(|s| s.split(':').next())
+                    .unwrap_or(s.as_str())
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        row = row.push(widget::tooltip(
+            chip,
+            widget::text::caption(reactors),
+            widget::tooltip::Position::Top,
+        ));
+    }
+    row.into()
+}
+
+/// Small avatars of the members who have read up to this message, right-aligned,
+/// with the full "Seen by" list on hover.
+fn read_receipts_row<'a>(
+    msg: &'a TimelineMessage,
+    state: &'a TimelineState,
+    avatars: &'a HashMap<String, ImageHandle>,
+) -> Element<'a, Message> {
+    const MAX_AVATARS: usize = 5;
+    const SIZE: f32 = 16.0;
+
+    let readers: Vec<(&str, Option<&ImageHandle>)> = msg
+        .read_by
+        .iter()
+        .map(|user_id| {
+            let member = state.members.iter().find(|m| m.user_id.as_str() == user_id);
+            let name = member.map_or(user_id.as_str(), |m| m.display_name.as_str());
+            let avatar = member
+                .and_then(|m| m.avatar_url.as_ref())
+                .and_then(|url| avatars.get(url));
+            (name, avatar)
+        })
+        .collect();
+
+    let mut faces = widget::row().spacing(2).align_y(Alignment::Center);
+    for (i, (name, avatar)) in readers.iter().take(MAX_AVATARS).enumerate() {
+        let face: Element<_> = match avatar {
+            Some(handle) => cosmic::iced::widget::image((*handle).clone())
+                .width(Length::Fixed(SIZE))
+                .height(Length::Fixed(SIZE))
+                .into(),
+            None => {
+                let initial = name.chars().next().unwrap_or('?').to_uppercase().to_string();
+                widget::container(
+                    widget::text::caption(initial).class(colors::sender_color(&msg.read_by[i])),
+                )
+                .width(Length::Fixed(SIZE))
+                .height(Length::Fixed(SIZE))
+                .align_x(Alignment::Center)
+                .align_y(Alignment::Center)
+                .into()
+            }
+        };
+        faces = faces.push(face);
     }
+    if readers.len() > MAX_AVATARS {
+        faces = faces.push(widget::text::caption(format!("+{}", readers.len() - MAX_AVATARS)));
+    }
+
+    let names = readers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+    widget::row()
+        .push(widget::horizontal_space())
+        .push(widget::tooltip(
+            faces,
+            widget::text::caption(format!("Seen by {names}")),
+            widget::tooltip::Position::Top,
+        ))
+        .into()
+}
+
+/// Row of quick reactions shown under a message while its picker is open.
+fn reaction_picker<'a>(msg: &'a TimelineMessage) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let mut row = widget::row().spacing(spacing.space_xxs);
+    for key in QUICK_REACTIONS {
+        row = row.push(
+            widget::button::text(key)
+                .on_press(Message::ToggleReaction {
+                    event_id: msg.event_id.clone(),
+                    key: key.to_string(),
+                })
+                .padding([0, spacing.space_xxs]),
+        );
+    }
+    row.into()
+}
+
+/// Reply count for a thread root: the server's bundled count, or the replies we have
+/// loaded if that is higher (the bundled count isn't updated live).
+fn thread_reply_count(msg: &TimelineMessage, state: &TimelineState) -> u64 {
+    if msg.event_id.is_empty() {
+        return 0;
+    }
+    let loaded = state
+        .items
+        .iter()
+        .filter(|item| {
+            matches!(item, TimelineItem::Message(m) if m.thread_root.as_deref() == Some(msg.event_id.as_str()))
+        })
+        .count() as u64;
+    msg.thread_reply_count.max(loaded)
+}
+
+/// Side panel with a thread's root and replies.
+pub fn thread_panel_view<'a>(
+    state: &'a TimelineState,
+    images: &'a HashMap<String, ImageHandle>,
+    avatars: &'a HashMap<String, ImageHandle>,
+) -> Element<'a, Message> {
+    let spacing = cosmic::theme::spacing();
+    let Some(ref thread) = state.thread else {
+        return widget::Space::new(Length::Shrink, Length::Shrink).into();
+    };
+
+    let mut col = widget::column().spacing(spacing.space_xxs);
+    for item in &thread.items {
+        col = col.push(render_timeline_item(item, state, images, avatars));
+    }
+    if thread.loading {
+        col = col.push(
+            widget::container(widget::text::caption("Loading replies..."))
+                .width(Length::Fill)
+                .align_x(Alignment::Center)
+                .padding(spacing.space_xs),
+        );
+    }
+
+    widget::column()
+        .push(
+            widget::row()
+                .push(widget::text::heading("Thread"))
+                .push(widget::horizontal_space())
+                .push(
+                    widget::button::text("×")
+                        .on_press(Message::CloseThread)
+                        .padding([0, spacing.space_xxs]),
+                )
+                .align_y(Alignment::Center)
+                .padding([spacing.space_xs, spacing.space_s]),
+        )
+        .push(widget::divider::horizontal::default())
+        .push(widget::scrollable(col).height(Length::Fill))
+        .width(Length::Fixed(360.0))
+        .height(Length::Fill)
+        .into()
+}
+
+/// Every version of an edited message, oldest first.
+pub fn edit_history_view(history: &EditHistory) -> Element<'_, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut col = widget::column().spacing(spacing.space_s);
+    col = col.push(
+        widget::row()
+            .push(widget::text::heading("Edit history"))
+            .push(widget::horizontal_space())
+            .push(widget::button::text("Close").on_press(Message::CloseEditHistory))
+            .align_y(Alignment::Center),
+    );
+
+    if history.loading {
+        col = col.push(widget::text::body("Loading..."));
+    } else if let Some(ref err) = history.error {
+        col = col.push(widget::text::body(err.as_str()));
+    } else if history.entries.is_empty() {
+        col = col.push(widget::text::body("No earlier versions found"));
+    }
+
+    for (i, entry) in history.entries.iter().enumerate() {
+        let label = if i == 0 { "Original" } else { "Edited" };
+        col = col.push(
+            widget::column()
+                .push(widget::text::caption(format!("{label} · {}", entry.timestamp)))
+                .push(widget::text::body(entry.body.as_str()))
+                .spacing(2),
+        );
+    }
+
+    widget::scrollable(
+        widget::container(col)
+            .padding(spacing.space_m)
+            .width(Length::Fill),
+    )
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .into()
+}
+
+pub fn devtools_view(devtools: &Devtools) -> Element<'_, Message> {
+    let spacing = cosmic::theme::spacing();
+
+    let mut col = widget::column().spacing(spacing.space_s);
+    col = col.push(
+        widget::row()
+            .push(widget::text::heading("Room state"))
+            .push(widget::horizontal_space())
+            .push(
+                widget::button::text("Copy")
+                    .on_press(Message::CopyToClipboard(devtools.text.clone())),
+            )
+            .push(widget::button::text("Close").on_press(Message::CloseDevtools))
+            .align_y(Alignment::Center),
+    );
+
+    if devtools.loading {
+        col = col.push(widget::text::body("Loading..."));
+    } else if let Some(ref err) = devtools.error {
+        col = col.push(widget::text::body(err.as_str()));
+    } else {
+        col = col.push(widget::text::monotext(devtools.text.as_str()));
+    }
+
+    widget::scrollable(
+        widget::container(col)
+            .padding(spacing.space_m)
+            .width(Length::Fill),
+    )
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .into()
 }
 
 fn render_message<'a>(
     msg: &'a TimelineMessage,
+    state: &'a TimelineState,
     images: &'a HashMap<String, ImageHandle>,
     avatars: &'a HashMap<String, ImageHandle>,
 ) -> Element<'a, Message> {
@@ -211,41 +531,41 @@ fn render_message<'a>(
                     .class(sender_col),
             );
         }
-        header = header.push(widget::text::caption(msg.timestamp.clone()));
+        if msg.is_local_echo {
+            header = header.push(widget::text::caption("Sending…"));
+        } else {
+            header = header.push(widget::text::caption(msg.timestamp.clone()));
+        }
+        if let Some(edited) = edited_marker(msg) {
+            header = header.push(edited);
+        }
         header = header.push(widget::horizontal_space());
-        let reply_ctx = ReplyContext {
-            event_id: msg.event_id.clone(),
-            sender_id: msg.sender.clone(),
-            sender_display: msg.sender_display.clone(),
-            body_preview: msg.body.chars().take(80).collect(),
-        };
-        header = header.push(
-            widget::button::text("↩")
-                .on_press(Message::ReplyTo(reply_ctx))
-                .padding([0, spacing.space_xxs]),
-        );
-        col = col.push(header);
+        col = col.push(header.push(message_actions(msg, state.can_redact(msg))));
     } else {
-        // Continuation — still show the reply button
-        let reply_ctx = ReplyContext {
-            event_id: msg.event_id.clone(),
-            sender_id: msg.sender.clone(),
-            sender_display: msg.sender_display.clone(),
-            body_preview: msg.body.chars().take(80).collect(),
-        };
+        // Continuation — still show the edited marker and actions
+        let mut row = widget::row();
+        if let Some(edited) = edited_marker(msg) {
+            row = row.push(edited);
+        }
         col = col.push(
-            widget::row()
-                .push(widget::horizontal_space())
-                .push(
-                    widget::button::text("↩")
-                        .on_press(Message::ReplyTo(reply_ctx))
-                        .padding([0, spacing.space_xxs]),
-                ),
+            row.push(widget::horizontal_space())
+                .push(message_actions(msg, state.can_redact(msg))),
         );
     }
 
-    // Render image or text body
-    if msg.image.is_some() {
+    // Render deletion placeholder, image or text body
+    if let Some(ref redaction) = msg.redaction {
+        let mut text = "🗑 Message deleted".to_string();
+        if let Some(ref by) = redaction.redacted_by {
+            if by != &msg.sender {
+                text.push_str(&format!(" by {by}"));
+            }
+        }
+        if let Some(ref reason) = redaction.reason {
+            text.push_str(&format!(" ({reason})"));
+        }
+        col = col.push(widget::text::caption(text));
+    } else if msg.image.is_some() {
         if let Some(handle) = images.get(&msg.event_id) {
             col = col.push(
                 cosmic::iced::widget::image(handle.clone())
@@ -258,10 +578,35 @@ fn render_message<'a>(
         if !msg.body.is_empty() {
             col = col.push(widget::text::caption(msg.body.as_str()));
         }
+    } else if let Some(ref blocks) = msg.formatted {
+        col = col.push(rich_text::rich_blocks_view(blocks));
     } else {
         col = col.push(widget::text::body(msg.body.clone()));
     }
 
+    if !msg.reactions.is_empty() && msg.redaction.is_none() {
+        col = col.push(reactions_row(msg));
+    }
+    let reply_count = thread_reply_count(msg, state);
+    if reply_count > 0 && msg.thread_root.is_none() {
+        let label = if reply_count == 1 {
+            "💬 1 reply".to_string()
+        } else {
+            format!("💬 {reply_count} replies")
+        };
+        col = col.push(
+            widget::button::text(label)
+                .on_press(Message::OpenThread(msg.event_id.clone()))
+                .padding([0, spacing.space_xxs]),
+        );
+    }
+    if state.reaction_picker.as_deref() == Some(msg.event_id.as_str()) {
+        col = col.push(reaction_picker(msg));
+    }
+    if !msg.read_by.is_empty() {
+        col = col.push(read_receipts_row(msg, state, avatars));
+    }
+
     let top_pad = if msg.is_continuation && msg.reply_to_sender.is_none() {
         1
     } else {